@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Weak, Mutex};
+use std::fmt;
+
+use super::{AssetRef, AsyncLoader, Loader, LoadError, DEFAULT_PRIORITY};
+
+struct Slot<T> {
+    asset_ref: AssetRef,
+    value: Mutex<Option<Arc<T>>>,
+}
+
+/// A shared reference to a cached asset.
+///
+/// Handles are cheap to clone, the asset is unloaded from the cache when the last one is dropped.
+pub struct Handle<T> {
+    slot: Arc<Slot<T>>,
+}
+
+impl<T> Handle<T> {
+    pub fn asset_ref(&self) -> &AssetRef {
+        &self.slot.asset_ref
+    }
+
+    /// Returns the asset, or `None` while it is still loading.
+    pub fn get(&self) -> Option<Arc<T>> {
+        self.slot.value.lock().unwrap().clone()
+    }
+
+    pub fn is_loaded(&self) -> bool {
+        self.slot.value.lock().unwrap().is_some()
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle { slot: self.slot.clone() }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.slot, &other.slot)
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Handle")
+            .field("asset_ref", &self.slot.asset_ref)
+            .field("loaded", &self.is_loaded())
+            .finish()
+    }
+}
+
+/// Caches the outputs of a loader behind reference counted handles.
+///
+/// Requesting an asset that is already cached or loading shares its handle
+/// instead of loading it again.
+pub struct AssetCache<A: Loader> {
+    loader: AsyncLoader<A>,
+    slots: HashMap<AssetRef, Weak<Slot<A::Output>>>,
+}
+
+impl<A: Loader> AssetCache<A> {
+    pub fn new(loader: AsyncLoader<A>) -> Self {
+        AssetCache {
+            loader: loader,
+            slots: HashMap::new(),
+        }
+    }
+
+    /// Returns a handle to the asset, loading it if needed.
+    pub fn request(&mut self, asset_ref: &AssetRef) -> Handle<A::Output> {
+        self.request_with_priority(asset_ref, DEFAULT_PRIORITY)
+    }
+
+    /// Returns a handle to the asset, loading it with `priority` if needed.
+    ///
+    /// See `AsyncLoader::load_with_priority`.
+    pub fn request_with_priority(&mut self, asset_ref: &AssetRef, priority: i32)
+        -> Handle<A::Output> {
+
+        if let Some(slot) = self.slots.get(asset_ref).and_then(Weak::upgrade) {
+            let handle = Handle { slot: slot };
+            if !handle.is_loaded() {
+                self.loader.load_with_priority(asset_ref, priority);
+            }
+
+            return handle;
+        }
+
+        let slot = Arc::new(Slot {
+            asset_ref: asset_ref.clone(),
+            value: Mutex::new(None),
+        });
+
+        self.slots.insert(asset_ref.clone(), Arc::downgrade(&slot));
+        self.loader.load_with_priority(asset_ref, priority);
+
+        Handle { slot: slot }
+    }
+
+    /// Returns a handle to the asset if it is cached or loading.
+    pub fn get(&self, asset_ref: &AssetRef) -> Option<Handle<A::Output>> {
+        self.slots
+            .get(asset_ref)
+            .and_then(Weak::upgrade)
+            .map(|slot| Handle { slot: slot })
+    }
+
+    /// Returns the number of handles to the asset.
+    pub fn handle_count(&self, asset_ref: &AssetRef) -> usize {
+        // The upgraded slot is not a handle
+        self.slots
+            .get(asset_ref)
+            .and_then(Weak::upgrade)
+            .map(|slot| Arc::strong_count(&slot) - 1)
+            .unwrap_or(0)
+    }
+
+    /// Returns the number of assets that are cached or loading.
+    pub fn len(&self) -> usize {
+        self.slots.values().filter(|slot| slot.upgrade().is_some()).count()
+    }
+
+    /// Stores the loaded assets in their handles and forgets the unloaded ones.
+    ///
+    /// Reloaded assets replace the previous value of their handles, the values
+    /// already obtained with `Handle::get` are not affected.
+    /// Returns the assets that could not be loaded or reloaded.
+    pub fn update(&mut self) -> Vec<(AssetRef, LoadError<A::Error>)> {
+        let mut errors = Vec::new();
+        let ready_assets: Vec<_> = self.loader.ready_assets().collect();
+
+        for ready in ready_assets {
+            let asset_ref = ready.asset_ref;
+            let slot = self.slots.get(&asset_ref).and_then(Weak::upgrade);
+
+            match (slot, ready.result) {
+                (Some(slot), Ok(value)) => *slot.value.lock().unwrap() = Some(Arc::new(value)),
+                (Some(_), Err(error)) => errors.push((asset_ref, error)),
+                // Every handle was dropped while loading, the slot may already be forgotten
+                (None, _) => Self::release(&mut self.loader, &asset_ref),
+            }
+        }
+
+        let loader = &mut self.loader;
+        self.slots.retain(|asset_ref, slot| {
+            let alive = slot.upgrade().is_some();
+            if !alive {
+                Self::release(loader, asset_ref);
+            }
+
+            alive
+        });
+
+        errors
+    }
+
+    /// Forgets an asset that has no handle left, whether it was loaded or not.
+    fn release(loader: &mut AsyncLoader<A>, asset_ref: &AssetRef) {
+        if !loader.unload(asset_ref) {
+            loader.clear_failure(asset_ref);
+        }
+    }
+
+    pub fn loader(&self) -> &AsyncLoader<A> {
+        &self.loader
+    }
+
+    /// Gives access to the loader, for example to enable hot reload.
+    ///
+    /// Assets loaded directly through it are not cached.
+    pub fn loader_mut(&mut self) -> &mut AsyncLoader<A> {
+        &mut self.loader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::Duration;
+    use {AssetRef, AssetFile, Loader, MemoryMount, Initializer};
+
+    /// Loads text files, once the gate is open.
+    struct GatedLoader(Arc<Mutex<()>>);
+
+    impl Loader for GatedLoader {
+        type Output = String;
+        type Error = io::Error;
+
+        fn load(&mut self, file: &mut AssetFile) -> Result<String, io::Error> {
+            let _open = self.0.lock().unwrap();
+
+            let mut text = String::new();
+            try!(file.read_to_string(&mut text));
+            Ok(text)
+        }
+    }
+
+    #[test]
+    fn test_handle_dropped_while_loading() {
+        let gate = Arc::new(Mutex::new(()));
+        let closed = gate.lock().unwrap();
+
+        let mut mount = MemoryMount::new();
+        mount.insert("hello.txt", "hello");
+        let mut initializer = Initializer::new();
+        initializer.mount("", mount);
+        let mut cache = initializer.create_asset_cache(GatedLoader(gate.clone()));
+        initializer.done();
+
+        let asset_ref = AssetRef::new("hello.txt");
+        let handle = cache.request(&asset_ref);
+        assert_eq!(cache.handle_count(&asset_ref), 1);
+        drop(handle);
+
+        // The slot is forgotten before the asset arrives
+        assert!(cache.update().is_empty());
+        assert_eq!(cache.len(), 0);
+        drop(closed);
+
+        for _ in 0..200 {
+            if cache.loader().status(&asset_ref).is_none() {
+                break;
+            }
+
+            thread::sleep(Duration::from_millis(5));
+            cache.update();
+        }
+        assert!(cache.loader().status(&asset_ref).is_none());
+        assert!(cache.get(&asset_ref).is_none());
+    }
+
+    #[test]
+    fn test_shared_handles() {
+        let mut mount = MemoryMount::new();
+        mount.insert("hello.txt", "hello");
+        let mut initializer = Initializer::new();
+        initializer.mount("", mount);
+        let mut cache = initializer.create_asset_cache(GatedLoader(Arc::new(Mutex::new(()))));
+        initializer.done();
+
+        let asset_ref = AssetRef::new("hello.txt");
+        let first = cache.request(&asset_ref);
+        let second = cache.request(&asset_ref);
+        assert_eq!(first, second);
+        assert_eq!(cache.handle_count(&asset_ref), 2);
+
+        while !first.is_loaded() {
+            thread::sleep(Duration::from_millis(1));
+            assert!(cache.update().is_empty());
+        }
+        assert_eq!(second.get().map(|text| (*text).clone()), Some("hello".to_string()));
+    }
+}
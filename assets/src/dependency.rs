@@ -0,0 +1,70 @@
+//! Assets needed to load other assets
+//!
+//! A loader lists the dependencies of an asset in `Loader::dependencies`, they
+//! are loaded by their own loaders and their outputs are handed to
+//! `Loader::load_with_dependencies`. Each dependent asset gets its own copy of
+//! its dependencies.
+
+use std::any::Any;
+use std::collections::HashMap;
+
+use request::Token;
+use super::{AssetRef, Loader};
+
+/// An asset loaded by the loader `L` before the asset depending on it.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Dependency {
+    pub(crate) token: Token,
+    pub(crate) asset_ref: AssetRef,
+}
+
+impl Dependency {
+    pub fn new<L: Loader>(asset_ref: AssetRef) -> Self {
+        Dependency {
+            token: Token::of::<L>(),
+            asset_ref: asset_ref,
+        }
+    }
+
+    pub fn asset_ref(&self) -> &AssetRef {
+        &self.asset_ref
+    }
+}
+
+/// The loaded outputs of the dependencies of an asset.
+pub struct Dependencies {
+    outputs: HashMap<Dependency, Box<Any + Send>>,
+}
+
+impl Dependencies {
+    pub(crate) fn new() -> Self {
+        Dependencies { outputs: HashMap::new() }
+    }
+
+    pub(crate) fn insert(&mut self, dependency: Dependency, output: Box<Any + Send>) {
+        self.outputs.insert(dependency, output);
+    }
+
+    /// Returns the output of a dependency loaded by `L`.
+    pub fn get<L: Loader>(&self, asset_ref: &AssetRef) -> Option<&L::Output> {
+        self.outputs
+            .get(&Dependency::new::<L>(asset_ref.clone()))
+            .and_then(|output| output.downcast_ref())
+    }
+
+    /// Moves the output of a dependency loaded by `L` out.
+    pub fn take<L: Loader>(&mut self, asset_ref: &AssetRef) -> Option<L::Output> {
+        self.outputs
+            .remove(&Dependency::new::<L>(asset_ref.clone()))
+            .and_then(|output| output.downcast().ok())
+            .map(|output| *output)
+    }
+
+    pub fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+}
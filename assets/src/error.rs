@@ -0,0 +1,74 @@
+use std::error::Error;
+use std::fmt;
+
+use super::AssetRef;
+
+/// The reason an asset could not be loaded.
+#[derive(Debug)]
+pub enum LoadError<E> {
+    /// The loader of the asset failed.
+    Loader(E),
+    /// A dependency of the asset could not be loaded.
+    Dependency {
+        asset_ref: AssetRef,
+        error: Box<Error + Send + Sync>,
+    },
+    /// The asset depends on itself, through this dependency.
+    CyclicDependency(AssetRef),
+    /// The loader of this dependency was never created, or was dropped.
+    MissingLoader(AssetRef),
+}
+
+impl<E> LoadError<E> {
+    pub(crate) fn map_loader<F, G>(self, f: F) -> LoadError<G>
+        where F: FnOnce(E) -> G
+    {
+        match self {
+            LoadError::Loader(error) => LoadError::Loader(f(error)),
+            LoadError::Dependency { asset_ref, error } => {
+                LoadError::Dependency {
+                    asset_ref: asset_ref,
+                    error: error,
+                }
+            }
+            LoadError::CyclicDependency(asset_ref) => LoadError::CyclicDependency(asset_ref),
+            LoadError::MissingLoader(asset_ref) => LoadError::MissingLoader(asset_ref),
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for LoadError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &LoadError::Loader(ref error) => error.fmt(f),
+            &LoadError::Dependency { ref asset_ref, ref error } => {
+                write!(f, "dependency `{}` failed: {}", asset_ref.path(), error)
+            }
+            &LoadError::CyclicDependency(ref asset_ref) => {
+                write!(f, "cyclic dependency on `{}`", asset_ref.path())
+            }
+            &LoadError::MissingLoader(ref asset_ref) => {
+                write!(f, "no loader for the dependency `{}`", asset_ref.path())
+            }
+        }
+    }
+}
+
+impl<E: Error> Error for LoadError<E> {
+    fn description(&self) -> &str {
+        match self {
+            &LoadError::Loader(ref error) => error.description(),
+            &LoadError::Dependency { .. } => "dependency failed",
+            &LoadError::CyclicDependency(_) => "cyclic dependency",
+            &LoadError::MissingLoader(_) => "no loader for a dependency",
+        }
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        match self {
+            &LoadError::Loader(ref error) => Some(error),
+            &LoadError::Dependency { ref error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
@@ -1,20 +1,50 @@
+#![feature(pub_restricted)]
 #![cfg_attr(feature = "eders", feature(plugin, custom_derive))]
 #![cfg_attr(feature = "eders", plugin(serde_macros))]
 
 #[cfg(feature = "eders")]
 extern crate serde;
 extern crate crossbeam;
+#[cfg(feature = "zip")]
+extern crate zip;
+#[cfg(feature = "tar")]
+extern crate tar;
 
 mod request;
+pub mod cache;
+pub mod dependency;
+pub mod error;
+pub mod retry;
+pub mod vfs;
+pub mod watch;
 
-use std::io;
 use std::any::Any;
-use std::collections::HashMap;
+use std::error::Error;
+use std::collections::{hash_map, HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use std::thread;
 use std::sync::atomic::{AtomicBool, ATOMIC_BOOL_INIT, Ordering};
 use request::*;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use crossbeam::sync::MsQueue;
 
+/// The priority of `AsyncLoader::load` requests.
+pub const DEFAULT_PRIORITY: i32 = 0;
+/// The number of loading threads, unless changed with `Initializer::set_worker_count`.
+pub const DEFAULT_WORKER_COUNT: usize = 4;
+
+pub use cache::{AssetCache, Handle};
+pub use dependency::{Dependency, Dependencies};
+pub use error::LoadError;
+pub use retry::{RetryPolicy, Failure};
+pub use vfs::{Vfs, Mount, DirectoryMount, MemoryMount, AssetFile};
+#[cfg(feature = "zip")]
+pub use vfs::ZipMount;
+#[cfg(feature = "tar")]
+pub use vfs::TarMount;
+pub use watch::FileWatcher;
+
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
 #[cfg_attr(feature = "eders", derive(Serialize, Deserialize))]
 pub struct AssetRef(String);
@@ -29,25 +59,102 @@ impl AssetRef {
     }
 }
 
-pub type LoaderResult<T> = Result<T, io::Error>;
+pub type LoaderResult<T, E> = Result<T, LoadError<E>>;
 
 pub trait Loader: Any + Send {
-    type Output: Send;
+    type Output: Send + 'static;
+    type Error: Error + Send + Sync + 'static;
+
+    fn load(&mut self, file: &mut AssetFile) -> Result<Self::Output, Self::Error>;
+
+    /// Returns the assets that must be loaded before `file`.
+    ///
+    /// Their outputs are given to `load_with_dependencies`.
+    fn dependencies(&mut self, _file: &mut AssetFile) -> Result<Vec<Dependency>, Self::Error> {
+        Ok(Vec::new())
+    }
+
+    /// Loads `file` once its dependencies are loaded, calls `load` by default.
+    fn load_with_dependencies(&mut self, file: &mut AssetFile, _dependencies: &mut Dependencies)
+        -> Result<Self::Output, Self::Error> {
 
-    fn load(&mut self, path: &str) -> LoaderResult<Self::Output>;
+        self.load(file)
+    }
+
+    /// Returns false if loading the asset again cannot fix the error.
+    fn is_retryable(_error: &Self::Error) -> bool {
+        true
+    }
 }
 
 pub enum Status {
     Loading,
+    /// The asset and all of its dependencies are loaded.
     Available,
+    /// The asset could not be loaded, see `AsyncLoader::failure`.
+    Failed,
 }
 
 type StatusMap = HashMap<AssetRef, Status>;
 
+/// An asset that finished loading.
+pub struct ReadyAsset<T, E> {
+    pub asset_ref: AssetRef,
+    pub result: LoaderResult<T, E>,
+    /// True if the asset was available and has been loaded again.
+    pub reloaded: bool,
+}
+
+struct HotReload {
+    watcher: FileWatcher,
+    /// The assets to reload when a file changes, the file of an asset or of a dependency.
+    assets: HashMap<PathBuf, HashSet<AssetRef>>,
+}
+
+impl HotReload {
+    fn watch(&mut self, vfs: &Vfs, asset_ref: &AssetRef, dependencies: &[AssetRef]) {
+        for file in Some(asset_ref).into_iter().chain(dependencies) {
+            // Only files on disk can change
+            let path = match vfs.real_path(file.path()) {
+                Some(path) => path,
+                None => continue,
+            };
+
+            if !self.watcher.is_watching(&path) {
+                self.watcher.watch(path.clone());
+            }
+            self.assets.entry(path).or_insert_with(HashSet::new).insert(asset_ref.clone());
+        }
+    }
+
+    fn unwatch(&mut self, asset_ref: &AssetRef) {
+        let watcher = &mut self.watcher;
+
+        self.assets.retain(|path, assets| {
+            assets.remove(asset_ref);
+            if assets.is_empty() {
+                watcher.unwatch(path);
+            }
+
+            !assets.is_empty()
+        });
+    }
+}
+
 pub struct AsyncLoader<A: Loader> {
     queue: RequestQueue<A>,
     status: StatusMap,
-    pending_requests: u32
+    pending_requests: u32,
+    /// The dependency trees of the available assets.
+    dependencies: HashMap<AssetRef, Vec<AssetRef>>,
+    reloading: HashSet<AssetRef>,
+    hot_reload: Option<HotReload>,
+    retry_policy: RetryPolicy,
+    /// The number of failed loads of the assets being loaded.
+    attempts: HashMap<AssetRef, u32>,
+    /// The failed loads to send again with their priority, they are still pending.
+    retries: Vec<(Instant, AssetRef, i32)>,
+    failures: HashMap<AssetRef, Failure>,
 }
 
 impl<A: Loader> AsyncLoader<A> {
@@ -55,35 +162,167 @@ impl<A: Loader> AsyncLoader<A> {
         AsyncLoader {
             queue: queue,
             status: StatusMap::new(),
-            pending_requests: 0
+            pending_requests: 0,
+            dependencies: HashMap::new(),
+            reloading: HashSet::new(),
+            hot_reload: None,
+            retry_policy: RetryPolicy::never(),
+            attempts: HashMap::new(),
+            retries: Vec::new(),
+            failures: HashMap::new(),
         }
     }
 
     pub fn load(&mut self, assert_ref: &AssetRef) {
+        self.load_with_priority(assert_ref, DEFAULT_PRIORITY);
+    }
+
+    /// Loads an asset before the queued requests of lower priority.
+    ///
+    /// Raises the priority of the request if the asset is already loading,
+    /// and loads a failed asset again.
+    pub fn load_with_priority(&mut self, assert_ref: &AssetRef, priority: i32) {
         use std::collections::hash_map::Entry;
 
         match self.status.entry(assert_ref.clone()) {
             Entry::Vacant(vacant) => {
                 vacant.insert(Status::Loading);
-                self.queue.send_request(assert_ref.path());
+                self.queue.send_request(assert_ref.path(), priority);
                 self.pending_requests += 1;
             }
             Entry::Occupied(occupied) => {
                 let status = occupied.into_mut();
 
-                if let Status::Available = *status {
-                    self.queue.send_request(assert_ref.path());
-                    self.pending_requests += 1;
+                match *status {
+                    Status::Available => {
+                        self.queue.send_request(assert_ref.path(), priority);
+                        self.pending_requests += 1;
+                    }
+                    Status::Loading => self.queue.raise_priority(assert_ref.path(), priority),
+                    Status::Failed => {
+                        *status = Status::Loading;
+                        self.failures.remove(assert_ref);
+                        self.queue.send_request(assert_ref.path(), priority);
+                        self.pending_requests += 1;
+                    }
                 }
             },
         }
     }
 
+    /// Loads an available asset again, it will be reported as reloaded.
+    ///
+    /// Returns false if the asset is not available.
+    pub fn reload(&mut self, asset_ref: &AssetRef) -> bool {
+        match self.status.get(asset_ref) {
+            Some(&Status::Available) => {}
+            _ => return false,
+        }
+
+        self.queue.send_request(asset_ref.path(), DEFAULT_PRIORITY);
+        self.pending_requests += 1;
+        self.reloading.insert(asset_ref.clone());
+        true
+    }
+
+    /// Watches the files of the available assets, and reloads them when they change.
+    ///
+    /// Only the files mounted from a directory are watched, they are checked at most
+    /// once per `interval` when calling `ready_assets`.
+    pub fn enable_hot_reload(&mut self, interval: Duration) {
+        let mut hot_reload = HotReload {
+            watcher: FileWatcher::new(interval),
+            assets: HashMap::new(),
+        };
+
+        {
+            let vfs = self.queue.vfs().read().unwrap();
+            for (asset_ref, status) in &self.status {
+                if let &Status::Available = status {
+                    let dependencies = self.dependencies(asset_ref).unwrap_or(&[]);
+                    hot_reload.watch(&vfs, asset_ref, dependencies);
+                }
+            }
+        }
+
+        self.hot_reload = Some(hot_reload);
+    }
+
+    pub fn disable_hot_reload(&mut self) {
+        self.hot_reload = None;
+    }
+
+    pub fn is_hot_reload_enabled(&self) -> bool {
+        self.hot_reload.is_some()
+    }
+
     pub fn status(&self, asset_ref: &AssetRef) -> Option<&Status> {
         self.status.get(asset_ref)
     }
 
+    /// Sets how the failed loads are retried, loads are not retried by default.
+    ///
+    /// Failed reloads are never retried, the asset stays available.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    pub fn failure(&self, asset_ref: &AssetRef) -> Option<&Failure> {
+        self.failures.get(asset_ref)
+    }
+
+    pub fn failures(&self) -> hash_map::Iter<AssetRef, Failure> {
+        self.failures.iter()
+    }
+
+    /// Forgets a failed asset, returns false if it has not failed.
+    pub fn clear_failure(&mut self, asset_ref: &AssetRef) -> bool {
+        if self.failures.remove(asset_ref).is_none() {
+            return false;
+        }
+
+        self.status.remove(asset_ref);
+        true
+    }
+
+    pub fn clear_failures(&mut self) {
+        for (asset_ref, _) in self.failures.drain() {
+            self.status.remove(&asset_ref);
+        }
+    }
+
+    /// Returns the assets that were loaded for an available asset, directly or not.
+    pub fn dependencies(&self, asset_ref: &AssetRef) -> Option<&[AssetRef]> {
+        self.dependencies.get(asset_ref).map(|dependencies| &dependencies[..])
+    }
+
+    /// Forgets an available asset, returns false if it is not available.
+    pub fn unload(&mut self, asset_ref: &AssetRef) -> bool {
+        match self.status.get(asset_ref) {
+            Some(&Status::Available) => {}
+            _ => return false,
+        }
+
+        self.status.remove(asset_ref);
+        self.dependencies.remove(asset_ref);
+        if let Some(ref mut hot_reload) = self.hot_reload {
+            hot_reload.unwatch(asset_ref);
+        }
+        true
+    }
+
+    /// Returns the assets that finished loading, without blocking.
+    ///
+    /// This is also where failed loads are retried, and where changed files are
+    /// reloaded with hot reload enabled.
     pub fn ready_assets(&mut self) -> ReadyAssetIter<A> {
+        self.reload_changed_assets();
+        self.send_due_retries();
+
         ReadyAssetIter {
             async_loader: self
         }
@@ -108,15 +347,101 @@ impl<A: Loader> AsyncLoader<A> {
         }
     }
 
-    fn handle_response(request: Request, result: LoaderResult<A::Output>, status: &mut StatusMap)
-        -> (AssetRef, LoaderResult<A::Output>) {
+    fn reload_changed_assets(&mut self) {
+        let changed: HashSet<AssetRef> = match self.hot_reload {
+            Some(ref mut hot_reload) => {
+                let assets = &hot_reload.assets;
+                hot_reload.watcher
+                    .poll()
+                    .into_iter()
+                    .filter_map(|path| assets.get(&path))
+                    .flat_map(|assets| assets.iter().cloned())
+                    .collect()
+            }
+            None => return,
+        };
 
-        let asset_ref = AssetRef::new(request.path);
-        if result.is_ok() {
-            status.insert(asset_ref.clone(), Status::Available);
+        for asset_ref in changed {
+            self.reload(&asset_ref);
         }
+    }
+
+    fn send_due_retries(&mut self) {
+        let now = Instant::now();
+        let (due, later): (Vec<_>, Vec<_>) =
+            self.retries.drain(..).partition(|&(at, _, _)| at <= now);
+
+        self.retries = later;
+        for (_, asset_ref, priority) in due {
+            self.queue.send_request(asset_ref.path(), priority);
+        }
+    }
+
+    /// Returns the number of requests that are not waiting to be retried.
+    fn requests_in_flight(&self) -> u32 {
+        self.pending_requests - self.retries.len() as u32
+    }
+
+    fn handle_response(&mut self, response: Response<A::Output, A::Error>)
+        -> Option<ReadyAsset<A::Output, A::Error>> {
+
+        let asset_ref = AssetRef::new(response.path);
+        let reloaded = self.reloading.remove(&asset_ref);
 
-        (asset_ref, result)
+        let result = match response.result {
+            Ok(output) => {
+                self.status.insert(asset_ref.clone(), Status::Available);
+                self.attempts.remove(&asset_ref);
+
+                if let Some(ref mut hot_reload) = self.hot_reload {
+                    let vfs = self.queue.vfs().read().unwrap();
+                    hot_reload.watch(&vfs, &asset_ref, &response.dependencies);
+                }
+                self.dependencies.insert(asset_ref.clone(), response.dependencies);
+
+                Ok(output)
+            }
+            Err(error) if reloaded => Err(error),
+            Err(error) => {
+                let attempts = {
+                    let attempts = self.attempts.entry(asset_ref.clone()).or_insert(0);
+                    *attempts += 1;
+                    *attempts
+                };
+
+                if attempts < self.retry_policy.max_attempts && Self::is_retryable(&error) {
+                    let at = Instant::now() + self.retry_policy.delay(attempts);
+                    self.retries.push((at, asset_ref, response.priority));
+                    return None;
+                }
+
+                self.attempts.remove(&asset_ref);
+                self.status.insert(asset_ref.clone(), Status::Failed);
+                self.failures.insert(asset_ref.clone(), Failure {
+                    attempts: attempts,
+                    message: error.to_string(),
+                });
+
+                Err(error)
+            }
+        };
+
+        self.pending_requests -= 1;
+        Some(ReadyAsset {
+            asset_ref: asset_ref,
+            result: result,
+            reloaded: reloaded,
+        })
+    }
+
+    fn is_retryable(error: &LoadError<A::Error>) -> bool {
+        match error {
+            &LoadError::Loader(ref error) => A::is_retryable(error),
+            // The dependency may be fixed by now
+            &LoadError::Dependency { .. } => true,
+            &LoadError::CyclicDependency(_) |
+            &LoadError::MissingLoader(_) => false,
+        }
     }
 }
 
@@ -125,15 +450,16 @@ pub struct ReadyAssetIter<'a, A: Loader> {
 }
 
 impl<'a, A: Loader> Iterator for ReadyAssetIter<'a, A> {
-    type Item = (AssetRef, LoaderResult<A::Output>);
+    type Item = ReadyAsset<A::Output, A::Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let AsyncLoader::<A> { ref mut queue, ref mut status, ref mut pending_requests} = *self.async_loader;
+        while let Some(response) = self.async_loader.queue.next_response() {
+            if let Some(ready) = self.async_loader.handle_response(response) {
+                return Some(ready);
+            }
+        }
 
-        queue.next_response().map(|(request, result)| {
-            *pending_requests -= 1;
-            AsyncLoader::<A>::handle_response(request, result, status)
-        })
+        None
     }
 }
 
@@ -143,51 +469,107 @@ pub struct WaitAssetIter<'a, A: Loader> {
 }
 
 impl<'a, A: Loader> Iterator for WaitAssetIter<'a, A> {
-    type Item = (AssetRef, LoaderResult<A::Output>);
+    type Item = ReadyAsset<A::Output, A::Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let AsyncLoader::<A> { ref mut queue, ref mut status, ref mut pending_requests} = *self.async_loader;
+        while self.limit != self.async_loader.pending_requests {
+            self.async_loader.send_due_retries();
+
+            if self.async_loader.requests_in_flight() == 0 {
+                // Only retries are left
+                let next_retry = self.async_loader.retries.iter().map(|&(at, _, _)| at).min();
+                if let Some(at) = next_retry {
+                    let now = Instant::now();
+                    if at > now {
+                        thread::sleep(at - now);
+                    }
+                }
+                continue;
+            }
 
-        if self.limit == *pending_requests {
-            None
-        } else {
-            *pending_requests -= 1;
-            let (request, result) = queue.wait_next_response();
-            Some(AsyncLoader::<A>::handle_response(request, result, status))
+            let response = self.async_loader.queue.wait_next_response();
+            if let Some(ready) = self.async_loader.handle_response(response) {
+                return Some(ready);
+            }
         }
+
+        None
     }
 }
 
 pub struct Initializer {
-    request_sender: RequestSender,
-    request_receiver: RequestReceiver,
-    handlers: HandlerMap,
+    pool: Arc<Pool>,
+    vfs: Arc<RwLock<Vfs>>,
+    worker_count: usize,
 }
 
 impl Initializer {
     fn new() -> Self {
-        let queue = Arc::new(MsQueue::new());
+        let vfs = Arc::new(RwLock::new(Vfs::new()));
 
         Initializer {
-            request_sender: queue.clone(),
-            request_receiver: Arc::downgrade(&queue),
-            handlers: HandlerMap::new()
+            pool: Arc::new(Pool::new(vfs.clone())),
+            vfs: vfs,
+            worker_count: DEFAULT_WORKER_COUNT,
         }
     }
 
+    /// Mounts a source of asset files, see `Vfs::mount`.
+    pub fn mount<M: Mount + 'static>(&mut self, point: &str, mount: M) {
+        self.vfs.write().unwrap().mount(point, mount);
+    }
+
+    /// Returns the filesystem the assets are loaded from, to change mounts later on.
+    pub fn vfs(&self) -> Arc<RwLock<Vfs>> {
+        self.vfs.clone()
+    }
+
+    /// Sets the number of loading threads shared by every loader.
+    pub fn set_worker_count(&mut self, count: usize) {
+        assert!(count > 0, "at least one loading thread is needed");
+        self.worker_count = count;
+    }
+
+    /// Creates an `AsyncLoader` loading one asset at a time.
     pub fn create_async_loader<A: Loader>(&mut self, loader: A) -> AsyncLoader<A> {
+        self.create_async_loader_with(vec![loader])
+    }
+
+    /// Creates an `AsyncLoader` loading up to `max_concurrency` assets at the same time,
+    /// each with its own copy of `loader`.
+    pub fn create_concurrent_async_loader<A>(&mut self, loader: A, max_concurrency: usize)
+        -> AsyncLoader<A>
+        where A: Loader + Clone
+    {
+        assert!(max_concurrency > 0, "the loader must be able to load at least one asset");
+        self.create_async_loader_with(vec![loader; max_concurrency])
+    }
+
+    fn create_async_loader_with<A: Loader>(&mut self, loaders: Vec<A>) -> AsyncLoader<A> {
         let queue = Arc::new(MsQueue::new());
-        let handler = RequestHandler::new(Arc::downgrade(&queue), loader);
+        let handlers = loaders.into_iter()
+            .map(|loader| {
+                Box::new(RequestHandler::new(Arc::downgrade(&queue), loader)) as Box<Handler>
+            })
+            .collect();
 
-        self.handlers.insert(Token::of::<A>(), Box::new(handler));
+        self.pool.add_handlers(Token::of::<A>(), handlers);
 
-        let queue = RequestQueue::new(self.request_sender.clone(), queue);
+        let queue = RequestQueue::new(self.pool.clone(), queue);
         AsyncLoader::new(queue)
     }
 
+    /// Creates an `AssetCache` backed by a new `AsyncLoader`.
+    pub fn create_asset_cache<A: Loader>(&mut self, loader: A) -> AssetCache<A> {
+        AssetCache::new(self.create_async_loader(loader))
+    }
+
+    /// Starts the loading threads.
+    ///
+    /// The requests of a dropped `AsyncLoader` are cancelled, and the threads stop once
+    /// every `AsyncLoader` has been dropped.
     pub fn done(self) {
-        let thread = LoadingThread::new(self.request_receiver, self.handlers);
-        thread.run();
+        Pool::spawn_workers(&self.pool, self.worker_count);
     }
 }
 
@@ -199,4 +581,198 @@ pub fn init() -> Initializer {
     }
 
     Initializer::new()
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{self, Read, Write};
+    use std::path::Path;
+    use std::thread;
+    use std::time::Duration;
+    use std::sync::atomic::AtomicUsize;
+    use super::*;
+
+    struct TextLoader;
+
+    impl Loader for TextLoader {
+        type Output = String;
+        type Error = io::Error;
+
+        fn load(&mut self, file: &mut AssetFile) -> Result<String, io::Error> {
+            let mut text = String::new();
+            try!(file.read_to_string(&mut text));
+            Ok(text)
+        }
+    }
+
+    /// Fails its first loads, the invalid data errors cannot be retried.
+    struct FlakyLoader {
+        calls: Arc<AtomicUsize>,
+        failures: usize,
+        kind: io::ErrorKind,
+    }
+
+    impl FlakyLoader {
+        fn new(failures: usize, kind: io::ErrorKind) -> (Self, Arc<AtomicUsize>) {
+            let calls = Arc::new(AtomicUsize::new(0));
+            let loader = FlakyLoader {
+                calls: calls.clone(),
+                failures: failures,
+                kind: kind,
+            };
+
+            (loader, calls)
+        }
+    }
+
+    impl Loader for FlakyLoader {
+        type Output = ();
+        type Error = io::Error;
+
+        fn load(&mut self, _file: &mut AssetFile) -> Result<(), io::Error> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                Err(io::Error::new(self.kind, "flaky"))
+            } else {
+                Ok(())
+            }
+        }
+
+        fn is_retryable(error: &io::Error) -> bool {
+            error.kind() != io::ErrorKind::InvalidData
+        }
+    }
+
+    fn is_failed(loader: &AsyncLoader<FlakyLoader>, asset_ref: &AssetRef) -> bool {
+        match loader.status(asset_ref) {
+            Some(&Status::Failed) => true,
+            _ => false,
+        }
+    }
+
+    /// Replaces the file at once, so that it is never read half written.
+    fn write(path: &Path, text: &str) {
+        let temporary = path.with_extension("tmp");
+        File::create(&temporary).unwrap().write_all(text.as_bytes()).unwrap();
+        fs::rename(&temporary, path).unwrap();
+    }
+
+    #[test]
+    fn test_hot_reload() {
+        let dir = env::temp_dir().join("lazybox_assets_test_hot_reload");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("text.txt");
+        write(&path, "one");
+
+        let mut initializer = Initializer::new();
+        initializer.mount("", DirectoryMount::new(dir.clone()));
+        let mut loader = initializer.create_async_loader(TextLoader);
+        initializer.done();
+
+        let asset_ref = AssetRef::new("text.txt");
+        assert!(!loader.reload(&asset_ref));
+        loader.load(&asset_ref);
+        let ready: Vec<_> = loader.wait_all().collect();
+        assert!(!ready[0].reloaded);
+
+        loader.enable_hot_reload(Duration::from_millis(0));
+        assert!(loader.is_hot_reload_enabled());
+
+        // The file is written again until its modification time changes,
+        // some filesystems only store it in seconds
+        let mut reloaded = None;
+        for _ in 0..300 {
+            write(&path, "two");
+            thread::sleep(Duration::from_millis(10));
+
+            reloaded = loader.ready_assets().next();
+            if reloaded.is_some() {
+                break;
+            }
+        }
+
+        let reloaded = reloaded.expect("the changed file was not reloaded");
+        assert_eq!(reloaded.asset_ref, asset_ref);
+        assert!(reloaded.reloaded);
+        assert_eq!(reloaded.result.unwrap(), "two");
+
+        // Unloaded assets are not watched anymore
+        assert!(loader.unload(&asset_ref));
+        assert!(!loader.hot_reload.as_ref().unwrap().watcher.is_watching(&path));
+
+        loader.disable_hot_reload();
+        assert!(!loader.is_hot_reload_enabled());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_retry_keeps_priority() {
+        let (flaky, calls) = FlakyLoader::new(2, io::ErrorKind::Other);
+        let mut initializer = Initializer::new();
+        let mut loader = initializer.create_async_loader(flaky);
+        initializer.done();
+        loader.set_retry_policy(RetryPolicy::new(3, Duration::from_millis(1)));
+
+        let asset_ref = AssetRef::new("flaky");
+        loader.load_with_priority(&asset_ref, 7);
+        while loader.retries.is_empty() {
+            assert!(loader.ready_assets().next().is_none());
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        assert_eq!(loader.retries[0].1, asset_ref);
+        assert_eq!(loader.retries[0].2, 7);
+
+        let ready: Vec<_> = loader.wait_all().collect();
+        assert_eq!(ready.len(), 1);
+        assert!(ready[0].result.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(loader.failure(&asset_ref).is_none());
+        assert!(loader.attempts.is_empty());
+    }
+
+    #[test]
+    fn test_failed_status() {
+        let (flaky, calls) = FlakyLoader::new(3, io::ErrorKind::Other);
+        let mut initializer = Initializer::new();
+        let mut loader = initializer.create_async_loader(flaky);
+        initializer.done();
+        loader.set_retry_policy(RetryPolicy::new(2, Duration::from_millis(1)));
+
+        let asset_ref = AssetRef::new("flaky");
+        loader.load(&asset_ref);
+        let ready: Vec<_> = loader.wait_all().collect();
+        assert!(ready[0].result.is_err());
+        assert!(is_failed(&loader, &asset_ref));
+        assert_eq!(loader.failure(&asset_ref).unwrap().attempts, 2);
+        assert_eq!(loader.failure(&asset_ref).unwrap().message, "flaky");
+        assert_eq!(loader.failures().count(), 1);
+
+        // Loading a failed asset again starts over, the retry of the third load succeeds
+        loader.load(&asset_ref);
+        assert!(loader.failure(&asset_ref).is_none());
+        let ready: Vec<_> = loader.wait_all().collect();
+        assert!(ready[0].result.is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_not_retryable() {
+        let (flaky, calls) = FlakyLoader::new(1, io::ErrorKind::InvalidData);
+        let mut initializer = Initializer::new();
+        let mut loader = initializer.create_async_loader(flaky);
+        initializer.done();
+        loader.set_retry_policy(RetryPolicy::new(5, Duration::from_millis(1)));
+
+        let asset_ref = AssetRef::new("flaky");
+        loader.load(&asset_ref);
+        let ready: Vec<_> = loader.wait_all().collect();
+        assert!(ready[0].result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(loader.failure(&asset_ref).unwrap().attempts, 1);
+
+        assert!(loader.clear_failure(&asset_ref));
+        assert!(!loader.clear_failure(&asset_ref));
+        assert!(loader.status(&asset_ref).is_none());
+    }
+}
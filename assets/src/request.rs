@@ -1,15 +1,21 @@
-use std::any::TypeId;
+use std::any::{Any, TypeId};
 use std::collections::HashMap;
-use std::io;
+use std::error::Error;
 use std::thread;
-use std::sync::{Arc, Weak};
+use std::sync::{Arc, Weak, Mutex, RwLock, Condvar};
 use crossbeam::sync::MsQueue;
 
-use super::{Loader, LoaderResult};
+use super::{AssetRef, Loader};
+use dependency::{Dependency, Dependencies};
+use error::LoadError;
+use vfs::{Vfs, AssetFile};
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq)]
 pub struct Token(TypeId);
 
+/// The output or the loader error of a handler, with their types erased.
+pub type ErasedResult<T> = Result<T, LoadError<Box<Any + Send>>>;
+
 impl Token {
     pub fn of<A: Loader>() -> Self {
         Token(TypeId::of::<A>())
@@ -19,58 +25,79 @@ impl Token {
 pub struct Request {
     pub token: Token,
     pub path: String,
+    pub priority: i32,
+    /// Orders requests of the same priority, first come first served.
+    /// Also identifies the request while it waits for its dependencies.
+    order: u64,
+    /// The request waiting for this one, `None` for the requests of an `AsyncLoader`.
+    parent: Option<u64>,
+    /// The requests this one is a dependency of, to detect cycles.
+    ancestors: Vec<(Token, String)>,
+    /// Every asset loaded for this one, directly or not.
+    tree: Vec<AssetRef>,
+    /// The outcome of the dependencies, once they are all loaded.
+    resolved: Option<ErasedResult<Dependencies>>,
 }
 
-impl Request {
-    fn new<P: Into<String>>(token: Token, path: P) -> Self {
-        Request {
-            token: token,
-            path: path.into(),
-        }
-    }
+/// A loaded asset, with the dependencies that were loaded for it.
+pub struct Response<T, E> {
+    pub path: String,
+    /// The priority the asset was requested with, to retry it with the same one.
+    pub priority: i32,
+    pub result: Result<T, LoadError<E>>,
+    pub dependencies: Vec<AssetRef>,
 }
 
-pub type RequestSender = Arc<MsQueue<Request>>;
-pub type RequestReceiver = Weak<MsQueue<Request>>;
-
-pub type Response<T> = Result<T, io::Error>;
-pub type ResponseReceiver<T> = Arc<MsQueue<(Request, LoaderResult<T>)>>;
-pub type ResponseSender<T> = Weak<MsQueue<(Request, LoaderResult<T>)>>;
+pub type ResponseReceiver<T, E> = Arc<MsQueue<Response<T, E>>>;
+pub type ResponseSender<T, E> = Weak<MsQueue<Response<T, E>>>;
 
 pub struct RequestQueue<A: Loader> {
-    sender: RequestSender,
-    receiver: ResponseReceiver<A::Output>,
+    pool: Arc<Pool>,
+    receiver: ResponseReceiver<A::Output, A::Error>,
 }
 
 impl<A: Loader> RequestQueue<A> {
-    pub fn new(sender: RequestSender, receiver: ResponseReceiver<A::Output>) -> Self {
+    pub fn new(pool: Arc<Pool>, receiver: ResponseReceiver<A::Output, A::Error>) -> Self {
         RequestQueue {
-            sender: sender,
+            pool: pool,
             receiver: receiver,
         }
     }
 
-    pub fn send_request<P: Into<String>>(&self, path: P) {
-        let request = Request::new(Token::of::<A>(), path.into());
-        self.sender.push(request)
+    pub fn send_request<P: Into<String>>(&self, path: P, priority: i32) {
+        self.pool.push(Token::of::<A>(), path.into(), priority);
+    }
+
+    pub fn raise_priority(&self, path: &str, priority: i32) {
+        self.pool.raise_priority(Token::of::<A>(), path, priority);
+    }
+
+    pub fn vfs(&self) -> &Arc<RwLock<Vfs>> {
+        &self.pool.vfs
     }
 
-    pub fn next_response(&self) -> Option<(Request, LoaderResult<A::Output>)> {
+    pub fn next_response(&self) -> Option<Response<A::Output, A::Error>> {
         self.receiver.try_pop()
     }
 
-    pub fn wait_next_response(&self) -> (Request, LoaderResult<A::Output>) {
+    pub fn wait_next_response(&self) -> Response<A::Output, A::Error> {
         self.receiver.pop()
     }
 }
 
+impl<A: Loader> Drop for RequestQueue<A> {
+    fn drop(&mut self) {
+        self.pool.cancel(Token::of::<A>());
+    }
+}
+
 pub struct RequestHandler<A: Loader> {
-    sender: ResponseSender<A::Output>,
+    sender: ResponseSender<A::Output, A::Error>,
     loader: A,
 }
 
 impl<A: Loader> RequestHandler<A> {
-    pub fn new(sender: ResponseSender<A::Output>, loader: A) -> Self {
+    pub fn new(sender: ResponseSender<A::Output, A::Error>, loader: A) -> Self {
         RequestHandler {
             sender: sender,
             loader: loader,
@@ -79,50 +106,542 @@ impl<A: Loader> RequestHandler<A> {
 }
 
 pub trait Handler: Send {
-    fn handle(&mut self, request: Request) -> bool;
+    fn dependencies(&mut self, file: &mut AssetFile) -> ErasedResult<Vec<Dependency>>;
+
+    fn load(&mut self, file: &mut AssetFile, dependencies: Dependencies)
+        -> ErasedResult<Box<Any + Send>>;
+
+    /// Turns an error of this handler into the error of a dependency.
+    fn dependency_error(&self, error: LoadError<Box<Any + Send>>) -> Box<Error + Send + Sync>;
+
+    /// Sends a loaded asset to the `AsyncLoader`, returns false if it was dropped.
+    fn respond(&self, response: Response<Box<Any + Send>, Box<Any + Send>>) -> bool;
+}
+
+impl<A: Loader> RequestHandler<A> {
+    fn typed_error(error: LoadError<Box<Any + Send>>) -> LoadError<A::Error> {
+        error.map_loader(|error| {
+            *error.downcast::<A::Error>().ok().expect("loader error of the wrong type")
+        })
+    }
+}
+
+fn erase_error<E: Any + Send>(error: E) -> LoadError<Box<Any + Send>> {
+    LoadError::Loader(Box::new(error))
 }
 
 impl<A: Loader> Handler for RequestHandler<A> {
-    fn handle(&mut self, request: Request) -> bool {
-        let result = self.loader.load(&request.path);
+    fn dependencies(&mut self, file: &mut AssetFile) -> ErasedResult<Vec<Dependency>> {
+        self.loader.dependencies(file).map_err(erase_error)
+    }
+
+    fn load(&mut self, file: &mut AssetFile, mut dependencies: Dependencies)
+        -> ErasedResult<Box<Any + Send>> {
+
+        self.loader
+            .load_with_dependencies(file, &mut dependencies)
+            .map(|output| Box::new(output) as Box<Any + Send>)
+            .map_err(erase_error)
+    }
+
+    fn dependency_error(&self, error: LoadError<Box<Any + Send>>) -> Box<Error + Send + Sync> {
+        Box::new(Self::typed_error(error))
+    }
+
+    fn respond(&self, response: Response<Box<Any + Send>, Box<Any + Send>>) -> bool {
+        let result = match response.result {
+            Ok(output) => {
+                Ok(*output.downcast::<A::Output>().ok().expect("loader output of the wrong type"))
+            }
+            Err(error) => Err(Self::typed_error(error)),
+        };
+        let response = Response {
+            path: response.path,
+            priority: response.priority,
+            result: result,
+            dependencies: response.dependencies,
+        };
 
         self.sender.upgrade()
-            .map(|sender| sender.push((request, result)))
+            .map(|sender| sender.push(response))
             .is_some()
     }
 }
 
-pub type HandlerMap = HashMap<Token, Box<Handler>>;
+/// The idle handlers of each loader, one per request that can be loaded concurrently.
+pub type HandlerMap = HashMap<Token, Vec<Box<Handler>>>;
+
+/// A request waiting for its dependencies to be loaded.
+struct Waiting {
+    request: Request,
+    remaining: usize,
+    dependencies: Dependencies,
+    error: Option<LoadError<Box<Any + Send>>>,
+}
 
-pub struct LoadingThread {
-    receiver: RequestReceiver,
+struct PoolState {
+    requests: Vec<Request>,
+    waiting: HashMap<u64, Waiting>,
     handlers: HandlerMap,
+    next_order: u64,
 }
 
-impl LoadingThread {
-    pub fn new(receiver: RequestReceiver, handlers: HandlerMap) -> Self {
-        LoadingThread {
-            receiver: receiver,
-            handlers: handlers,
+impl PoolState {
+    fn next_order(&mut self) -> u64 {
+        let order = self.next_order;
+        self.next_order += 1;
+        order
+    }
+
+    /// Queues a request again once its dependencies are resolved.
+    fn requeue(&mut self, mut request: Request, resolved: ErasedResult<Dependencies>) {
+        if self.handlers.contains_key(&request.token) {
+            request.resolved = Some(resolved);
+            self.requests.push(request);
+        } else {
+            // The loader was cancelled in the meantime
+            self.abandon(request);
         }
     }
 
-    pub fn run(mut self) {
-        thread::spawn(move || {
-            while let Some(receiver) =  self.receiver.upgrade() {
-                self.handle_request(receiver.pop());
+    /// Fails the request waiting for a request whose loader was cancelled.
+    fn abandon(&mut self, request: Request) {
+        if let Some(parent) = request.parent {
+            let error = LoadError::MissingLoader(AssetRef::new(request.path.clone()));
+            self.resolve(parent, request, Err(error));
+        }
+    }
+
+    /// Hands a loaded dependency to the request waiting for it.
+    fn resolve(&mut self, parent: u64, request: Request, result: ErasedResult<Box<Any + Send>>) {
+        let done = match self.waiting.get_mut(&parent) {
+            Some(waiting) => {
+                let asset_ref = AssetRef::new(request.path);
+                waiting.request.tree.extend(request.tree);
+                waiting.request.tree.push(asset_ref.clone());
+
+                match result {
+                    Ok(output) => {
+                        let dependency = Dependency {
+                            token: request.token,
+                            asset_ref: asset_ref,
+                        };
+                        waiting.dependencies.insert(dependency, output);
+                    }
+                    Err(error) => {
+                        if waiting.error.is_none() {
+                            waiting.error = Some(error);
+                        }
+                    }
+                }
+
+                waiting.remaining -= 1;
+                waiting.remaining == 0
             }
+            // The loader of the parent was cancelled
+            None => false,
+        };
+
+        if done {
+            let waiting = self.waiting.remove(&parent).unwrap();
+            let resolved = match waiting.error {
+                Some(error) => Err(error),
+                None => Ok(waiting.dependencies),
+            };
+
+            self.requeue(waiting.request, resolved);
+        }
+    }
+}
+
+/// Requests shared by the loading threads.
+///
+/// A request is picked by priority among the ones whose loader has an idle handler,
+/// so that a busy loader does not block the others. Handlers are not kept busy
+/// while their request waits for its dependencies.
+pub struct Pool {
+    state: Mutex<PoolState>,
+    changed: Condvar,
+    vfs: Arc<RwLock<Vfs>>,
+}
+
+impl Pool {
+    pub fn new(vfs: Arc<RwLock<Vfs>>) -> Self {
+        Pool {
+            vfs: vfs,
+            state: Mutex::new(PoolState {
+                requests: Vec::new(),
+                waiting: HashMap::new(),
+                handlers: HandlerMap::new(),
+                next_order: 0,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    pub fn add_handlers(&self, token: Token, handlers: Vec<Box<Handler>>) {
+        let mut state = self.state.lock().unwrap();
+        state.handlers.insert(token, handlers);
+    }
+
+    fn push(&self, token: Token, path: String, priority: i32) {
+        let mut state = self.state.lock().unwrap();
+        let order = state.next_order();
+
+        state.requests.push(Request {
+            token: token,
+            path: path,
+            priority: priority,
+            order: order,
+            parent: None,
+            ancestors: Vec::new(),
+            tree: Vec::new(),
+            resolved: None,
         });
+        self.changed.notify_one();
     }
 
-    fn handle_request(&mut self, request: Request) {
-        let token = request.token;
+    fn raise_priority(&self, token: Token, path: &str, priority: i32) {
+        let mut state = self.state.lock().unwrap();
+
+        for request in &mut state.requests {
+            if request.token == token && request.path == path && request.priority < priority {
+                request.priority = priority;
+            }
+        }
+    }
 
-        let result = self.handlers.get_mut(&token)
-                                  .map(|handler| handler.handle(request));
+    /// Forgets the queued requests and the handlers of a loader.
+    ///
+    /// Requests being loaded are finished, but their handlers are dropped afterwards.
+    /// The requests of other loaders depending on the forgotten ones fail with
+    /// `LoadError::MissingLoader`.
+    fn cancel(&self, token: Token) {
+        let mut state = self.state.lock().unwrap();
+        state.handlers.remove(&token);
 
-        if let Some(false) = result {
-            self.handlers.remove(&token);
+        let (mut cancelled, requests): (Vec<_>, Vec<_>) =
+            state.requests.drain(..).partition(|request| request.token == token);
+        state.requests = requests;
+
+        let waiting: Vec<u64> = state.waiting
+            .iter()
+            .filter(|&(_, waiting)| waiting.request.token == token)
+            .map(|(&order, _)| order)
+            .collect();
+        for order in waiting {
+            cancelled.push(state.waiting.remove(&order).unwrap().request);
+        }
+
+        for request in cancelled {
+            state.abandon(request);
+        }
+        self.changed.notify_all();
+    }
+
+    /// Starts the loading threads, they stop once every loader has been cancelled.
+    pub fn spawn_workers(pool: &Arc<Pool>, count: usize) {
+        for _ in 0..count {
+            let pool = pool.clone();
+
+            thread::spawn(move || {
+                while let Some((request, mut handler)) = pool.next_job() {
+                    let token = request.token;
+                    let alive = pool.run(request, &mut *handler);
+                    pool.finish(token, handler, alive);
+                }
+            });
         }
     }
-}
\ No newline at end of file
+
+    fn next_job(&self) -> Option<(Request, Box<Handler>)> {
+        let mut state = self.state.lock().unwrap();
+
+        loop {
+            if state.handlers.is_empty() {
+                return None;
+            }
+
+            let next = {
+                let PoolState { ref requests, ref handlers, .. } = *state;
+
+                requests.iter()
+                    .enumerate()
+                    .filter(|&(_, request)| {
+                        handlers.get(&request.token).map_or(false, |idle| !idle.is_empty())
+                    })
+                    .max_by(|&(_, a), &(_, b)| {
+                        a.priority.cmp(&b.priority).then(b.order.cmp(&a.order))
+                    })
+                    .map(|(index, _)| index)
+            };
+
+            if let Some(index) = next {
+                let request = state.requests.swap_remove(index);
+                let handler = state.handlers.get_mut(&request.token).unwrap().pop().unwrap();
+
+                return Some((request, handler));
+            }
+
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Loads a request, or queues its dependencies. Returns false if the handler is dead.
+    fn run(&self, mut request: Request, handler: &mut Handler) -> bool {
+        let result = match request.resolved.take() {
+            Some(Ok(dependencies)) => handler.load(&mut self.open(&request), dependencies),
+            Some(Err(error)) => Err(error),
+            None => {
+                match handler.dependencies(&mut self.open(&request)) {
+                    Ok(ref dependencies) if !dependencies.is_empty() => {
+                        self.wait_for(request, dependencies);
+                        return true;
+                    }
+                    // The file is opened again, listing the dependencies may have read it
+                    Ok(_) => handler.load(&mut self.open(&request), Dependencies::new()),
+                    Err(error) => Err(error),
+                }
+            }
+        };
+
+        match request.parent {
+            None => {
+                handler.respond(Response {
+                    path: request.path,
+                    priority: request.priority,
+                    result: result,
+                    dependencies: request.tree,
+                })
+            }
+            Some(parent) => {
+                let result = result.map_err(|error| {
+                    LoadError::Dependency {
+                        asset_ref: AssetRef::new(request.path.clone()),
+                        error: handler.dependency_error(error),
+                    }
+                });
+                self.resolve(parent, request, result);
+                true
+            }
+        }
+    }
+
+    fn open(&self, request: &Request) -> AssetFile {
+        AssetFile::new(request.path.clone(), self.vfs.clone())
+    }
+
+    fn wait_for(&self, request: Request, dependencies: &[Dependency]) {
+        let mut state = self.state.lock().unwrap();
+
+        let mut ancestors = request.ancestors.clone();
+        ancestors.push((request.token, request.path.clone()));
+
+        let mut unique: Vec<&Dependency> = Vec::new();
+        for dependency in dependencies {
+            if unique.contains(&dependency) {
+                continue;
+            }
+
+            let path = dependency.asset_ref.path();
+            if ancestors.iter().any(|&(token, ref p)| token == dependency.token && p == path) {
+                let error = LoadError::CyclicDependency(dependency.asset_ref.clone());
+                state.requeue(request, Err(error));
+                return;
+            }
+            if !state.handlers.contains_key(&dependency.token) {
+                let error = LoadError::MissingLoader(dependency.asset_ref.clone());
+                state.requeue(request, Err(error));
+                return;
+            }
+
+            unique.push(dependency);
+        }
+
+        for dependency in &unique {
+            let order = state.next_order();
+
+            state.requests.push(Request {
+                token: dependency.token,
+                path: dependency.asset_ref.path().to_string(),
+                priority: request.priority,
+                order: order,
+                parent: Some(request.order),
+                ancestors: ancestors.clone(),
+                tree: Vec::new(),
+                resolved: None,
+            });
+        }
+
+        state.waiting.insert(request.order, Waiting {
+            remaining: unique.len(),
+            request: request,
+            dependencies: Dependencies::new(),
+            error: None,
+        });
+        self.changed.notify_all();
+    }
+
+    /// Hands a loaded dependency to the request waiting for it.
+    fn resolve(&self, parent: u64, request: Request, result: ErasedResult<Box<Any + Send>>) {
+        let mut state = self.state.lock().unwrap();
+
+        state.resolve(parent, request, result);
+        self.changed.notify_all();
+    }
+
+    fn finish(&self, token: Token, handler: Box<Handler>, alive: bool) {
+        let mut state = self.state.lock().unwrap();
+
+        if alive {
+            if let Some(idle) = state.handlers.get_mut(&token) {
+                idle.push(handler);
+            }
+        }
+        self.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Weak, RwLock};
+    use super::*;
+    use vfs::{Vfs, AssetFile, MemoryMount};
+
+    struct FirstLoader;
+
+    impl Loader for FirstLoader {
+        type Output = ();
+        type Error = io::Error;
+
+        fn load(&mut self, _file: &mut AssetFile) -> Result<(), io::Error> {
+            Ok(())
+        }
+    }
+
+    struct SecondLoader;
+
+    impl Loader for SecondLoader {
+        type Output = ();
+        type Error = io::Error;
+
+        fn load(&mut self, _file: &mut AssetFile) -> Result<(), io::Error> {
+            Ok(())
+        }
+    }
+
+    /// Depends on an asset of the first loader.
+    struct ParentLoader;
+
+    impl Loader for ParentLoader {
+        type Output = ();
+        type Error = io::Error;
+
+        fn dependencies(&mut self, _file: &mut AssetFile) -> Result<Vec<Dependency>, io::Error> {
+            Ok(vec![Dependency::new::<FirstLoader>(AssetRef::new("child"))])
+        }
+
+        fn load(&mut self, _file: &mut AssetFile) -> Result<(), io::Error> {
+            Ok(())
+        }
+    }
+
+    fn handler<A: Loader>(loader: A) -> Box<Handler> {
+        Box::new(RequestHandler::new(Weak::new(), loader))
+    }
+
+    fn new_pool() -> Pool {
+        let pool = Pool::new(Arc::new(RwLock::new(Vfs::new())));
+        pool.add_handlers(Token::of::<FirstLoader>(), vec![handler(FirstLoader)]);
+        pool.add_handlers(Token::of::<SecondLoader>(), vec![handler(SecondLoader)]);
+
+        pool
+    }
+
+    /// Takes the next job and gives its handler back without running it.
+    fn next_path(pool: &Pool) -> String {
+        let (request, handler) = pool.next_job().unwrap();
+        pool.finish(request.token, handler, true);
+
+        request.path
+    }
+
+    #[test]
+    fn test_priority_order() {
+        let pool = new_pool();
+        let first = Token::of::<FirstLoader>();
+
+        pool.push(first, "low".to_string(), -1);
+        pool.push(first, "early".to_string(), 0);
+        pool.push(first, "high".to_string(), 5);
+        pool.push(first, "late".to_string(), 0);
+        pool.push(first, "raised".to_string(), 0);
+        pool.raise_priority(first, "raised", 1);
+        // Lowering the priority has no effect
+        pool.raise_priority(first, "high", 0);
+
+        let paths: Vec<_> = (0..5).map(|_| next_path(&pool)).collect();
+        assert_eq!(paths, vec!["high", "raised", "early", "late", "low"]);
+    }
+
+    #[test]
+    fn test_busy_loader_skipped() {
+        let pool = new_pool();
+        let first = Token::of::<FirstLoader>();
+        let second = Token::of::<SecondLoader>();
+
+        pool.push(first, "a".to_string(), 5);
+        pool.push(first, "b".to_string(), 5);
+        pool.push(second, "c".to_string(), 0);
+
+        // The only handler of the first loader is busy
+        let (request, busy) = pool.next_job().unwrap();
+        assert_eq!(request.path, "a");
+        assert_eq!(next_path(&pool), "c");
+
+        pool.finish(first, busy, true);
+        assert_eq!(next_path(&pool), "b");
+    }
+
+    #[test]
+    fn test_no_job_once_cancelled() {
+        let pool = new_pool();
+
+        pool.push(Token::of::<FirstLoader>(), "a".to_string(), 0);
+        pool.cancel(Token::of::<FirstLoader>());
+        pool.cancel(Token::of::<SecondLoader>());
+        assert!(pool.next_job().is_none());
+    }
+
+    #[test]
+    fn test_cancel_dependency_loader() {
+        let pool = new_pool();
+        let mut mount = MemoryMount::new();
+        mount.insert("parent", "");
+        pool.vfs.write().unwrap().mount("", mount);
+
+        let receiver = Arc::new(MsQueue::new());
+        let parent = Token::of::<ParentLoader>();
+        let sender = Arc::downgrade(&receiver);
+        pool.add_handlers(parent, vec![Box::new(RequestHandler::new(sender, ParentLoader))]);
+
+        // The parent waits for its dependency, which is still queued
+        pool.push(parent, "parent".to_string(), 0);
+        let (request, mut handler) = pool.next_job().unwrap();
+        assert!(pool.run(request, &mut *handler));
+        pool.finish(parent, handler, true);
+
+        pool.cancel(Token::of::<FirstLoader>());
+        assert_eq!(pool.state.lock().unwrap().requests.len(), 1);
+        let (request, mut handler) = pool.next_job().unwrap();
+        assert_eq!(request.path, "parent");
+        assert!(pool.run(request, &mut *handler));
+
+        let response: Response<(), io::Error> = receiver.try_pop().unwrap();
+        match response.result {
+            Err(LoadError::MissingLoader(ref asset_ref)) => assert_eq!(asset_ref.path(), "child"),
+            _ => panic!("the parent did not fail with its missing loader"),
+        }
+    }
+}
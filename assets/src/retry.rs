@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+/// How failed loads are retried.
+///
+/// Retries are sent by `AsyncLoader::ready_assets` and `AsyncLoader::wait_all`
+/// once their delay has elapsed, the delay is multiplied by `backoff` after each retry.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// The number of loads before giving up, including the first one.
+    pub max_attempts: u32,
+    /// The delay before the first retry.
+    pub delay: Duration,
+    pub backoff: u32,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Gives up after the first failure.
+    pub fn never() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            delay: Duration::from_secs(0),
+            backoff: 1,
+            max_delay: Duration::from_secs(0),
+        }
+    }
+
+    /// Retries up to `max_attempts` loads, doubling the delay after each retry.
+    pub fn new(max_attempts: u32, delay: Duration) -> Self {
+        RetryPolicy {
+            max_attempts: max_attempts,
+            delay: delay,
+            backoff: 2,
+            max_delay: Duration::from_secs(30),
+        }
+    }
+
+    /// Returns the delay after the failure of the `attempt`th load.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let mut delay = self.delay;
+        for _ in 1..attempt {
+            if delay >= self.max_delay {
+                break;
+            }
+            delay = delay * self.backoff;
+        }
+
+        if delay > self.max_delay { self.max_delay } else { delay }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::never()
+    }
+}
+
+/// An asset that could not be loaded.
+#[derive(Clone, Debug)]
+pub struct Failure {
+    /// The number of loads that failed.
+    pub attempts: u32,
+    /// The description of the last error.
+    pub message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::*;
+
+    fn millis(delays: &[u64]) -> Vec<Duration> {
+        delays.iter().map(|&delay| Duration::from_millis(delay)).collect()
+    }
+
+    #[test]
+    fn test_backoff() {
+        let mut policy = RetryPolicy::new(5, Duration::from_millis(100));
+        let delays: Vec<_> = (1..5).map(|attempt| policy.delay(attempt)).collect();
+        assert_eq!(delays, millis(&[100, 200, 400, 800]));
+
+        policy.max_delay = Duration::from_millis(300);
+        policy.backoff = 3;
+        let delays: Vec<_> = (1..5).map(|attempt| policy.delay(attempt)).collect();
+        assert_eq!(delays, millis(&[100, 300, 300, 300]));
+
+        assert_eq!(RetryPolicy::never().delay(3), Duration::from_secs(0));
+    }
+}
@@ -0,0 +1,387 @@
+//! Virtual filesystem
+//!
+//! Assets are read from mounts: directories, in-memory files, or zip and tar
+//! packs with the `zip` and `tar` features. Each mount is attached to a mount
+//! point, a path prefix such as `textures`, and the last mounts shadow the
+//! previous ones, so that a patch can be mounted over a pack.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Cursor};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// A source of files.
+pub trait Mount: Send + Sync {
+    /// Returns true if the mount has the file at `path`, relative to the mount point.
+    fn contains(&self, path: &str) -> bool;
+
+    fn open(&self, path: &str) -> io::Result<Box<Read + Send>>;
+
+    /// Returns the file on disk, for hot reloading.
+    fn real_path(&self, _path: &str) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// The files of a directory, the paths that may lead out of it are rejected.
+pub struct DirectoryMount {
+    root: PathBuf,
+}
+
+impl DirectoryMount {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        DirectoryMount { root: root.into() }
+    }
+
+    /// Returns the file on disk, or `None` if `path` may lead out of the directory.
+    fn file_path(&self, path: &str) -> Option<PathBuf> {
+        let outside = Path::new(path).components().any(|component| {
+            match component {
+                Component::ParentDir | Component::RootDir | Component::Prefix(_) => true,
+                Component::CurDir | Component::Normal(_) => false,
+            }
+        });
+
+        if outside { None } else { Some(self.root.join(path)) }
+    }
+}
+
+impl Mount for DirectoryMount {
+    fn contains(&self, path: &str) -> bool {
+        self.file_path(path).map_or(false, |path| path.is_file())
+    }
+
+    fn open(&self, path: &str) -> io::Result<Box<Read + Send>> {
+        let file_path = match self.file_path(path) {
+            Some(file_path) => file_path,
+            None => {
+                let message = format!("`{}` is outside of the mounted directory", path);
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+            }
+        };
+
+        let file = try!(File::open(file_path));
+        Ok(Box::new(file))
+    }
+
+    fn real_path(&self, path: &str) -> Option<PathBuf> {
+        self.file_path(path)
+    }
+}
+
+#[derive(Clone)]
+struct SharedBytes(Arc<Vec<u8>>);
+
+impl AsRef<[u8]> for SharedBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Files kept in memory, for tests or generated assets.
+pub struct MemoryMount {
+    files: HashMap<String, SharedBytes>,
+}
+
+impl MemoryMount {
+    pub fn new() -> Self {
+        MemoryMount { files: HashMap::new() }
+    }
+
+    pub fn insert<P: Into<String>, D: Into<Vec<u8>>>(&mut self, path: P, data: D) {
+        self.files.insert(path.into(), SharedBytes(Arc::new(data.into())));
+    }
+
+    pub fn remove(&mut self, path: &str) -> bool {
+        self.files.remove(path).is_some()
+    }
+}
+
+impl Mount for MemoryMount {
+    fn contains(&self, path: &str) -> bool {
+        self.files.contains_key(path)
+    }
+
+    fn open(&self, path: &str) -> io::Result<Box<Read + Send>> {
+        match self.files.get(path) {
+            Some(data) => Ok(Box::new(Cursor::new(data.clone()))),
+            None => Err(not_found(path)),
+        }
+    }
+}
+
+/// The files of a zip pack, each one is decompressed in memory when opened.
+#[cfg(feature = "zip")]
+pub struct ZipMount {
+    archive: ::std::sync::Mutex<::zip::ZipArchive<File>>,
+}
+
+#[cfg(feature = "zip")]
+impl ZipMount {
+    pub fn new<P: AsRef<::std::path::Path>>(path: P) -> io::Result<Self> {
+        let file = try!(File::open(path));
+        let archive = try!(::zip::ZipArchive::new(file));
+
+        Ok(ZipMount { archive: ::std::sync::Mutex::new(archive) })
+    }
+}
+
+#[cfg(feature = "zip")]
+impl Mount for ZipMount {
+    fn contains(&self, path: &str) -> bool {
+        self.archive.lock().unwrap().by_name(path).is_ok()
+    }
+
+    fn open(&self, path: &str) -> io::Result<Box<Read + Send>> {
+        let mut archive = self.archive.lock().unwrap();
+        let mut file = try!(archive.by_name(path));
+
+        let mut data = Vec::with_capacity(file.size() as usize);
+        try!(file.read_to_end(&mut data));
+        Ok(Box::new(Cursor::new(data)))
+    }
+}
+
+/// The files of a tar pack, read from the pack when opened.
+#[cfg(feature = "tar")]
+pub struct TarMount {
+    path: PathBuf,
+    /// The offset and size of each file in the pack.
+    entries: HashMap<String, (u64, u64)>,
+}
+
+#[cfg(feature = "tar")]
+impl TarMount {
+    pub fn new<P: Into<PathBuf>>(path: P) -> io::Result<Self> {
+        let path = path.into();
+        let mut archive = ::tar::Archive::new(try!(File::open(&path)));
+        let mut entries = HashMap::new();
+
+        for entry in try!(archive.entries()) {
+            let entry = try!(entry);
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let name = try!(entry.path()).to_string_lossy().replace('\\', "/");
+            let size = try!(entry.header().size());
+            entries.insert(name, (entry.raw_file_position(), size));
+        }
+
+        Ok(TarMount {
+            path: path,
+            entries: entries,
+        })
+    }
+}
+
+#[cfg(feature = "tar")]
+impl Mount for TarMount {
+    fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    fn open(&self, path: &str) -> io::Result<Box<Read + Send>> {
+        use std::io::{Seek, SeekFrom};
+
+        let (offset, size) = match self.entries.get(path) {
+            Some(&entry) => entry,
+            None => return Err(not_found(path)),
+        };
+
+        let mut file = try!(File::open(&self.path));
+        try!(file.seek(SeekFrom::Start(offset)));
+        Ok(Box::new(file.take(size)))
+    }
+}
+
+pub struct Vfs {
+    mounts: Vec<(String, Box<Mount>)>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Vfs { mounts: Vec::new() }
+    }
+
+    /// Mounts a source of files at `point`, use `""` for the root.
+    pub fn mount<M: Mount + 'static>(&mut self, point: &str, mount: M) {
+        self.mounts.push((point.trim_matches('/').to_string(), Box::new(mount)));
+    }
+
+    /// Removes the last mount at `point`, returns false if there is none.
+    pub fn unmount(&mut self, point: &str) -> bool {
+        let point = point.trim_matches('/');
+
+        match self.mounts.iter().rposition(|&(ref p, _)| p == point) {
+            Some(index) => {
+                self.mounts.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.find(path).is_some()
+    }
+
+    pub fn open(&self, path: &str) -> io::Result<Box<Read + Send>> {
+        match self.find(path) {
+            Some((mount, relative)) => mount.open(relative),
+            None => Err(not_found(path)),
+        }
+    }
+
+    /// Returns the file on disk, if the file comes from a directory.
+    pub fn real_path(&self, path: &str) -> Option<PathBuf> {
+        self.find(path).and_then(|(mount, relative)| mount.real_path(relative))
+    }
+
+    /// Returns the mount of a file and the path relative to its mount point.
+    fn find<'a, 'p>(&'a self, path: &'p str) -> Option<(&'a Mount, &'p str)> {
+        let path = path.trim_left_matches('/');
+
+        self.mounts
+            .iter()
+            .rev()
+            .filter_map(|&(ref point, ref mount)| relative_path(point, path).map(|r| (mount, r)))
+            .find(|&(mount, relative)| mount.contains(relative))
+            .map(|(mount, relative)| (&**mount, relative))
+    }
+}
+
+fn relative_path<'p>(point: &str, path: &'p str) -> Option<&'p str> {
+    if point.is_empty() {
+        Some(path)
+    } else if path.starts_with(point) && path[point.len()..].starts_with('/') {
+        Some(&path[point.len() + 1..])
+    } else {
+        None
+    }
+}
+
+fn not_found(path: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("`{}` is not in the filesystem", path))
+}
+
+/// The file of an asset, opened on the first read.
+pub struct AssetFile {
+    path: String,
+    vfs: Arc<RwLock<Vfs>>,
+    reader: Option<Box<Read + Send>>,
+}
+
+impl AssetFile {
+    pub(crate) fn new(path: String, vfs: Arc<RwLock<Vfs>>) -> Self {
+        AssetFile {
+            path: path,
+            vfs: vfs,
+            reader: None,
+        }
+    }
+
+    /// Returns the path of the asset in the virtual filesystem.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Read for AssetFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.reader.is_none() {
+            self.reader = Some(try!(self.vfs.read().unwrap().open(&self.path)));
+        }
+
+        self.reader.as_mut().unwrap().read(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+    use std::sync::{Arc, RwLock};
+    use super::*;
+
+    fn read(vfs: &Vfs, path: &str) -> String {
+        let mut text = String::new();
+        vfs.open(path).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    fn memory(files: &[(&str, &str)]) -> MemoryMount {
+        let mut mount = MemoryMount::new();
+        for &(path, text) in files {
+            mount.insert(path, text);
+        }
+
+        mount
+    }
+
+    #[test]
+    fn test_mount_order() {
+        let mut vfs = Vfs::new();
+        vfs.mount("", memory(&[("a.txt", "pack"), ("textures/b.txt", "pack")]));
+        vfs.mount("/textures/", memory(&[("b.txt", "textures"), ("c.txt", "textures")]));
+        vfs.mount("", memory(&[("a.txt", "patch")]));
+
+        // The last mounts shadow the previous ones, for the files they have
+        assert_eq!(read(&vfs, "a.txt"), "patch");
+        assert_eq!(read(&vfs, "/textures/b.txt"), "textures");
+        assert_eq!(read(&vfs, "textures/c.txt"), "textures");
+        assert!(!vfs.contains("texturesc.txt"));
+        assert!(!vfs.contains("c.txt"));
+        assert!(vfs.open("missing.txt").is_err());
+
+        assert!(vfs.unmount(""));
+        assert_eq!(read(&vfs, "a.txt"), "pack");
+        assert!(vfs.unmount("textures"));
+        assert_eq!(read(&vfs, "textures/b.txt"), "pack");
+        assert!(!vfs.unmount("textures"));
+    }
+
+    #[test]
+    fn test_asset_file() {
+        let mut vfs = Vfs::new();
+        vfs.mount("", memory(&[("a.txt", "text")]));
+        let vfs = Arc::new(RwLock::new(vfs));
+
+        let mut file = AssetFile::new("a.txt".to_string(), vfs.clone());
+        let mut text = String::new();
+        file.read_to_string(&mut text).unwrap();
+        assert_eq!(file.path(), "a.txt");
+        assert_eq!(text, "text");
+
+        let mut missing = AssetFile::new("b.txt".to_string(), vfs);
+        assert!(missing.read_to_string(&mut text).is_err());
+    }
+
+    #[test]
+    fn test_directory_mount() {
+        let root = env::temp_dir().join("lazybox_assets_test_directory_mount");
+        let dir = root.join("mounted");
+        fs::create_dir_all(&dir).unwrap();
+        File::create(dir.join("inside.txt")).unwrap().write_all(b"inside").unwrap();
+        File::create(root.join("outside.txt")).unwrap().write_all(b"outside").unwrap();
+
+        let mut vfs = Vfs::new();
+        vfs.mount("assets", DirectoryMount::new(dir.clone()));
+        assert_eq!(read(&vfs, "assets/inside.txt"), "inside");
+        assert_eq!(read(&vfs, "assets/./inside.txt"), "inside");
+        assert_eq!(vfs.real_path("assets/inside.txt"), Some(dir.join("inside.txt")));
+
+        let mount = DirectoryMount::new(dir.clone());
+        let outside = root.join("outside.txt");
+        for path in &["../outside.txt", "./../outside.txt", outside.to_str().unwrap()] {
+            assert!(!mount.contains(path), "{}", path);
+            assert!(mount.open(path).is_err(), "{}", path);
+            assert!(mount.real_path(path).is_none(), "{}", path);
+        }
+        assert!(!vfs.contains("assets/../outside.txt"));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,78 @@
+//! File modification polling, used for hot reloading
+//!
+//! The watcher compares file modification times, it does not need any
+//! platform specific notification mechanism. It can watch files that are not
+//! assets, such as settings overrides.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+pub struct FileWatcher {
+    files: HashMap<PathBuf, Option<SystemTime>>,
+    interval: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl FileWatcher {
+    /// Creates a watcher checking the files at most once per `interval`.
+    pub fn new(interval: Duration) -> Self {
+        FileWatcher {
+            files: HashMap::new(),
+            interval: interval,
+            last_poll: None,
+        }
+    }
+
+    pub fn watch<P: Into<PathBuf>>(&mut self, path: P) {
+        let path = path.into();
+        let modified = modified_time(&path);
+        self.files.insert(path, modified);
+    }
+
+    /// Stops watching a file, returns false if it was not watched.
+    pub fn unwatch<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        self.files.remove(path.as_ref()).is_some()
+    }
+
+    pub fn is_watching<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.files.contains_key(path.as_ref())
+    }
+
+    pub fn files(&self) -> Vec<&Path> {
+        self.files.keys().map(|path| path.as_path()).collect()
+    }
+
+    /// Returns the files modified since the last poll.
+    ///
+    /// Returns nothing if the interval has not elapsed since the last poll.
+    /// Missing files are not reported, a file being saved may briefly disappear.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        if let Some(last_poll) = self.last_poll {
+            if now.duration_since(last_poll) < self.interval {
+                return Vec::new();
+            }
+        }
+        self.last_poll = Some(now);
+
+        let mut changed = Vec::new();
+        for (path, last_modified) in &mut self.files {
+            let modified = modified_time(path);
+
+            if modified.is_some() && modified != *last_modified {
+                changed.push(path.clone());
+            }
+            if modified.is_some() {
+                *last_modified = modified;
+            }
+        }
+
+        changed
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
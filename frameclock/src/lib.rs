@@ -1,43 +1,206 @@
 extern crate time;
 
-pub struct FrameClock {
+mod stats;
+pub use self::stats::*;
+
+/// The number of fixed updates elapsed since the clock started.
+pub type Tick = u64;
+
+/// A monotonic time, in seconds.
+pub trait TimeSource {
+    fn now(&self) -> f64;
+}
+
+/// The monotonic time of the system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MonotonicTime;
+
+impl TimeSource for MonotonicTime {
+    fn now(&self) -> f64 {
+        time::precise_time_s()
+    }
+}
+
+/// A time that only moves when advanced, to drive a clock from tests or replays.
+///
+/// ```
+/// use lazybox_frameclock::{FrameClock, ManualTime};
+///
+/// let mut clock = FrameClock::with_source(0.5, ManualTime::new());
+/// clock.source_mut().advance(1.25);
+/// clock.reset();
+/// assert_eq!(clock.drain_updates().count(), 2);
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ManualTime {
+    now: f64,
+}
+
+impl ManualTime {
+    pub fn new() -> Self {
+        ManualTime { now: 0. }
+    }
+
+    pub fn advance(&mut self, seconds: f64) {
+        self.now += seconds;
+    }
+
+    pub fn set(&mut self, now: f64) {
+        self.now = now;
+    }
+}
+
+impl TimeSource for ManualTime {
+    fn now(&self) -> f64 {
+        self.now
+    }
+}
+
+pub struct FrameClock<T: TimeSource = MonotonicTime> {
+    source: T,
     mark: f64,
     timestep: f64,
     accumulator: f64,
+    ticks: Tick,
+    scaled_delta: f64,
+    max_frame_time: Option<f64>,
+    max_steps: Option<u32>,
+    time_scale: f64,
+    paused: bool,
 }
 
 impl FrameClock {
     pub fn start(timestep: f64) -> Self {
+        FrameClock::with_source(timestep, MonotonicTime)
+    }
+}
+
+impl<T: TimeSource> FrameClock<T> {
+    pub fn with_source(timestep: f64, source: T) -> Self {
         FrameClock {
-            mark: time::precise_time_s(),
+            mark: source.now(),
+            source: source,
             timestep: timestep,
             accumulator: 0.,
+            ticks: 0,
+            scaled_delta: 0.,
+            max_frame_time: None,
+            max_steps: None,
+            time_scale: 1.,
+            paused: false,
         }
     }
 
+    /// Starts a new frame and returns the real time elapsed since the last one.
+    ///
+    /// The time given to the fixed updates is clamped to the max frame time, then scaled.
     pub fn reset(&mut self) -> f64 {
-        let now = time::precise_time_s();
+        let now = self.source.now();
         let delta = now - self.mark;
         self.mark = now;
-        self.accumulator += delta;
+
+        let clamped = self.max_frame_time.map_or(delta, |max| delta.min(max));
+        self.scaled_delta = if self.paused { 0. } else { clamped * self.time_scale };
+        self.accumulator += self.scaled_delta;
         delta
     }
 
-    pub fn drain_updates(&mut self) -> UpdatesDrain {
-        UpdatesDrain { clock: self }
+    pub fn source(&self) -> &T {
+        &self.source
+    }
+
+    pub fn source_mut(&mut self) -> &mut T {
+        &mut self.source
+    }
+
+    /// Returns the time given to the fixed updates by the last `reset`.
+    pub fn scaled_delta(&self) -> f64 {
+        self.scaled_delta
+    }
+
+    /// Limits the time a frame can add, so that a long stall such as a breakpoint is not
+    /// caught up.
+    pub fn set_max_frame_time(&mut self, max: Option<f64>) {
+        self.max_frame_time = max;
+    }
+
+    /// Limits the number of fixed updates per frame, the time of the skipped ones is dropped.
+    pub fn set_max_steps(&mut self, max: Option<u32>) {
+        self.max_steps = max;
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Scales the time given to the fixed updates, such as `0.5` for slow motion.
+    ///
+    /// Negative and NaN scales are clamped to 0, `pause` is the way to stop the time.
+    pub fn set_time_scale(&mut self, scale: f64) {
+        self.time_scale = scale.max(0.);
+    }
+
+    /// Stops giving time to the fixed updates, until `resume` is called.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Queues a single fixed update, to step through a paused game.
+    pub fn step(&mut self) {
+        self.accumulator += self.timestep;
+    }
+
+    /// Returns the number of fixed updates drained so far.
+    pub fn ticks(&self) -> Tick {
+        self.ticks
+    }
+
+    pub fn timestep(&self) -> f64 {
+        self.timestep
+    }
+
+    /// Returns how far the clock is from the last fixed update to the next one, from 0 to 1.
+    ///
+    /// Call it after draining the updates to blend the previous and current fixed states when
+    /// rendering.
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.timestep).min(1.)
+    }
+
+    pub fn drain_updates(&mut self) -> UpdatesDrain<T> {
+        UpdatesDrain {
+            clock: self,
+            steps: 0,
+        }
     }
 }
 
-pub struct UpdatesDrain<'a> {
-    clock: &'a mut FrameClock,
+pub struct UpdatesDrain<'a, T: 'a + TimeSource = MonotonicTime> {
+    clock: &'a mut FrameClock<T>,
+    steps: u32,
 }
 
-impl<'a> Iterator for UpdatesDrain<'a> {
+impl<'a, T: TimeSource> Iterator for UpdatesDrain<'a, T> {
     type Item = ();
 
     fn next(&mut self) -> Option<Self::Item> {
+        if self.clock.max_steps.map_or(false, |max| self.steps >= max) {
+            self.clock.accumulator %= self.clock.timestep;
+            return None;
+        }
+
         if self.clock.accumulator >= self.clock.timestep {
             self.clock.accumulator -= self.clock.timestep;
+            self.steps += 1;
+            self.clock.ticks += 1;
             Some(())
         } else {
             None
@@ -73,4 +236,135 @@ impl FpsCounter {
             None
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock() -> FrameClock<ManualTime> {
+        FrameClock::with_source(0.25, ManualTime::new())
+    }
+
+    /// Runs a frame after `seconds`, returns the number of fixed updates.
+    fn frame(clock: &mut FrameClock<ManualTime>, seconds: f64) -> usize {
+        clock.source_mut().advance(seconds);
+        clock.reset();
+        clock.drain_updates().count()
+    }
+
+    #[test]
+    fn test_max_frame_time() {
+        let mut clock = clock();
+        clock.set_max_frame_time(Some(1.));
+
+        clock.source_mut().advance(10.);
+        assert_eq!(clock.reset(), 10.);
+        assert_eq!(clock.scaled_delta(), 1.);
+        assert_eq!(clock.drain_updates().count(), 4);
+
+        assert_eq!(frame(&mut clock, 0.5), 2);
+        clock.set_max_frame_time(None);
+        assert_eq!(frame(&mut clock, 10.), 40);
+    }
+
+    #[test]
+    fn test_max_steps() {
+        let mut clock = clock();
+        clock.set_max_steps(Some(3));
+
+        assert_eq!(frame(&mut clock, 2.125), 3);
+        assert_eq!(clock.alpha(), 0.5);
+        assert_eq!(frame(&mut clock, 0.), 0);
+        assert_eq!(frame(&mut clock, 0.5), 2);
+        assert_eq!(clock.ticks(), 5);
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut clock = clock();
+        assert_eq!(frame(&mut clock, 0.375), 1);
+
+        clock.pause();
+        assert!(clock.is_paused());
+        assert_eq!(frame(&mut clock, 1.), 0);
+        assert_eq!(clock.scaled_delta(), 0.);
+        assert_eq!(clock.alpha(), 0.5);
+
+        // The time of the pause is not caught up
+        clock.resume();
+        assert_eq!(frame(&mut clock, 0.125), 1);
+        assert_eq!(clock.ticks(), 2);
+    }
+
+    #[test]
+    fn test_step() {
+        let mut clock = clock();
+        assert_eq!(frame(&mut clock, 0.125), 0);
+        clock.pause();
+
+        clock.step();
+        assert_eq!(frame(&mut clock, 1.), 1);
+        assert_eq!(clock.alpha(), 0.5);
+        assert_eq!(frame(&mut clock, 1.), 0);
+
+        clock.step();
+        clock.step();
+        assert_eq!(frame(&mut clock, 0.), 2);
+        assert_eq!(clock.ticks(), 3);
+    }
+
+    #[test]
+    fn test_time_scale() {
+        let mut clock = clock();
+        clock.set_time_scale(0.5);
+        assert_eq!(frame(&mut clock, 1.), 2);
+        assert_eq!(clock.scaled_delta(), 0.5);
+
+        clock.set_time_scale(2.);
+        assert_eq!(frame(&mut clock, 1.), 8);
+
+        for &scale in &[0., ::std::f64::NAN, -1.] {
+            clock.set_time_scale(scale);
+            assert_eq!(clock.time_scale(), 0.);
+            assert_eq!(frame(&mut clock, 1.), 0);
+            assert_eq!(clock.alpha(), 0.);
+        }
+    }
+
+    #[test]
+    fn test_manual_time() {
+        let mut clock = clock();
+        assert_eq!(clock.reset(), 0.);
+        assert_eq!(clock.ticks(), 0);
+
+        assert_eq!(frame(&mut clock, 0.375), 1);
+        assert_eq!(clock.alpha(), 0.5);
+        assert_eq!(frame(&mut clock, 0.375), 2);
+        assert_eq!(clock.alpha(), 0.);
+        assert_eq!(frame(&mut clock, 0.0625), 0);
+        assert_eq!(clock.alpha(), 0.25);
+        assert_eq!(clock.ticks(), 3);
+
+        clock.source_mut().set(2.);
+        assert_eq!(clock.reset(), 1.1875);
+        assert_eq!(clock.alpha(), 1.);
+        assert_eq!(clock.drain_updates().count(), 5);
+        assert_eq!(clock.alpha(), 0.);
+        assert_eq!(clock.ticks(), 8);
+    }
+
+    #[test]
+    fn test_fps_counter() {
+        let mut time = ManualTime::new();
+        let mut counter = FpsCounter::new(1.);
+
+        let mut last = time.now();
+        let mut samples = Vec::new();
+        for _ in 0..12 {
+            time.advance(0.125);
+            samples.extend(counter.update(time.now() - last));
+            last = time.now();
+        }
+        assert_eq!(samples, [8.]);
+    }
+}
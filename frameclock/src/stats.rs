@@ -0,0 +1,228 @@
+//! Frame time statistics over the last frames
+
+use std::cmp::Ordering;
+use std::collections::{vec_deque, VecDeque};
+
+/// Keeps the times of the last frames, for percentiles, graphs and spike detection.
+pub struct FrameStats {
+    history: VecDeque<f64>,
+    capacity: usize,
+    spike_threshold: Option<f64>,
+    spikes: u64,
+}
+
+impl FrameStats {
+    /// Keeps the times of the last `capacity` frames.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        FrameStats {
+            history: VecDeque::with_capacity(capacity),
+            capacity: capacity,
+            spike_threshold: None,
+            spikes: 0,
+        }
+    }
+
+    /// Frames longer than `threshold` seconds are spikes, none by default.
+    pub fn set_spike_threshold(&mut self, threshold: Option<f64>) {
+        self.spike_threshold = threshold;
+    }
+
+    /// Records the time of a frame, returns true if it is a spike.
+    pub fn update(&mut self, delta: f64) -> bool {
+        if self.history.len() == self.capacity {
+            self.history.pop_front();
+        }
+        self.history.push_back(delta);
+
+        let spike = self.spike_threshold.map_or(false, |threshold| delta > threshold);
+        if spike {
+            self.spikes += 1;
+        }
+        spike
+    }
+
+    /// Returns the number of spikes since the creation or the last `clear`.
+    pub fn spikes(&self) -> u64 {
+        self.spikes
+    }
+
+    /// Iterates over the recorded frame times, from the oldest to the newest.
+    pub fn history(&self) -> vec_deque::Iter<f64> {
+        self.history.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+        self.spikes = 0;
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        self.history.iter().cloned().fold(None, |min, t| Some(min.map_or(t, |min: f64| min.min(t))))
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        self.history.iter().cloned().fold(None, |max, t| Some(max.map_or(t, |max: f64| max.max(t))))
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.history.is_empty() {
+            None
+        } else {
+            Some(self.history.iter().sum::<f64>() / self.history.len() as f64)
+        }
+    }
+
+    /// Returns the frame time that `percent` percent of the frames do not exceed.
+    pub fn percentile(&self, percent: f64) -> Option<f64> {
+        percentile(&self.sorted(), percent)
+    }
+
+    /// Computes every statistic with a single sort, `None` if no frame was recorded.
+    pub fn summary(&self) -> Option<FrameSummary> {
+        let sorted = self.sorted();
+
+        self.mean().map(|mean| {
+            FrameSummary {
+                min: sorted[0],
+                max: sorted[sorted.len() - 1],
+                mean: mean,
+                p50: percentile(&sorted, 50.).unwrap(),
+                p95: percentile(&sorted, 95.).unwrap(),
+                p99: percentile(&sorted, 99.).unwrap(),
+            }
+        })
+    }
+
+    fn sorted(&self) -> Vec<f64> {
+        let mut sorted: Vec<_> = self.history.iter().cloned().collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+        sorted
+    }
+}
+
+/// The nearest-rank percentile of sorted frame times.
+fn percentile(sorted: &[f64], percent: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+
+    let rank = (percent / 100. * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.max(1).min(sorted.len()) - 1])
+}
+
+/// Frame time statistics, in seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameSummary {
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+}
+
+impl FrameSummary {
+    /// Returns the average number of frames per second.
+    pub fn fps(&self) -> f64 {
+        1. / self.mean
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorded(times: &[f64]) -> FrameStats {
+        let mut stats = FrameStats::new(times.len());
+        for &time in times {
+            stats.update(time);
+        }
+        stats
+    }
+
+    #[test]
+    fn test_percentile() {
+        let stats = recorded(&[7., 3., 10., 1., 5., 2., 9., 4., 8., 6.]);
+        assert_eq!(stats.percentile(0.), Some(1.));
+        assert_eq!(stats.percentile(10.), Some(1.));
+        assert_eq!(stats.percentile(11.), Some(2.));
+        assert_eq!(stats.percentile(50.), Some(5.));
+        assert_eq!(stats.percentile(95.), Some(10.));
+        assert_eq!(stats.percentile(100.), Some(10.));
+
+        let times: Vec<_> = (1..21).map(|t| t as f64).collect();
+        let stats = recorded(&times);
+        assert_eq!(stats.percentile(95.), Some(19.));
+        assert_eq!(stats.percentile(99.), Some(20.));
+    }
+
+    #[test]
+    fn test_history() {
+        let mut stats = FrameStats::new(3);
+        for &time in &[1., 2., 3., 4.] {
+            stats.update(time);
+        }
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats.history().cloned().collect::<Vec<_>>(), [2., 3., 4.]);
+        assert_eq!(stats.min(), Some(2.));
+        assert_eq!(stats.mean(), Some(3.));
+
+        let mut stats = FrameStats::new(0);
+        stats.update(1.);
+        stats.update(2.);
+        assert_eq!(stats.history().cloned().collect::<Vec<_>>(), [2.]);
+    }
+
+    #[test]
+    fn test_spikes() {
+        let mut stats = FrameStats::new(2);
+        assert!(!stats.update(1.));
+
+        stats.set_spike_threshold(Some(0.05));
+        assert!(stats.update(0.1));
+        assert!(!stats.update(0.05));
+        assert!(stats.update(0.2));
+        assert_eq!(stats.spikes(), 2);
+
+        stats.clear();
+        assert!(stats.is_empty());
+        assert_eq!(stats.spikes(), 0);
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut stats = FrameStats::new(4);
+        assert_eq!(stats.summary(), None);
+        assert_eq!(stats.percentile(50.), None);
+        assert_eq!((stats.min(), stats.max(), stats.mean()), (None, None, None));
+
+        stats.update(0.02);
+        let summary = stats.summary().unwrap();
+        assert_eq!(summary,
+                   FrameSummary {
+                       min: 0.02,
+                       max: 0.02,
+                       mean: 0.02,
+                       p50: 0.02,
+                       p95: 0.02,
+                       p99: 0.02,
+                   });
+        assert_eq!(summary.fps(), 50.);
+
+        for &time in &[0.01, 0.04, 0.03] {
+            stats.update(time);
+        }
+        let summary = stats.summary().unwrap();
+        assert_eq!((summary.min, summary.max, summary.p50, summary.p99), (0.01, 0.04, 0.02, 0.04));
+        assert_eq!(summary.mean, stats.mean().unwrap());
+    }
+}
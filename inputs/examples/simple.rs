@@ -38,7 +38,7 @@ fn main() {
 
     let mut frameclock = FrameClock::start(1. / 60.);
     'main: loop {
-        frameclock.reset();
+        inputs.new_frame(frameclock.reset());
 
         for event in window.poll_events() {
             match event {
@@ -46,7 +46,7 @@ fn main() {
                 _ => ()
             }
 
-            inputs.handle_event(&event);
+            inputs.handle_glutin_event(&event);
         }
 
         for _ in frameclock.drain_updates() {
@@ -11,6 +11,15 @@ error_chain! {
         RulesFormat
         InterfaceFormat
         ConditionFormat
+        AxisFormat
         UnknownInterface
+        UnknownAction
+        ProfileEmit {
+            description("could not write the interaction profile")
+        }
+        RecordFormat(line: usize) {
+            description("invalid input record")
+            display("invalid input record at line {}", line)
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,319 @@
+//! Rules combining several inputs
+//!
+//! Chords are inputs that only trigger while some modifiers are held.
+//! Gestures depend on time: double taps, long presses and sequences.
+//! Time is measured in seconds, from the frame deltas given to `Inputs::new_frame`.
+
+use event::ElementState;
+use interaction::{Input, Condition, Element};
+use state::InputState;
+
+pub(crate) const DEFAULT_DOUBLE_TAP_WINDOW: f64 = 0.25;
+pub(crate) const DEFAULT_SEQUENCE_TIMEOUT: f64 = 0.5;
+
+/// An input triggering only while all of its modifiers are held.
+pub(crate) struct Chord {
+    modifiers: Vec<Condition>,
+    input: Input,
+}
+
+impl Chord {
+    pub fn new(modifiers: Vec<Condition>, input: Input) -> Self {
+        Chord {
+            modifiers: modifiers,
+            input: input,
+        }
+    }
+
+    pub fn modifier_count(&self) -> usize {
+        self.modifiers.len()
+    }
+
+    pub fn matches(&self, input: &Input, state: &InputState) -> bool {
+        self.input == *input && self.modifiers.iter().all(|m| m.evaluate(state))
+    }
+}
+
+pub(crate) enum Gesture {
+    /// The element is pressed twice within `window` seconds.
+    DoubleTap {
+        element: Element,
+        window: f64,
+        first_tap: Option<f64>,
+        released: bool,
+    },
+    /// The element is held for `duration` seconds, triggers once per press.
+    LongPress {
+        element: Element,
+        duration: f64,
+        pressed_at: Option<f64>,
+        triggered: bool,
+    },
+    /// The steps are matched in order, each within `timeout` seconds of the previous one.
+    Sequence {
+        steps: Vec<Chord>,
+        timeout: f64,
+        progress: usize,
+        last_step: f64,
+    },
+}
+
+impl Gesture {
+    pub fn double_tap(element: Element) -> Self {
+        Gesture::DoubleTap {
+            element: element,
+            window: DEFAULT_DOUBLE_TAP_WINDOW,
+            first_tap: None,
+            released: false,
+        }
+    }
+
+    pub fn long_press(element: Element, duration: f64) -> Self {
+        Gesture::LongPress {
+            element: element,
+            duration: duration,
+            pressed_at: None,
+            triggered: false,
+        }
+    }
+
+    pub fn sequence(steps: Vec<Chord>) -> Self {
+        Gesture::Sequence {
+            steps: steps,
+            timeout: DEFAULT_SEQUENCE_TIMEOUT,
+            progress: 0,
+            last_step: 0.,
+        }
+    }
+
+    /// Sets the double tap window or the sequence timeout.
+    ///
+    /// Returns false if the gesture has no timeout.
+    pub fn set_timeout(&mut self, timeout: f64) -> bool {
+        match self {
+            &mut Gesture::DoubleTap { ref mut window, .. } => *window = timeout,
+            &mut Gesture::Sequence { timeout: ref mut t, .. } => *t = timeout,
+            &mut Gesture::LongPress { .. } => return false,
+        }
+
+        true
+    }
+
+    /// Feeds an input to the gesture, returns true if it completes the gesture.
+    pub fn on_input(&mut self, input: &Input, state: &InputState, now: f64) -> bool {
+        match self {
+            &mut Gesture::DoubleTap { element, window, ref mut first_tap, ref mut released } => {
+                if *input == element.input(ElementState::Pressed) {
+                    match *first_tap {
+                        Some(tap) if *released && now - tap <= window => {
+                            *first_tap = None;
+                            return true;
+                        }
+                        // Repeated press events while the element is held
+                        Some(_) if !*released => {}
+                        _ => {
+                            *first_tap = Some(now);
+                            *released = false;
+                        }
+                    }
+                } else if *input == element.input(ElementState::Released) {
+                    *released = true;
+                }
+
+                false
+            }
+            &mut Gesture::LongPress { element, ref mut pressed_at, ref mut triggered, .. } => {
+                if *input == element.input(ElementState::Pressed) {
+                    if pressed_at.is_none() {
+                        *pressed_at = Some(now);
+                        *triggered = false;
+                    }
+                } else if *input == element.input(ElementState::Released) {
+                    *pressed_at = None;
+                }
+
+                false
+            }
+            &mut Gesture::Sequence { ref steps, timeout, ref mut progress, ref mut last_step } => {
+                if *progress > 0 && now - *last_step > timeout {
+                    *progress = 0;
+                }
+
+                if steps[*progress].matches(input, state) {
+                    *progress += 1;
+                    *last_step = now;
+
+                    if *progress == steps.len() {
+                        *progress = 0;
+                        return true;
+                    }
+                } else if input.is_press() {
+                    // A wrong press breaks the sequence, but it may start a new one
+                    *progress = if steps[0].matches(input, state) { 1 } else { 0 };
+                    *last_step = now;
+                }
+
+                false
+            }
+        }
+    }
+
+    /// Checks the gesture against the time, returns true if it completes the gesture.
+    pub fn on_time(&mut self, now: f64) -> bool {
+        match self {
+            &mut Gesture::LongPress { duration,
+                                      pressed_at: Some(pressed_at),
+                                      ref mut triggered,
+                                      .. } => {
+                if !*triggered && now - pressed_at >= duration {
+                    *triggered = true;
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use event::Key;
+    use event::ElementState::*;
+
+    fn press(state: &mut InputState, key: Key) -> Input {
+        state.update_key(key, Pressed);
+        Input::Key(Pressed, key)
+    }
+
+    fn release(state: &mut InputState, key: Key) -> Input {
+        state.update_key(key, Released);
+        Input::Key(Released, key)
+    }
+
+    fn ctrl() -> Condition {
+        Condition::AnyKeyHeld(vec![Key::LControl, Key::RControl])
+    }
+
+    #[test]
+    fn test_double_tap() {
+        let mut state = InputState::new();
+        let mut gesture = Gesture::double_tap(Element::Key(Key::Space));
+
+        assert!(!gesture.on_input(&press(&mut state, Key::Space), &state, 0.));
+        // Repeated press events while held are not a second tap
+        assert!(!gesture.on_input(&press(&mut state, Key::Space), &state, 0.1));
+        assert!(!gesture.on_input(&release(&mut state, Key::Space), &state, 0.15));
+        assert!(gesture.on_input(&press(&mut state, Key::Space), &state, 0.2));
+        assert!(!gesture.on_input(&release(&mut state, Key::Space), &state, 0.25));
+
+        // The second tap comes too late, but starts a new double tap
+        assert!(!gesture.on_input(&press(&mut state, Key::Space), &state, 1.));
+        assert!(!gesture.on_input(&release(&mut state, Key::Space), &state, 1.1));
+        assert!(!gesture.on_input(&press(&mut state, Key::Space), &state, 1.3));
+        assert!(!gesture.on_input(&release(&mut state, Key::Space), &state, 1.35));
+        assert!(gesture.on_input(&press(&mut state, Key::Space), &state, 1.5));
+    }
+
+    #[test]
+    fn test_double_tap_timeout() {
+        let mut state = InputState::new();
+        let mut gesture = Gesture::double_tap(Element::Key(Key::Space));
+        assert!(gesture.set_timeout(1.));
+
+        gesture.on_input(&press(&mut state, Key::Space), &state, 0.);
+        gesture.on_input(&release(&mut state, Key::Space), &state, 0.5);
+        assert!(gesture.on_input(&press(&mut state, Key::Space), &state, 1.));
+    }
+
+    #[test]
+    fn test_long_press() {
+        let mut state = InputState::new();
+        let mut gesture = Gesture::long_press(Element::Key(Key::E), 0.5);
+        assert!(!gesture.set_timeout(1.));
+
+        gesture.on_input(&press(&mut state, Key::E), &state, 0.);
+        assert!(!gesture.on_time(0.4));
+        assert!(gesture.on_time(0.5));
+        // Only once per press
+        assert!(!gesture.on_time(0.6));
+        gesture.on_input(&press(&mut state, Key::E), &state, 0.7);
+        assert!(!gesture.on_time(2.));
+
+        // Released before the threshold
+        gesture.on_input(&release(&mut state, Key::E), &state, 2.);
+        gesture.on_input(&press(&mut state, Key::E), &state, 3.);
+        gesture.on_input(&release(&mut state, Key::E), &state, 3.2);
+        assert!(!gesture.on_time(4.));
+
+        gesture.on_input(&press(&mut state, Key::E), &state, 5.);
+        assert!(gesture.on_time(5.5));
+    }
+
+    #[test]
+    fn test_sequence() {
+        let mut state = InputState::new();
+        let step = |key| Chord::new(Vec::new(), Input::Key(Pressed, key));
+        let mut gesture = Gesture::sequence(vec![step(Key::Down), step(Key::Right), step(Key::A)]);
+
+        assert!(!gesture.on_input(&press(&mut state, Key::Down), &state, 0.));
+        // Releases do not break the sequence
+        assert!(!gesture.on_input(&release(&mut state, Key::Down), &state, 0.1));
+        assert!(!gesture.on_input(&press(&mut state, Key::Right), &state, 0.2));
+        assert!(gesture.on_input(&press(&mut state, Key::A), &state, 0.3));
+
+        // A wrong key resets the sequence
+        gesture.on_input(&press(&mut state, Key::Down), &state, 1.);
+        gesture.on_input(&press(&mut state, Key::Up), &state, 1.1);
+        assert!(!gesture.on_input(&press(&mut state, Key::Right), &state, 1.2));
+        assert!(!gesture.on_input(&press(&mut state, Key::A), &state, 1.3));
+
+        // The wrong key may start a new sequence
+        gesture.on_input(&press(&mut state, Key::Down), &state, 2.);
+        gesture.on_input(&press(&mut state, Key::Down), &state, 2.1);
+        gesture.on_input(&press(&mut state, Key::Right), &state, 2.2);
+        assert!(gesture.on_input(&press(&mut state, Key::A), &state, 2.3));
+
+        // Too slow
+        gesture.on_input(&press(&mut state, Key::Down), &state, 3.);
+        gesture.on_input(&press(&mut state, Key::Right), &state, 3.6);
+        assert!(!gesture.on_input(&press(&mut state, Key::A), &state, 3.7));
+    }
+
+    #[test]
+    fn test_chord() {
+        let mut state = InputState::new();
+        let save = Chord::new(vec![ctrl()], Input::Key(Pressed, Key::S));
+        assert_eq!(save.modifier_count(), 1);
+
+        assert!(!save.matches(&press(&mut state, Key::S), &state));
+        release(&mut state, Key::S);
+
+        press(&mut state, Key::RControl);
+        assert!(save.matches(&press(&mut state, Key::S), &state));
+        assert!(!save.matches(&release(&mut state, Key::S), &state));
+
+        release(&mut state, Key::RControl);
+        assert!(!save.matches(&press(&mut state, Key::S), &state));
+    }
+
+    #[test]
+    fn test_chord_release_order() {
+        let mut state = InputState::new();
+        let chord = Chord::new(vec![ctrl()], Input::Key(Released, Key::S));
+
+        // The key is released while the modifier is still held
+        press(&mut state, Key::LControl);
+        press(&mut state, Key::S);
+        assert!(chord.matches(&release(&mut state, Key::S), &state));
+        release(&mut state, Key::LControl);
+
+        // The modifier is released first
+        press(&mut state, Key::LControl);
+        press(&mut state, Key::S);
+        release(&mut state, Key::LControl);
+        assert!(!chord.matches(&release(&mut state, Key::S), &state));
+    }
+}
@@ -0,0 +1,51 @@
+//! The glutin backend
+//!
+//! Enabled by the `glutin` feature.
+
+use glutin;
+use event::{Event, ElementState, MouseButton};
+
+/// Used to convert pixel scroll deltas into lines.
+pub const PIXELS_PER_LINE: f32 = 20.;
+
+impl From<glutin::ElementState> for ElementState {
+    fn from(state: glutin::ElementState) -> ElementState {
+        match state {
+            glutin::ElementState::Pressed => ElementState::Pressed,
+            glutin::ElementState::Released => ElementState::Released,
+        }
+    }
+}
+
+impl From<glutin::MouseButton> for MouseButton {
+    fn from(button: glutin::MouseButton) -> MouseButton {
+        match button {
+            glutin::MouseButton::Left => MouseButton::Left,
+            glutin::MouseButton::Right => MouseButton::Right,
+            glutin::MouseButton::Middle => MouseButton::Middle,
+            glutin::MouseButton::Other(code) => MouseButton::Other(code),
+        }
+    }
+}
+
+/// Converts a glutin event, returns `None` if it is not an input event.
+pub fn convert_event(event: &glutin::Event) -> Option<Event> {
+    match event {
+        &glutin::Event::KeyboardInput(state, scan_code, key) => {
+            Some(Event::KeyboardInput(state.into(), scan_code, key.map(Into::into)))
+        }
+        &glutin::Event::MouseMoved(x, y) => Some(Event::MouseMoved(x, y)),
+        &glutin::Event::MouseInput(state, button) => {
+            Some(Event::MouseInput(state.into(), button.into()))
+        }
+        &glutin::Event::MouseWheel(glutin::MouseScrollDelta::LineDelta(x, y), ..) => {
+            Some(Event::MouseWheel(x, y))
+        }
+        &glutin::Event::MouseWheel(glutin::MouseScrollDelta::PixelDelta(x, y), ..) => {
+            Some(Event::MouseWheel(x / PIXELS_PER_LINE, y / PIXELS_PER_LINE))
+        }
+        &glutin::Event::ReceivedCharacter(c) => Some(Event::ReceivedCharacter(c)),
+        &glutin::Event::Focused(focused) => Some(Event::Focused(focused)),
+        _ => None,
+    }
+}
@@ -1,18 +1,108 @@
 use std::collections::{HashMap, HashSet};
-use glutin::{VirtualKeyCode, MouseButton, ElementState};
+use event::{Key, MouseButton, ElementState, GamepadButton, Axis};
 use yaml_rust::Yaml;
+use yaml_rust::yaml::Hash;
 use state::InputState;
+use gesture::{Chord, Gesture};
+use rebind::{self, Capture};
 use error::{ErrorKind, Result};
 
+/// The modifiers usable in chords, with the keys that hold them.
+pub(crate) const MODIFIERS: &'static [(&'static str, &'static [Key])] =
+    &[("Ctrl", &[Key::LControl, Key::RControl]),
+      ("Shift", &[Key::LShift, Key::RShift]),
+      ("Alt", &[Key::LAlt, Key::RAlt, Key::LMenu, Key::RMenu]),
+      ("Super", &[Key::LWin, Key::RWin])];
+
 #[derive(Clone, Eq, PartialEq, Hash)]
 pub(crate) enum Input {
-    Key(ElementState, VirtualKeyCode),
+    Key(ElementState, Key),
     MouseButton(ElementState, MouseButton),
+    GamepadButton(ElementState, GamepadButton),
+    MouseWheel(WheelDirection),
+}
+
+impl Input {
+    pub fn is_press(&self) -> bool {
+        match self {
+            &Input::Key(ElementState::Pressed, _) |
+            &Input::MouseButton(ElementState::Pressed, _) |
+            &Input::GamepadButton(ElementState::Pressed, _) |
+            &Input::MouseWheel(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_release(&self) -> bool {
+        match self {
+            &Input::Key(ElementState::Released, _) |
+            &Input::MouseButton(ElementState::Released, _) |
+            &Input::GamepadButton(ElementState::Released, _) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+pub(crate) enum WheelDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Something that can be pressed and released.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Element {
+    Key(Key),
+    MouseButton(MouseButton),
+    GamepadButton(GamepadButton),
+}
+
+impl Element {
+    pub fn input(&self, state: ElementState) -> Input {
+        match *self {
+            Element::Key(key) => Input::Key(state, key),
+            Element::MouseButton(button) => Input::MouseButton(state, button),
+            Element::GamepadButton(button) => Input::GamepadButton(state, button),
+        }
+    }
+
+    pub fn held(&self) -> Condition {
+        match *self {
+            Element::Key(key) => Condition::KeyHeld(key),
+            Element::MouseButton(button) => Condition::MouseButtonHeld(button),
+            Element::GamepadButton(button) => Condition::GamepadButtonHeld(button),
+        }
+    }
+
+    pub fn is_pressed(&self, state: &InputState) -> bool {
+        match *self {
+            Element::Key(ref key) => state.is_key_pressed(key),
+            Element::MouseButton(ref button) => state.is_mouse_button_pressed(button),
+            Element::GamepadButton(ref button) => state.is_gamepad_button_pressed(button),
+        }
+    }
+
+    pub fn is_released(&self, state: &InputState) -> bool {
+        match *self {
+            Element::Key(ref key) => state.is_key_released(key),
+            Element::MouseButton(ref button) => state.is_mouse_button_released(button),
+            Element::GamepadButton(ref button) => state.is_gamepad_button_released(button),
+        }
+    }
 }
 
 pub(crate) enum Condition {
-    KeyHeld(VirtualKeyCode),
+    KeyHeld(Key),
     MouseButtonHeld(MouseButton),
+    GamepadButtonHeld(GamepadButton),
+    /// Any of the keys is held, used for modifiers that have a left and a right key.
+    AnyKeyHeld(Vec<Key>),
+    /// The element was pressed during the frame.
+    JustPressed(Element),
+    /// The element was released during the frame.
+    JustReleased(Element),
 }
 
 impl Condition {
@@ -20,12 +110,27 @@ impl Condition {
         match self {
             &Condition::KeyHeld(ref key) => state.is_key_held(key),
             &Condition::MouseButtonHeld(ref button) => state.is_mouse_button_held(button),
+            &Condition::GamepadButtonHeld(ref button) => state.is_gamepad_button_held(button),
+            &Condition::AnyKeyHeld(ref keys) => keys.iter().any(|key| state.is_key_held(key)),
+            &Condition::JustPressed(ref element) => element.is_pressed(state),
+            &Condition::JustReleased(ref element) => element.is_released(state),
         }
     }
 }
 
 pub type Action = &'static str;
 
+/// Which inputs an interface hides from the interfaces below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Consumption {
+    /// Every interface sees the inputs.
+    Nothing,
+    /// The inputs triggering an action of the interface.
+    Matched,
+    /// Every input, for modal interfaces such as menus.
+    Everything,
+}
+
 pub(crate) struct ConditionalAction {
     action: Action,
     condition: Condition,
@@ -41,25 +146,70 @@ impl ConditionalAction {
     }
 }
 
+/// Binds an analog action to an axis.
+pub(crate) struct AxisBinding {
+    action: Action,
+    axis: Axis,
+    dead_zone: f32,
+    sensitivity: f32,
+    inverted: bool,
+}
+
+impl AxisBinding {
+    pub fn value(&self, state: &InputState) -> f32 {
+        let raw = state.axis(self.axis);
+        if raw.abs() <= self.dead_zone {
+            return 0.;
+        }
+
+        // Rescales the values outside of the dead zone so that they still start from 0
+        let value = raw.signum() * (raw.abs() - self.dead_zone) / (1. - self.dead_zone);
+        let value = value * self.sensitivity;
+
+        if self.inverted { -value } else { value }
+    }
+}
+
 pub(crate) struct Rules {
     by_input: HashMap<Input, Action>,
+    chords: Vec<(Chord, Action)>,
+    gestures: Vec<(Gesture, Action)>,
     others: Vec<ConditionalAction>,
+    axes: Vec<AxisBinding>,
 }
 
 impl Rules {
     pub fn new() -> Self {
         Rules {
             by_input: HashMap::new(),
+            chords: Vec::new(),
+            gestures: Vec::new(),
             others: Vec::new(),
+            axes: Vec::new(),
         }
     }
 
     pub fn clear(&mut self) {
+        self.clear_triggers();
+        self.axes.clear();
+    }
+
+    /// Clears every rule but the axis bindings.
+    pub fn clear_triggers(&mut self) {
         self.by_input.clear();
+        self.chords.clear();
+        self.gestures.clear();
         self.others.clear();
     }
 }
 
+/// A rule as written in the profile, kept to rebuild the rules and to save them.
+pub(crate) struct RuleSource {
+    action: Action,
+    when: String,
+    timeout: Option<i64>,
+}
+
 pub struct InteractionBuilder {
     interfaces: HashMap<&'static str, Interface>,
 }
@@ -75,17 +225,33 @@ impl InteractionBuilder {
     }
 
     pub(crate) fn build(self) -> Interaction {
-        Interaction { interfaces: self.interfaces }
+        let mut interaction = Interaction {
+            interfaces: self.interfaces,
+            stack: Vec::new(),
+            time: 0.,
+            capture: None,
+        };
+        interaction.sort_stack();
+
+        interaction
     }
 }
 
 pub struct InterfaceBuilder {
     actions: HashSet<Action>,
+    priority: i32,
+    enabled: bool,
+    consumption: Consumption,
 }
 
 impl InterfaceBuilder {
     pub fn new() -> Self {
-        InterfaceBuilder { actions: HashSet::new() }
+        InterfaceBuilder {
+            actions: HashSet::new(),
+            priority: 0,
+            enabled: true,
+            consumption: Consumption::Nothing,
+        }
     }
 
     pub fn action(mut self, a: Action) -> Self {
@@ -93,10 +259,33 @@ impl InterfaceBuilder {
         self
     }
 
+    /// Interfaces with a higher priority see the inputs first, defaults to 0.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Whether the interface starts enabled, defaults to true.
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Which inputs are hidden from the interfaces with a lower priority,
+    /// defaults to `Consumption::Nothing`.
+    pub fn consumption(mut self, consumption: Consumption) -> Self {
+        self.consumption = consumption;
+        self
+    }
+
     fn build(self) -> Interface {
         Interface {
             actions: self.actions,
+            priority: self.priority,
+            enabled: self.enabled,
+            consumption: self.consumption,
             rules: Rules::new(),
+            sources: Vec::new(),
             triggered_actions: Vec::new(),
         }
     }
@@ -104,6 +293,10 @@ impl InterfaceBuilder {
 
 pub struct Interaction {
     interfaces: HashMap<&'static str, Interface>,
+    /// The interface names, from the highest priority to the lowest.
+    stack: Vec<&'static str>,
+    time: f64,
+    capture: Option<Capture>,
 }
 
 impl Interaction {
@@ -115,26 +308,238 @@ impl Interaction {
             } else {
                 bail!(ErrorKind::RulesFormat)
             }
+
+            let axes = &profile[name]["axes"];
+            if !axes.is_badvalue() {
+                match axes.as_vec() {
+                    Some(axes) => interface.load_axes(axes)?,
+                    None => bail!(ErrorKind::AxisFormat),
+                }
+            }
         }
 
         Ok(())
     }
 
+    /// Builds a profile that `load_profile` can read back.
+    pub fn save_profile(&self) -> Yaml {
+        let mut profile = Hash::new();
+        for (&name, interface) in &self.interfaces {
+            profile.insert(Yaml::String(name.to_string()), interface.save());
+        }
+
+        Yaml::Hash(profile)
+    }
+
+    /// Adds a rule triggering `action` in the interface `interface_name`.
+    ///
+    /// `when` uses the same syntax as the profiles, for example `Ctrl+S`.
+    pub fn bind(&mut self, interface_name: &str, action: &str, when: &str) -> Result<()> {
+        self.interface_mut_or_err(interface_name)?.bind(action, when, None)
+    }
+
+    /// Removes all the rules triggering `action` in the interface `interface_name`.
+    pub fn unbind(&mut self, interface_name: &str, action: &str) -> Result<()> {
+        self.interface_mut_or_err(interface_name)?.unbind(action)
+    }
+
+    /// Replaces the rules triggering `action` by a single one.
+    pub fn rebind(&mut self, interface_name: &str, action: &str, when: &str) -> Result<()> {
+        let interface = self.interface_mut_or_err(interface_name)?;
+
+        // Checks the rule before removing the old ones
+        if WhenParse::from_str(when).is_none() {
+            bail!(ErrorKind::ConditionFormat);
+        }
+
+        interface.unbind(action)?;
+        interface.bind(action, when, None)
+    }
+
+    /// Returns the actions of the interface that are already triggered by `when`.
+    pub fn conflicts(&self, interface_name: &str, when: &str) -> Result<Vec<Action>> {
+        match self.interfaces.get(interface_name) {
+            Some(interface) => Ok(interface.conflicts(when)),
+            None => bail!(ErrorKind::UnknownInterface),
+        }
+    }
+
+    /// Returns the rules of an interface, as `(action, when)` pairs.
+    pub fn bindings(&self, interface_name: &str) -> Option<Vec<(Action, &str)>> {
+        self.interfaces.get(interface_name).map(|interface| {
+            interface.sources
+                .iter()
+                .map(|source| (source.action, &source.when[..]))
+                .collect()
+        })
+    }
+
+    /// Captures the next input instead of dispatching it to the interfaces.
+    ///
+    /// Modifiers held at that time are captured too. Use `take_captured_input`
+    /// to get the captured rule trigger, which can be given to `bind`.
+    /// The inputs are not dispatched until the capture is taken or cancelled.
+    pub fn capture_next_input(&mut self) {
+        self.capture = Some(Capture::new());
+    }
+
+    pub fn cancel_capture(&mut self) {
+        self.capture = None;
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    /// Returns the captured rule trigger, ending the capture.
+    ///
+    /// Returns `None` while nothing has been captured.
+    pub fn take_captured_input(&mut self) -> Option<String> {
+        let captured = self.capture.as_mut().and_then(Capture::take);
+        if captured.is_some() {
+            self.capture = None;
+        }
+
+        captured
+    }
+
+    /// Enables an interface, returns false if it does not exist.
+    pub fn enable(&mut self, interface_name: &str) -> bool {
+        self.set_enabled(interface_name, true)
+    }
+
+    /// Disables an interface, its triggered actions are cleared.
+    ///
+    /// Returns false if it does not exist.
+    pub fn disable(&mut self, interface_name: &str) -> bool {
+        self.set_enabled(interface_name, false)
+    }
+
+    pub fn is_enabled(&self, interface_name: &str) -> bool {
+        self.interfaces.get(interface_name).map(|i| i.enabled).unwrap_or(false)
+    }
+
+    /// Changes the priority of an interface, returns false if it does not exist.
+    pub fn set_priority(&mut self, interface_name: &str, priority: i32) -> bool {
+        match self.interfaces.get_mut(interface_name) {
+            Some(interface) => interface.priority = priority,
+            None => return false,
+        }
+
+        self.sort_stack();
+        true
+    }
+
+    /// Returns the enabled interfaces, from the highest priority to the lowest.
+    pub fn active_interfaces(&self) -> Vec<&'static str> {
+        self.stack
+            .iter()
+            .cloned()
+            .filter(|name| self.interfaces[name].enabled)
+            .collect()
+    }
+
+    /// Returns true if an enabled interface with a higher priority consumes every input.
+    pub fn is_blocked(&self, interface_name: &str) -> bool {
+        for name in &self.stack {
+            if *name == interface_name {
+                return false;
+            }
+
+            let interface = &self.interfaces[name];
+            if interface.enabled && interface.consumption == Consumption::Everything {
+                return true;
+            }
+        }
+
+        false
+    }
+
     pub(crate) fn interface(&self, name: &str) -> Option<&Interface> {
         self.interfaces.get(name)
     }
 
+    fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.interfaces.get_mut(name) {
+            Some(interface) => {
+                interface.enabled = enabled;
+                if !enabled {
+                    interface.clear_actions();
+                }
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn sort_stack(&mut self) {
+        let mut stack: Vec<(i32, &'static str)> = self.interfaces
+            .iter()
+            .map(|(&name, interface)| (interface.priority, name))
+            .collect();
+        // Highest priority first, ties broken by name to keep the dispatch deterministic
+        stack.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+
+        self.stack = stack.into_iter().map(|(_, name)| name).collect();
+    }
+
+    fn interface_mut_or_err(&mut self, name: &str) -> Result<&mut Interface> {
+        match self.interfaces.get_mut(name) {
+            Some(interface) => Ok(interface),
+            None => bail!(ErrorKind::UnknownInterface),
+        }
+    }
+
+    /// Advances the time used by the timed rules.
+    pub(crate) fn advance_time(&mut self, delta: f64) {
+        self.time += delta;
+    }
+
     pub(crate) fn trigger_input_actions(&mut self, input: &Input, state: &InputState) {
-        // TODO: enable/disable interface dispatch
-        for (_, interface) in &mut self.interfaces {
-            interface.trigger_input_actions(input, state);
+        if let Some(ref mut capture) = self.capture {
+            capture.feed(input, state);
+            return;
+        }
+
+        let mut blocked = false;
+        for name in &self.stack {
+            let interface = self.interfaces.get_mut(name).unwrap();
+            if !interface.enabled {
+                continue;
+            }
+
+            // The blocked interfaces still see the releases, so that the elements pressed
+            // before they were blocked do not stay held in their gestures.
+            if blocked {
+                interface.release(input, state, self.time);
+                continue;
+            }
+
+            let matched = interface.trigger_input_actions(input, state, self.time);
+            blocked = match interface.consumption {
+                Consumption::Everything => true,
+                Consumption::Matched => matched,
+                _ => false,
+            };
+
+            if blocked && !input.is_release() {
+                break;
+            }
         }
     }
 
     pub(crate) fn trigger_state_actions(&mut self, state: &InputState) {
-        // TODO: enable/disable interface dispatch
-        for (_, interface) in &mut self.interfaces {
-            interface.trigger_state_actions(state);
+        for name in &self.stack {
+            let interface = self.interfaces.get_mut(name).unwrap();
+            if !interface.enabled {
+                continue;
+            }
+
+            interface.trigger_state_actions(state, self.time);
+            if interface.consumption == Consumption::Everything {
+                break;
+            }
         }
     }
 
@@ -147,13 +552,18 @@ impl Interaction {
 
 pub(crate) struct Interface {
     actions: HashSet<Action>,
+    priority: i32,
+    enabled: bool,
+    consumption: Consumption,
     rules: Rules,
+    sources: Vec<RuleSource>,
     triggered_actions: Vec<Action>,
 }
 
 impl Interface {
     fn load_rules(&mut self, rules: &[Yaml]) -> Result<()> {
         self.rules.clear();
+        self.sources.clear();
         for rule in rules {
             let action = match rule["action"].as_str() {
                 Some(action) => action,
@@ -165,42 +575,264 @@ impl Interface {
                 None => bail!(ErrorKind::InterfaceFormat),
             };
 
+            // In milliseconds, for double taps and sequences
+            let timeout = match rule["timeout"] {
+                Yaml::BadValue => None,
+                Yaml::Integer(ms) if ms > 0 => Some(ms),
+                _ => bail!(ErrorKind::ConditionFormat),
+            };
 
-            if let Some(action) = self.actions.get(action) {
-                use self::WhenParse::*;
+            if self.actions.contains(action) {
+                self.bind(action, when, timeout)?;
+            } else {
+                bail!(ErrorKind::UnknownInterface);
+            }
+        }
 
-                match WhenParse::from_str(when) {
-                    Some(Input(input)) => {
-                        self.rules.by_input.insert(input, action);
-                    }
-                    Some(Condition(condition)) => {
-                        self.rules.others.push(ConditionalAction {
-                            action: action,
-                            condition: condition,
-                        });
+        Ok(())
+    }
+
+    fn bind(&mut self, action: &str, when: &str, timeout: Option<i64>) -> Result<()> {
+        let action = match self.actions.get(action) {
+            Some(action) => *action,
+            None => bail!(ErrorKind::UnknownAction),
+        };
+
+        Interface::compile_rule(&mut self.rules, action, when, timeout)?;
+        self.sources.push(RuleSource {
+            action: action,
+            when: when.to_string(),
+            timeout: timeout,
+        });
+
+        Ok(())
+    }
+
+    fn unbind(&mut self, action: &str) -> Result<()> {
+        if !self.actions.contains(action) {
+            bail!(ErrorKind::UnknownAction);
+        }
+
+        self.sources.retain(|source| source.action != action);
+        self.rules.clear_triggers();
+
+        for source in &self.sources {
+            Interface::compile_rule(&mut self.rules, source.action, &source.when, source.timeout)
+                .expect("a rule that was valid could not be rebuilt");
+        }
+
+        Ok(())
+    }
+
+    fn compile_rule(rules: &mut Rules,
+                    action: Action,
+                    when: &str,
+                    timeout: Option<i64>)
+                    -> Result<()> {
+        use self::WhenParse::*;
+
+        let parsed = WhenParse::from_str(when);
+        if timeout.is_some() {
+            match parsed {
+                Some(Gesture(_)) => {}
+                _ => bail!(ErrorKind::ConditionFormat),
+            }
+        }
+
+        match parsed {
+            Some(Input(input)) => {
+                rules.by_input.insert(input, action);
+            }
+            Some(Chord(chord)) => {
+                rules.chords.push((chord, action));
+            }
+            Some(Gesture(mut gesture)) => {
+                if let Some(timeout) = timeout {
+                    if !gesture.set_timeout(timeout as f64 / 1000.) {
+                        bail!(ErrorKind::ConditionFormat);
                     }
-                    None => bail!(ErrorKind::ConditionFormat),
                 }
-            } else {
-                bail!(ErrorKind::UnknownInterface);
+
+                rules.gestures.push((gesture, action));
             }
+            Some(Condition(condition)) => {
+                rules.others.push(ConditionalAction {
+                    action: action,
+                    condition: condition,
+                });
+            }
+            None => bail!(ErrorKind::ConditionFormat),
         }
 
         Ok(())
     }
 
-    fn trigger_input_actions(&mut self, input: &Input, _state: &InputState) {
-        if let Some(action) = self.rules.by_input.get(input) {
-            self.triggered_actions.push(action);
+    fn conflicts(&self, when: &str) -> Vec<Action> {
+        let when = rebind::canonical_rule(when);
+        let mut actions: Vec<Action> = self.sources
+            .iter()
+            .filter(|source| rebind::canonical_rule(&source.when) == when)
+            .map(|source| source.action)
+            .collect();
+        actions.sort();
+        actions.dedup();
+
+        actions
+    }
+
+    fn save(&self) -> Yaml {
+        let rules = self.sources
+            .iter()
+            .map(|source| {
+                let mut rule = Hash::new();
+                rule.insert(Yaml::String("action".to_string()),
+                            Yaml::String(source.action.to_string()));
+                rule.insert(Yaml::String("when".to_string()),
+                            Yaml::String(source.when.clone()));
+                if let Some(timeout) = source.timeout {
+                    rule.insert(Yaml::String("timeout".to_string()), Yaml::Integer(timeout));
+                }
+
+                Yaml::Hash(rule)
+            })
+            .collect();
+
+        let axes = self.rules
+            .axes
+            .iter()
+            .map(|binding| {
+                let mut axis = Hash::new();
+                axis.insert(Yaml::String("action".to_string()),
+                            Yaml::String(binding.action.to_string()));
+                axis.insert(Yaml::String("axis".to_string()),
+                            Yaml::String(format!("Gamepad.{}", axis_to_str(binding.axis))));
+                axis.insert(Yaml::String("dead_zone".to_string()),
+                            Yaml::Real(binding.dead_zone.to_string()));
+                axis.insert(Yaml::String("sensitivity".to_string()),
+                            Yaml::Real(binding.sensitivity.to_string()));
+                axis.insert(Yaml::String("invert".to_string()),
+                            Yaml::Boolean(binding.inverted));
+
+                Yaml::Hash(axis)
+            })
+            .collect();
+
+        let mut interface = Hash::new();
+        interface.insert(Yaml::String("rules".to_string()), Yaml::Array(rules));
+        interface.insert(Yaml::String("axes".to_string()), Yaml::Array(axes));
+
+        Yaml::Hash(interface)
+    }
+
+    fn load_axes(&mut self, axes: &[Yaml]) -> Result<()> {
+        for binding in axes {
+            let action = match binding["action"].as_str() {
+                Some(action) => action,
+                None => bail!(ErrorKind::InterfaceFormat),
+            };
+
+            let action = match self.actions.get(action) {
+                Some(action) => *action,
+                None => bail!(ErrorKind::UnknownInterface),
+            };
+
+            let axis = match binding["axis"].as_str().and_then(axis_from_rule) {
+                Some(axis) => axis,
+                None => bail!(ErrorKind::AxisFormat),
+            };
+
+            let dead_zone = yaml_number_or(&binding["dead_zone"], 0.)?;
+            let sensitivity = yaml_number_or(&binding["sensitivity"], 1.)?;
+            let inverted = match binding["invert"] {
+                Yaml::BadValue => false,
+                Yaml::Boolean(inverted) => inverted,
+                _ => bail!(ErrorKind::AxisFormat),
+            };
+
+            // Written so that NaN values are rejected too
+            if !(dead_zone >= 0. && dead_zone < 1.) || !(sensitivity >= 0.) {
+                bail!(ErrorKind::AxisFormat);
+            }
+
+            self.rules.axes.push(AxisBinding {
+                action: action,
+                axis: axis,
+                dead_zone: dead_zone,
+                sensitivity: sensitivity,
+                inverted: inverted,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns the value of the analog `action`, between -1 and 1.
+    ///
+    /// The values of all the axes bound to the action are summed up.
+    /// Returns `None` if no axis is bound to the action.
+    pub fn analog_value(&self, action: &str, state: &InputState) -> Option<f32> {
+        let mut bindings = self.rules.axes.iter().filter(|b| b.action == action).peekable();
+        if bindings.peek().is_none() {
+            return None;
         }
+
+        let value = bindings.fold(0., |sum, binding| sum + binding.value(state));
+        Some(value.max(-1.).min(1.))
     }
 
-    fn trigger_state_actions(&mut self, state: &InputState) {
+    /// Returns true if the input triggered an action.
+    fn trigger_input_actions(&mut self, input: &Input, state: &InputState, now: f64) -> bool {
+        let count = self.triggered_actions.len();
+
+        // The matching chord with the most modifiers wins over the other ones
+        // and over the plain input, so that `Ctrl+S` does not also trigger `S`.
+        let mut chord_action: Option<(usize, Action)> = None;
+        for &(ref chord, action) in &self.rules.chords {
+            if chord.matches(input, state) {
+                let count = chord.modifier_count();
+                if chord_action.map(|(best, _)| count > best).unwrap_or(true) {
+                    chord_action = Some((count, action));
+                }
+            }
+        }
+
+        match chord_action {
+            Some((_, action)) => self.triggered_actions.push(action),
+            None => {
+                if let Some(action) = self.rules.by_input.get(input) {
+                    self.triggered_actions.push(action);
+                }
+            }
+        }
+
+        for &mut (ref mut gesture, action) in &mut self.rules.gestures {
+            if gesture.on_input(input, state, now) {
+                self.triggered_actions.push(action);
+            }
+        }
+
+        self.triggered_actions.len() > count
+    }
+
+    /// Feeds a release to the gestures without triggering any action.
+    fn release(&mut self, input: &Input, state: &InputState, now: f64) {
+        for &mut (ref mut gesture, _) in &mut self.rules.gestures {
+            gesture.on_input(input, state, now);
+        }
+    }
+
+    fn trigger_state_actions(&mut self, state: &InputState, now: f64) {
         for ca in &self.rules.others {
             if let Some(action) = ca.may_trigger(state) {
                 self.triggered_actions.push(action);
             }
         }
+
+        for &mut (ref mut gesture, action) in &mut self.rules.gestures {
+            if gesture.on_time(now) {
+                self.triggered_actions.push(action);
+            }
+        }
     }
 
     pub fn triggered_actions(&self) -> &[Action] {
@@ -214,49 +846,157 @@ impl Interface {
 
 enum WhenParse {
     Input(Input),
+    Chord(Chord),
+    Gesture(Gesture),
     Condition(Condition),
 }
 
 impl WhenParse {
+    /// Parses a rule trigger:
+    ///
+    /// - `Key.Pressed.A`, `MouseButton.Released.Left`, `GamepadButton.Held.South`
+    /// - `Key.JustPressed.A`, `Key.JustReleased.A`, once per frame
+    /// - `MouseWheel.Up`
+    /// - `Key.DoubleTapped.Space`, `Key.HeldFor.Space.500ms`
+    /// - `Ctrl+Shift+S`, `Key.Held.Q+MouseButton.Pressed.Left`
+    /// - `Key.Pressed.Down > Key.Pressed.Right > Key.Pressed.A`
     pub fn from_str(s: &str) -> Option<Self> {
+        if s.contains('>') {
+            let steps = s.split('>').map(parse_chord).collect::<Option<Vec<_>>>();
+
+            match steps {
+                Some(ref steps) if steps.len() < 2 => None,
+                Some(steps) => Some(WhenParse::Gesture(Gesture::sequence(steps))),
+                None => None,
+            }
+        } else if s.contains('+') {
+            parse_chord(s).map(WhenParse::Chord)
+        } else {
+            WhenParse::from_token(s)
+        }
+    }
+
+    fn from_token(s: &str) -> Option<Self> {
         use self::WhenParse::*;
-        use self::Input::*;
-        use self::Condition::*;
-        use glutin::ElementState::*;
+        use event::ElementState::*;
 
-        let mut split = s.split('.');
-        match split.next() {
-            Some("Key") => {
-                split.next().and_then(|state| {
-                    split.next().and_then(|k| key_from_str(k)).and_then(|key| match state {
-                        "Pressed" => Some(Input(Key(Pressed, key))),
-                        "Released" => Some(Input(Key(Released, key))),
-                        "Held" => Some(Condition(KeyHeld(key))),
-                        _ => None,
-                    })
-                })
+        let mut split = s.trim().split('.');
+        let kind = split.next();
+        let state = split.next();
+
+        if kind == Some("MouseWheel") {
+            return match (state.and_then(wheel_direction_from_str), split.next()) {
+                (Some(direction), None) => Some(Input(self::Input::MouseWheel(direction))),
+                _ => None,
+            };
+        }
+
+        let element = match (kind, split.next()) {
+            (Some("Key"), Some(k)) => key_from_str(k).map(Element::Key),
+            (Some("MouseButton"), Some(b)) => mouse_button_from_str(b).map(Element::MouseButton),
+            (Some("GamepadButton"), Some(b)) => {
+                gamepad_button_from_str(b).map(Element::GamepadButton)
             }
-            Some("MouseButton") => {
-                split.next().and_then(|state| {
-                    split.next()
-                        .and_then(|b| mouse_button_from_str(b))
-                        .and_then(|button| match state {
-                            "Pressed" => Some(Input(MouseButton(Pressed, button))),
-                            "Released" => Some(Input(MouseButton(Released, button))),
-                            "Held" => Some(Condition(MouseButtonHeld(button))),
-                            _ => None,
-                        })
-                })
+            _ => None,
+        };
+
+        let element = match element {
+            Some(element) => element,
+            None => return None,
+        };
+
+        let parsed = match state {
+            Some("Pressed") => Some(Input(element.input(Pressed))),
+            Some("Released") => Some(Input(element.input(Released))),
+            Some("Held") => Some(Condition(element.held())),
+            Some("JustPressed") => Some(Condition(self::Condition::JustPressed(element))),
+            Some("JustReleased") => Some(Condition(self::Condition::JustReleased(element))),
+            Some("DoubleTapped") => Some(Gesture(::gesture::Gesture::double_tap(element))),
+            Some("HeldFor") => {
+                split.next()
+                    .and_then(parse_duration)
+                    .map(|duration| Gesture(::gesture::Gesture::long_press(element, duration)))
             }
             _ => None,
+        };
+
+        match split.next() {
+            Some(_) => None,
+            None => parsed,
+        }
+    }
+}
+
+/// Parses modifiers followed by an input, such as `Ctrl+Shift+S`.
+fn parse_chord(s: &str) -> Option<Chord> {
+    let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+    let input = match parts.pop() {
+        Some(last) if !last.contains('.') => {
+            key_from_str(last).map(|key| Input::Key(ElementState::Pressed, key))
+        }
+        Some(last) => {
+            match WhenParse::from_token(last) {
+                Some(WhenParse::Input(input)) => Some(input),
+                _ => None,
+            }
+        }
+        None => None,
+    };
+
+    let modifiers = parts.into_iter().map(parse_modifier).collect::<Option<Vec<_>>>();
+
+    match (modifiers, input) {
+        (Some(modifiers), Some(input)) => Some(Chord::new(modifiers, input)),
+        _ => None,
+    }
+}
+
+fn parse_modifier(s: &str) -> Option<Condition> {
+    if let Some(&(_, keys)) = MODIFIERS.iter().find(|&&(name, _)| name == s) {
+        return Some(Condition::AnyKeyHeld(keys.to_vec()));
+    }
+
+    match WhenParse::from_token(s) {
+        Some(WhenParse::Condition(condition)) => Some(condition),
+        _ => None,
+    }
+}
+
+/// Parses a duration in milliseconds such as `500ms`, returns it in seconds.
+fn parse_duration(s: &str) -> Option<f64> {
+    if s.ends_with("ms") {
+        s[..s.len() - 2].parse::<u32>().ok().map(|ms| ms as f64 / 1000.)
+    } else {
+        None
+    }
+}
+
+fn axis_from_rule(s: &str) -> Option<Axis> {
+    let mut split = s.split('.');
+    match (split.next(), split.next(), split.next()) {
+        (Some("Gamepad"), Some(axis), None) => axis_from_str(axis),
+        _ => None,
+    }
+}
+
+fn yaml_number_or(yaml: &Yaml, default: f32) -> Result<f32> {
+    match yaml {
+        &Yaml::BadValue => Ok(default),
+        &Yaml::Integer(n) => Ok(n as f32),
+        &Yaml::Real(_) => {
+            match yaml.as_f64() {
+                Some(n) => Ok(n as f32),
+                None => bail!(ErrorKind::AxisFormat),
+            }
         }
+        _ => bail!(ErrorKind::AxisFormat),
     }
 }
 
 macro_rules! enum_to_str {
     ( $name:ident, $enum_path:path, $($variant:pat),* ) => {
         #[allow(dead_code)]
-        fn $name(v: $enum_path) -> &'static str {
+        pub(crate) fn $name(v: $enum_path) -> &'static str {
             use $enum_path::*;
             match v {
                 $( $variant => stringify!($variant), )*
@@ -268,7 +1008,7 @@ macro_rules! enum_to_str {
 macro_rules! enum_from_str {
     ( $name:ident, $enum_path:path, $($variant:ident),* ) => {
         #[allow(dead_code)]
-        fn $name(s: &str) -> Option<$enum_path> {
+        pub(crate) fn $name(s: &str) -> Option<$enum_path> {
             use $enum_path::*;
             match s {
                 $( stringify!($variant) => Some($variant), )*
@@ -291,7 +1031,13 @@ macro_rules! enum_str_conv {
 }
 
 enum_str_conv! {
-    key_to_str, key_from_str, ::glutin::VirtualKeyCode,
+    element_state_to_str, element_state_from_str, ::event::ElementState,
+    Pressed,
+    Released
+}
+
+enum_str_conv! {
+    key_to_str, key_from_str, ::event::Key,
     Key1,
     Key2,
     Key3,
@@ -445,9 +1191,238 @@ enum_str_conv! {
 }
 
 enum_str_conv! {
-    mouse_button_to_str, mouse_button_from_str, ::glutin::MouseButton,
+    mouse_button_to_str, mouse_button_from_str, ::event::MouseButton,
     Left,
     Right,
     Middle
     _
+}
+
+enum_str_conv! {
+    gamepad_button_to_str, gamepad_button_from_str, ::event::GamepadButton,
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftTrigger,
+    RightTrigger,
+    Select,
+    Start,
+    Mode,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight
+}
+
+enum_str_conv! {
+    axis_to_str, axis_from_str, ::event::Axis,
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger
+}
+
+enum_str_conv! {
+    wheel_direction_to_str, wheel_direction_from_str, ::interaction::WheelDirection,
+    Up,
+    Down,
+    Left,
+    Right
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use event::ElementState::*;
+
+    fn interaction() -> Interaction {
+        let game = InterfaceBuilder::new().action("save").action("jump").action("fire");
+        InteractionBuilder::new().interface("game", game).build()
+    }
+
+    fn press(interaction: &mut Interaction, state: &mut InputState, key: Key) {
+        state.update_key(key, Pressed);
+        interaction.trigger_input_actions(&Input::Key(Pressed, key), state);
+    }
+
+    fn release(interaction: &mut Interaction, state: &mut InputState, key: Key) {
+        state.update_key(key, Released);
+        interaction.trigger_input_actions(&Input::Key(Released, key), state);
+    }
+
+    fn triggered(interaction: &Interaction, interface_name: &str) -> Vec<Action> {
+        interaction.interface(interface_name).unwrap().triggered_actions().to_vec()
+    }
+
+    #[test]
+    fn test_bind_unbind() {
+        let mut interaction = interaction();
+        let mut state = InputState::new();
+
+        interaction.bind("game", "jump", "Key.Pressed.Space").unwrap();
+        interaction.bind("game", "jump", "GamepadButton.Pressed.South").unwrap();
+        interaction.bind("game", "save", "Ctrl+S").unwrap();
+        assert!(interaction.bind("game", "crouch", "Key.Pressed.C").is_err());
+        assert!(interaction.bind("menu", "jump", "Key.Pressed.Space").is_err());
+        assert!(interaction.bind("game", "jump", "Key.Smashed.Space").is_err());
+        assert_eq!(interaction.bindings("game").unwrap().len(), 3);
+
+        press(&mut interaction, &mut state, Key::Space);
+        assert_eq!(triggered(&interaction, "game"), vec!["jump"]);
+
+        interaction.clear_actions();
+        interaction.unbind("game", "jump").unwrap();
+        assert_eq!(interaction.bindings("game").unwrap(), vec![("save", "Ctrl+S")]);
+        release(&mut interaction, &mut state, Key::Space);
+        press(&mut interaction, &mut state, Key::Space);
+        assert!(triggered(&interaction, "game").is_empty());
+
+        // The other rules are rebuilt
+        press(&mut interaction, &mut state, Key::LControl);
+        press(&mut interaction, &mut state, Key::S);
+        assert_eq!(triggered(&interaction, "game"), vec!["save"]);
+        assert!(interaction.unbind("game", "crouch").is_err());
+    }
+
+    #[test]
+    fn test_rebind() {
+        let mut interaction = interaction();
+        let mut state = InputState::new();
+
+        interaction.bind("game", "fire", "Key.Pressed.F").unwrap();
+        interaction.bind("game", "fire", "MouseButton.Pressed.Left").unwrap();
+        interaction.rebind("game", "fire", "Key.Pressed.G").unwrap();
+        assert_eq!(interaction.bindings("game").unwrap(), vec![("fire", "Key.Pressed.G")]);
+
+        // An invalid rule keeps the old ones
+        assert!(interaction.rebind("game", "fire", "Key.Pressed").is_err());
+        assert_eq!(interaction.bindings("game").unwrap(), vec![("fire", "Key.Pressed.G")]);
+
+        press(&mut interaction, &mut state, Key::F);
+        press(&mut interaction, &mut state, Key::G);
+        assert_eq!(triggered(&interaction, "game"), vec!["fire"]);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let mut interaction = interaction();
+
+        interaction.bind("game", "save", "Ctrl+Shift+S").unwrap();
+        interaction.bind("game", "fire", "Key.Pressed.F").unwrap();
+        interaction.bind("game", "jump", "Shift+Ctrl+Key.Pressed.S").unwrap();
+        interaction.bind("game", "save", "Ctrl + Shift + Key.Pressed.S").unwrap();
+
+        // Each action is reported once, whatever the modifier order
+        assert_eq!(interaction.conflicts("game", "Shift+Ctrl+S").unwrap(), vec!["jump", "save"]);
+        assert_eq!(interaction.conflicts("game", "Key.Pressed.F").unwrap(), vec!["fire"]);
+        assert!(interaction.conflicts("game", "Ctrl+S").unwrap().is_empty());
+        assert!(interaction.conflicts("menu", "Key.Pressed.F").is_err());
+    }
+
+    fn stack(hud: Consumption) -> Interaction {
+        let hud = InterfaceBuilder::new().action("select").priority(2).consumption(hud);
+        let game = InterfaceBuilder::new().action("jump").action("fire").action("dodge");
+        let mut interaction = InteractionBuilder::new()
+            .interface("hud", hud)
+            .interface("game", game)
+            .build();
+
+        interaction.bind("hud", "select", "Key.Pressed.Space").unwrap();
+        interaction.bind("game", "jump", "Key.Pressed.Space").unwrap();
+        interaction.bind("game", "fire", "Key.Pressed.F").unwrap();
+        interaction.bind("game", "dodge", "Key.DoubleTapped.D").unwrap();
+        interaction
+    }
+
+    #[test]
+    fn test_priority_order() {
+        let mut interaction = stack(Consumption::Matched);
+        let mut state = InputState::new();
+        assert_eq!(interaction.active_interfaces(), vec!["hud", "game"]);
+
+        press(&mut interaction, &mut state, Key::Space);
+        assert_eq!(triggered(&interaction, "hud"), vec!["select"]);
+        assert!(triggered(&interaction, "game").is_empty());
+
+        interaction.clear_actions();
+        release(&mut interaction, &mut state, Key::Space);
+        assert!(interaction.set_priority("game", 3));
+        assert!(!interaction.set_priority("menu", 3));
+        assert_eq!(interaction.active_interfaces(), vec!["game", "hud"]);
+
+        // The game consumes nothing, so the hud still sees the input
+        press(&mut interaction, &mut state, Key::Space);
+        assert_eq!(triggered(&interaction, "game"), vec!["jump"]);
+        assert_eq!(triggered(&interaction, "hud"), vec!["select"]);
+    }
+
+    #[test]
+    fn test_disabled_interface() {
+        let mut interaction = stack(Consumption::Everything);
+        let mut state = InputState::new();
+
+        press(&mut interaction, &mut state, Key::Space);
+        assert!(interaction.disable("hud"));
+        assert!(!interaction.is_enabled("hud"));
+        assert!(triggered(&interaction, "hud").is_empty());
+        assert!(!interaction.is_blocked("game"));
+        assert_eq!(interaction.active_interfaces(), vec!["game"]);
+
+        release(&mut interaction, &mut state, Key::Space);
+        press(&mut interaction, &mut state, Key::Space);
+        assert!(triggered(&interaction, "hud").is_empty());
+        assert_eq!(triggered(&interaction, "game"), vec!["jump"]);
+
+        assert!(interaction.enable("hud"));
+        assert!(!interaction.disable("menu"));
+        assert!(!interaction.is_enabled("menu"));
+    }
+
+    #[test]
+    fn test_consumption() {
+        let mut state = InputState::new();
+
+        let mut interaction = stack(Consumption::Nothing);
+        press(&mut interaction, &mut state, Key::Space);
+        assert_eq!(triggered(&interaction, "hud"), vec!["select"]);
+        assert_eq!(triggered(&interaction, "game"), vec!["jump"]);
+
+        // Only the matched inputs are hidden
+        let mut interaction = stack(Consumption::Matched);
+        press(&mut interaction, &mut state, Key::F);
+        assert_eq!(triggered(&interaction, "game"), vec!["fire"]);
+        assert!(!interaction.is_blocked("game"));
+
+        let mut interaction = stack(Consumption::Everything);
+        press(&mut interaction, &mut state, Key::F);
+        assert!(triggered(&interaction, "game").is_empty());
+        assert!(interaction.is_blocked("game"));
+        assert!(!interaction.is_blocked("hud"));
+    }
+
+    #[test]
+    fn test_blocked_gesture_release() {
+        let mut interaction = stack(Consumption::Everything);
+        let mut state = InputState::new();
+        interaction.disable("hud");
+
+        press(&mut interaction, &mut state, Key::D);
+
+        // The release happens while the hud blocks the game
+        interaction.enable("hud");
+        interaction.advance_time(0.1);
+        release(&mut interaction, &mut state, Key::D);
+        interaction.disable("hud");
+
+        interaction.advance_time(0.1);
+        press(&mut interaction, &mut state, Key::D);
+        assert_eq!(triggered(&interaction, "game"), vec!["dodge"]);
+    }
 }
\ No newline at end of file
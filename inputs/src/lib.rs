@@ -1,5 +1,6 @@
 #![feature(pub_restricted)]
 
+#[cfg(feature = "glutin")]
 extern crate glutin;
 extern crate cgmath;
 extern crate yaml_rust;
@@ -7,24 +8,33 @@ extern crate yaml_rust;
 extern crate error_chain;
 
 pub mod error;
+pub mod event;
 pub mod state;
 pub mod interaction;
+pub mod record;
+mod gesture;
+mod rebind;
+#[cfg(feature = "glutin")]
+pub mod glutin_backend;
 #[macro_use]
 pub mod macros;
 
 pub use error::Error;
+pub use event::{Event, Key, MouseButton, ElementState, ScanCode, GamepadButton, Axis};
 pub use state::InputState;
-pub use interaction::{Interaction, InteractionBuilder, InterfaceBuilder, Action};
+pub use interaction::{Interaction, InteractionBuilder, InterfaceBuilder, Action, Consumption};
+pub use record::{InputRecorder, InputPlayer, RecordedFrame};
 use interaction::Interface;
-use error::Result;
+use error::{ErrorKind, Result};
 
-use glutin::Event;
-use cgmath::Point2;
-use yaml_rust::YamlLoader;
+use std::io::Write;
+use cgmath::{Point2, Vector2};
+use yaml_rust::{YamlLoader, YamlEmitter};
 
 pub struct Inputs {
     state: InputState,
     interaction: Interaction,
+    recorder: Option<InputRecorder>,
 }
 
 impl Inputs {
@@ -32,6 +42,7 @@ impl Inputs {
         Inputs {
             state: InputState::new(),
             interaction: interaction.build(),
+            recorder: None,
         }
     }
 
@@ -43,12 +54,29 @@ impl Inputs {
         &mut self.interaction
     }
 
+    /// Returns the actions triggered in an interface.
+    ///
+    /// Disabled interfaces and the inputs consumed by higher interfaces do not trigger actions.
     pub fn triggered_actions(&self, interface_name: &str) -> Option<&[Action]> {
         self.interaction
             .interface(interface_name)
             .map(Interface::triggered_actions)
     }
 
+    /// Returns the value of an analog action, between -1 and 1.
+    ///
+    /// Returns `None` if the interface does not exist or if no axis is bound to the action.
+    /// The value is 0 while the interface is disabled or blocked by a higher interface.
+    pub fn analog_value(&self, interface_name: &str, action: &str) -> Option<f32> {
+        let active = self.interaction.is_enabled(interface_name) &&
+                     !self.interaction.is_blocked(interface_name);
+
+        self.interaction
+            .interface(interface_name)
+            .and_then(|interface| interface.analog_value(action, &self.state))
+            .map(|value| if active { value } else { 0. })
+    }
+
     pub fn load_interaction_profile(&mut self, path: &str) -> Result<()> {
         use std::fs::File;
         use std::io::prelude::*;
@@ -62,8 +90,69 @@ impl Inputs {
         self.interaction.load_profile(&docs[0]).map_err(Error::from)
     }
 
+    /// Saves the current rules, including the rebound ones, in a profile.
+    pub fn save_interaction_profile(&self, path: &str) -> Result<()> {
+        use std::fs::File;
+
+        let profile = self.interaction.save_profile();
+        let mut s = String::new();
+        {
+            let mut emitter = YamlEmitter::new(&mut s);
+            if emitter.dump(&profile).is_err() {
+                bail!(ErrorKind::ProfileEmit);
+            }
+        }
+        s.push('\n');
+
+        let mut f = File::create(path)?;
+        f.write_all(s.as_bytes())?;
+        Ok(())
+    }
+
+    /// Starts recording the events received by `handle_event` into `writer`.
+    ///
+    /// A recording already in progress is discarded.
+    pub fn start_recording<W: Write + Send + 'static>(&mut self, writer: W) {
+        self.recorder = Some(InputRecorder::new(writer));
+    }
+
+    /// Stops the current recording, returning the first error that happened while recording.
+    pub fn stop_recording(&mut self) -> Result<()> {
+        match self.recorder.take() {
+            Some(recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Notifies the start of a new frame lasting `delta` seconds.
+    ///
+    /// It must be called before handling the events of the frame for them to be recorded properly.
+    /// It also resets the per frame state, such as the mouse delta or the typed text,
+    /// and advances the time used by the timed rules, such as double taps and sequences.
+    pub fn new_frame(&mut self, delta: f64) {
+        if let Some(ref mut recorder) = self.recorder {
+            recorder.frame(delta);
+        }
+
+        self.state.new_frame();
+        self.interaction.advance_time(delta);
+    }
+
+    /// Updates the state and triggers the actions of an input event.
+    ///
+    /// `new_frame` must be called at the start of every frame, otherwise the pressed and released
+    /// elements, the mouse delta, the scroll and the typed text keep accumulating, and the timed
+    /// rules never fire.
     pub fn handle_event(&mut self, event: &Event) {
-        let &mut Inputs { ref mut state, ref mut interaction } = self;
+        let &mut Inputs { ref mut state, ref mut interaction, ref mut recorder } = self;
+
+        if let &mut Some(ref mut recorder) = recorder {
+            recorder.record(event);
+        }
 
         match event {
             &Event::KeyboardInput(e_state, _, Some(key)) => {
@@ -81,15 +170,48 @@ impl Inputs {
                 let input = interaction::Input::MouseButton(e_state, button);
                 interaction.trigger_input_actions(&input, state);
             }
+            &Event::MouseWheel(x, y) => {
+                use interaction::WheelDirection::*;
+
+                state.update_scroll(Vector2::new(x, y));
+
+                let directions = [(y > 0., Up), (y < 0., Down), (x > 0., Right), (x < 0., Left)];
+                for &(scrolled, direction) in &directions {
+                    if scrolled {
+                        let input = interaction::Input::MouseWheel(direction);
+                        interaction.trigger_input_actions(&input, state);
+                    }
+                }
+            }
+            &Event::ReceivedCharacter(c) => {
+                state.push_character(c);
+            }
             &Event::Focused(focused) => {
                 state.update_window_focus(focused);
             }
+            &Event::GamepadInput(e_state, button) => {
+                state.update_gamepad_button(button, e_state);
+
+                let input = interaction::Input::GamepadButton(e_state, button);
+                interaction.trigger_input_actions(&input, state);
+            }
+            &Event::GamepadAxisMoved(axis, value) => {
+                state.update_axis(axis, value);
+            }
             _ => {}
         }
     }
 
+    /// Handles a glutin event, ignoring the ones that are not input events.
+    #[cfg(feature = "glutin")]
+    pub fn handle_glutin_event(&mut self, event: &glutin::Event) {
+        if let Some(event) = glutin_backend::convert_event(event) {
+            self.handle_event(&event);
+        }
+    }
+
     pub fn trigger_state_actions(&mut self) {
-        let &mut Inputs { ref mut state, ref mut interaction } = self;
+        let &mut Inputs { ref mut state, ref mut interaction, .. } = self;
 
         interaction.trigger_state_actions(state);
     }
@@ -98,3 +220,153 @@ impl Inputs {
         self.interaction.clear_actions();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use event::ElementState::*;
+
+    fn feed(inputs: &mut Inputs, events: &[Event]) {
+        inputs.new_frame(0.016);
+        for event in events {
+            inputs.handle_event(event);
+        }
+    }
+
+    #[test]
+    fn test_buttons() {
+        let mut inputs = Inputs::new(InteractionBuilder::new());
+
+        feed(&mut inputs,
+             &[Event::KeyboardInput(Pressed, 30, Some(Key::A)),
+               Event::MouseInput(Pressed, MouseButton::Right),
+               Event::GamepadInput(Pressed, GamepadButton::Start)]);
+        assert!(inputs.state().is_key_held(&Key::A));
+        assert!(inputs.state().is_mouse_button_held(&MouseButton::Right));
+        assert!(inputs.state().is_gamepad_button_held(&GamepadButton::Start));
+        assert!(!inputs.state().is_key_held(&Key::B));
+
+        feed(&mut inputs,
+             &[Event::KeyboardInput(Released, 30, Some(Key::A)),
+               Event::MouseInput(Released, MouseButton::Right)]);
+        assert!(!inputs.state().is_key_held(&Key::A));
+        assert!(!inputs.state().is_mouse_button_held(&MouseButton::Right));
+        assert!(inputs.state().is_gamepad_button_held(&GamepadButton::Start));
+    }
+
+    #[test]
+    fn test_unknown_key_ignored() {
+        let mut inputs = Inputs::new(InteractionBuilder::new());
+
+        feed(&mut inputs, &[Event::KeyboardInput(Pressed, 200, None)]);
+        assert!(!inputs.state().is_key_held(&Key::Unlabeled));
+    }
+
+    #[test]
+    fn test_axes() {
+        let mut inputs = Inputs::new(InteractionBuilder::new());
+        assert_eq!(inputs.state().axis(Axis::LeftStickX), 0.);
+
+        feed(&mut inputs,
+             &[Event::GamepadAxisMoved(Axis::LeftStickX, 0.25),
+               Event::GamepadAxisMoved(Axis::RightTrigger, 1.),
+               Event::GamepadAxisMoved(Axis::LeftStickX, -0.75)]);
+        assert_eq!(inputs.state().axis(Axis::LeftStickX), -0.75);
+        assert_eq!(inputs.state().axis(Axis::RightTrigger), 1.);
+        assert_eq!(inputs.state().axis(Axis::LeftStickY), 0.);
+
+        // Axes keep their value until they move again
+        feed(&mut inputs, &[]);
+        assert_eq!(inputs.state().axis(Axis::LeftStickX), -0.75);
+    }
+
+    fn analog_inputs(profile: &str) -> Result<Inputs> {
+        let interaction = InteractionBuilder::new()
+            .interface("game", InterfaceBuilder::new().action("turn").action("look"))
+            .interface("menu",
+                       InterfaceBuilder::new()
+                           .priority(1)
+                           .enabled(false)
+                           .consumption(Consumption::Everything));
+        let mut inputs = Inputs::new(interaction);
+
+        let docs = YamlLoader::load_from_str(profile).unwrap();
+        inputs.interaction_mut().load_profile(&docs[0])?;
+        Ok(inputs)
+    }
+
+    fn axes_profile(binding: &str) -> String {
+        format!("game:\n  rules: []\n  axes:\n    - {}\nmenu:\n  rules: []\n", binding)
+    }
+
+    #[test]
+    fn test_analog_value() {
+        let profile = "game:
+  rules: []
+  axes:
+    - { action: turn, axis: Gamepad.LeftStickX, dead_zone: 0.5, sensitivity: 1.5 }
+    - { action: look, axis: Gamepad.RightStickX, invert: true }
+menu:
+  rules: []
+";
+        let mut inputs = analog_inputs(profile).unwrap();
+        assert_eq!(inputs.analog_value("game", "turn"), Some(0.));
+        assert_eq!(inputs.analog_value("game", "jump"), None);
+        assert_eq!(inputs.analog_value("hud", "turn"), None);
+
+        // Inside the dead zone
+        feed(&mut inputs, &[Event::GamepadAxisMoved(Axis::LeftStickX, 0.5)]);
+        assert_eq!(inputs.analog_value("game", "turn"), Some(0.));
+
+        // Rescaled from the edge of the dead zone, then multiplied by the sensitivity
+        feed(&mut inputs, &[Event::GamepadAxisMoved(Axis::LeftStickX, -0.75)]);
+        assert_eq!(inputs.analog_value("game", "turn"), Some(-0.75));
+
+        feed(&mut inputs, &[Event::GamepadAxisMoved(Axis::LeftStickX, 1.)]);
+        assert_eq!(inputs.analog_value("game", "turn"), Some(1.));
+
+        feed(&mut inputs, &[Event::GamepadAxisMoved(Axis::RightStickX, 0.25)]);
+        assert_eq!(inputs.analog_value("game", "look"), Some(-0.25));
+
+        inputs.interaction_mut().enable("menu");
+        assert_eq!(inputs.analog_value("game", "turn"), Some(0.));
+        inputs.interaction_mut().disable("menu");
+        inputs.interaction_mut().disable("game");
+        assert_eq!(inputs.analog_value("game", "turn"), Some(0.));
+    }
+
+    #[test]
+    fn test_invalid_axes() {
+        let valid = "{ action: turn, axis: Gamepad.LeftStickX, dead_zone: 0, sensitivity: 0 }";
+        assert!(analog_inputs(&axes_profile(valid)).is_ok());
+
+        let invalid = ["{ action: turn, axis: Gamepad.LeftStickX, sensitivity: -1 }",
+                       "{ action: turn, axis: Gamepad.LeftStickX, sensitivity: .nan }",
+                       "{ action: turn, axis: Gamepad.LeftStickX, dead_zone: 1 }",
+                       "{ action: turn, axis: Gamepad.LeftStickX, dead_zone: -0.1 }",
+                       "{ action: turn, axis: Gamepad.LeftStickX, dead_zone: .nan }",
+                       "{ action: turn, axis: Gamepad.Wheel }"];
+        for binding in &invalid {
+            assert!(analog_inputs(&axes_profile(binding)).is_err(), "{}", binding);
+        }
+    }
+
+    #[test]
+    fn test_actions() {
+        let interaction = InteractionBuilder::new()
+            .interface("game", InterfaceBuilder::new().action("jump").action("fire"));
+        let mut inputs = Inputs::new(interaction);
+        inputs.interaction_mut().bind("game", "jump", "Key.Pressed.Space").unwrap();
+        inputs.interaction_mut().bind("game", "fire", "GamepadButton.Pressed.South").unwrap();
+
+        feed(&mut inputs,
+             &[Event::KeyboardInput(Pressed, 57, Some(Key::Space)),
+               Event::GamepadInput(Pressed, GamepadButton::South)]);
+        assert_eq!(inputs.triggered_actions("game"), Some(&["jump", "fire"][..]));
+
+        inputs.clear_actions();
+        feed(&mut inputs, &[Event::KeyboardInput(Released, 57, Some(Key::Space))]);
+        assert_eq!(inputs.triggered_actions("game"), Some(&[][..]));
+        assert_eq!(inputs.triggered_actions("menu"), None);
+    }
+}
@@ -8,6 +8,19 @@ macro_rules! input_interfaces {
                     $($variant,)*
                 }
 
+                impl Action {
+                    pub fn name(&self) -> &'static str {
+                        match *self {
+                            $(Action::$variant => stringify!($variant),)*
+                        }
+                    }
+                }
+
+                /// Returns the value of an analog action, see `Inputs::analog_value`.
+                pub fn analog_value(inputs: &$crate::Inputs, action: Action) -> Option<f32> {
+                    inputs.analog_value(stringify!($interface), action.name())
+                }
+
                 impl<'a> From<&'a str> for Action {
                     fn from(name: &'a str) -> Action {
                         match name {
@@ -0,0 +1,134 @@
+//! Input capture for rebinding menus
+//!
+//! While capturing, the next input is turned into a rule trigger, such as
+//! `Key.Pressed.F` or `Ctrl+Key.Pressed.S`, instead of triggering actions.
+
+use event::{ElementState, MouseButton};
+use interaction::{Input, MODIFIERS};
+use interaction::{element_state_to_str, key_to_str, mouse_button_to_str, gamepad_button_to_str};
+use interaction::wheel_direction_to_str;
+use state::InputState;
+
+pub(crate) struct Capture {
+    /// A modifier key pressed alone, captured if it is released before any other input.
+    pending_modifier: Option<Input>,
+    captured: Option<String>,
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Capture {
+            pending_modifier: None,
+            captured: None,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.captured.is_some()
+    }
+
+    pub fn take(&mut self) -> Option<String> {
+        self.captured.take()
+    }
+
+    pub fn feed(&mut self, input: &Input, state: &InputState) {
+        if self.is_done() {
+            return;
+        }
+
+        if !input.is_press() {
+            if let Some(pressed) = self.pending_modifier.take() {
+                if is_release_of(input, &pressed) {
+                    self.captured = input_rule(&pressed);
+                } else {
+                    self.pending_modifier = Some(pressed);
+                }
+            }
+            return;
+        }
+
+        if modifier_name(input).is_some() {
+            if self.pending_modifier.is_none() {
+                self.pending_modifier = Some(input.clone());
+            }
+            return;
+        }
+
+        if let Some(rule) = input_rule(input) {
+            let mut parts: Vec<String> = MODIFIERS.iter()
+                .filter(|&&(_, keys)| keys.iter().any(|key| state.is_key_held(key)))
+                .map(|&(name, _)| name.to_string())
+                .collect();
+            parts.push(rule);
+
+            self.captured = Some(parts.join("+"));
+        }
+    }
+}
+
+fn modifier_name(input: &Input) -> Option<&'static str> {
+    match input {
+        &Input::Key(_, key) => {
+            MODIFIERS.iter()
+                .find(|&&(_, keys)| keys.contains(&key))
+                .map(|&(name, _)| name)
+        }
+        _ => None,
+    }
+}
+
+fn is_release_of(input: &Input, pressed: &Input) -> bool {
+    match (input, pressed) {
+        (&Input::Key(ElementState::Released, released), &Input::Key(_, key)) => released == key,
+        _ => false,
+    }
+}
+
+/// Returns the rule trigger of `input`, or `None` if it cannot be expressed in a rule.
+pub(crate) fn input_rule(input: &Input) -> Option<String> {
+    match input {
+        &Input::Key(state, key) => {
+            Some(format!("Key.{}.{}", element_state_to_str(state), key_to_str(key)))
+        }
+        &Input::MouseButton(_, MouseButton::Other(_)) => None,
+        &Input::MouseButton(state, button) => {
+            Some(format!("MouseButton.{}.{}",
+                         element_state_to_str(state),
+                         mouse_button_to_str(button)))
+        }
+        &Input::GamepadButton(state, button) => {
+            Some(format!("GamepadButton.{}.{}",
+                         element_state_to_str(state),
+                         gamepad_button_to_str(button)))
+        }
+        &Input::MouseWheel(direction) => {
+            Some(format!("MouseWheel.{}", wheel_direction_to_str(direction)))
+        }
+    }
+}
+
+/// Normalizes a rule trigger so that equivalent triggers can be compared.
+pub(crate) fn canonical_rule(when: &str) -> String {
+    if !when.contains('+') && !when.contains('>') {
+        return when.trim().to_string();
+    }
+
+    let steps: Vec<String> = when.split('>')
+        .map(|step| {
+            let mut parts: Vec<String> = step.split('+').map(|p| p.trim().to_string()).collect();
+            if let Some(last) = parts.last_mut() {
+                if !last.contains('.') {
+                    *last = format!("Key.Pressed.{}", last);
+                }
+            }
+
+            // The modifiers can be written in any order, the key comes last.
+            let modifiers = parts.len() - 1;
+            parts[..modifiers].sort();
+
+            parts.join("+")
+        })
+        .collect();
+
+    steps.join(" > ")
+}
@@ -0,0 +1,428 @@
+//! Input recording and replay
+//!
+//! A record is a text log of the events received by `Inputs`, split in frames.
+//! Each frame starts with its duration in seconds:
+//!
+//! ```text
+//! Frame 0.016667
+//! Key.Pressed.Space 57
+//! MouseMoved 120 48
+//! Frame 0.016667
+//! MouseButton.Released.Left
+//! MouseWheel 0 -1
+//! ReceivedCharacter 97
+//! GamepadAxisMoved LeftStickX -0.5
+//! Focused false
+//! ```
+
+use std::io::{self, Write, BufRead, BufReader, Read};
+use std::collections::VecDeque;
+use event::{Event, MouseButton};
+use interaction::{key_to_str, key_from_str, mouse_button_to_str, mouse_button_from_str};
+use interaction::{element_state_to_str, element_state_from_str};
+use interaction::{gamepad_button_to_str, gamepad_button_from_str, axis_to_str, axis_from_str};
+use error::{ErrorKind, Result};
+use Inputs;
+
+/// Writes the events received by `Inputs` in a record.
+pub struct InputRecorder {
+    writer: Box<Write + Send>,
+    error: Option<io::Error>,
+}
+
+impl InputRecorder {
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        InputRecorder {
+            writer: Box::new(writer),
+            error: None,
+        }
+    }
+
+    /// Starts a new frame lasting `delta` seconds.
+    pub fn frame(&mut self, delta: f64) {
+        let result = writeln!(self.writer, "Frame {}", delta);
+        self.keep_error(result);
+    }
+
+    /// Records an event, the events ignored by `Inputs` are not recorded.
+    pub fn record(&mut self, event: &Event) {
+        let result = match event {
+            &Event::KeyboardInput(state, scan_code, Some(key)) => {
+                writeln!(self.writer,
+                         "Key.{}.{} {}",
+                         element_state_to_str(state),
+                         key_to_str(key),
+                         scan_code)
+            }
+            &Event::MouseMoved(x, y) => writeln!(self.writer, "MouseMoved {} {}", x, y),
+            &Event::MouseInput(state, button) => {
+                writeln!(self.writer,
+                         "MouseButton.{}.{}",
+                         element_state_to_str(state),
+                         button_name(button))
+            }
+            &Event::MouseWheel(x, y) => writeln!(self.writer, "MouseWheel {} {}", x, y),
+            // Characters are written as code points, since they may be whitespaces
+            &Event::ReceivedCharacter(c) => {
+                writeln!(self.writer, "ReceivedCharacter {}", c as u32)
+            }
+            &Event::Focused(focused) => writeln!(self.writer, "Focused {}", focused),
+            &Event::GamepadInput(state, button) => {
+                writeln!(self.writer,
+                         "GamepadButton.{}.{}",
+                         element_state_to_str(state),
+                         gamepad_button_to_str(button))
+            }
+            &Event::GamepadAxisMoved(axis, value) => {
+                writeln!(self.writer, "GamepadAxisMoved {} {}", axis_to_str(axis), value)
+            }
+            _ => Ok(()),
+        };
+
+        self.keep_error(result);
+    }
+
+    /// Flushes the record, returning the first error that happened while recording.
+    pub fn finish(mut self) -> Result<()> {
+        if let Some(error) = self.error.take() {
+            return Err(error.into());
+        }
+
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn keep_error(&mut self, result: io::Result<()>) {
+        if let Err(error) = result {
+            if self.error.is_none() {
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+/// The events received during a frame.
+#[derive(Clone, Debug)]
+pub struct RecordedFrame {
+    pub delta: f64,
+    pub events: Vec<Event>,
+}
+
+/// Feeds a record back into `Inputs`, frame by frame.
+pub struct InputPlayer {
+    frames: VecDeque<RecordedFrame>,
+}
+
+impl InputPlayer {
+    pub fn new(frames: Vec<RecordedFrame>) -> Self {
+        InputPlayer { frames: frames.into_iter().collect() }
+    }
+
+    /// Reads a record written by an `InputRecorder`.
+    pub fn load<R: Read>(reader: R) -> Result<Self> {
+        let mut frames: Vec<RecordedFrame> = Vec::new();
+
+        for (index, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let number = index + 1;
+            match parse_line(line) {
+                Some(Line::Frame(delta)) => {
+                    frames.push(RecordedFrame {
+                        delta: delta,
+                        events: Vec::new(),
+                    })
+                }
+                Some(Line::Event(event)) => {
+                    match frames.last_mut() {
+                        Some(frame) => frame.events.push(event),
+                        None => bail!(ErrorKind::RecordFormat(number)),
+                    }
+                }
+                None => bail!(ErrorKind::RecordFormat(number)),
+            }
+        }
+
+        Ok(InputPlayer::new(frames))
+    }
+
+    /// Returns the next recorded frame without feeding it.
+    pub fn next_frame(&mut self) -> Option<RecordedFrame> {
+        self.frames.pop_front()
+    }
+
+    /// Feeds the events of the next frame to `inputs`.
+    ///
+    /// Returns the frame duration, or `None` when the record is over.
+    pub fn play_frame(&mut self, inputs: &mut Inputs) -> Option<f64> {
+        self.frames.pop_front().map(|frame| {
+            inputs.new_frame(frame.delta);
+            for event in &frame.events {
+                inputs.handle_event(event);
+            }
+
+            frame.delta
+        })
+    }
+
+    pub fn remaining_frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+enum Line {
+    Frame(f64),
+    Event(Event),
+}
+
+fn parse_line(line: &str) -> Option<Line> {
+    let mut tokens = line.split_whitespace();
+    let head = match tokens.next() {
+        Some(head) => head,
+        None => return None,
+    };
+
+    let line = match head {
+        "Frame" => tokens.next().and_then(|t| t.parse::<f64>().ok()).map(Line::Frame),
+        "MouseMoved" => {
+            let x = tokens.next().and_then(|t| t.parse::<i32>().ok());
+            let y = tokens.next().and_then(|t| t.parse::<i32>().ok());
+
+            match (x, y) {
+                (Some(x), Some(y)) => Some(Line::Event(Event::MouseMoved(x, y))),
+                _ => None,
+            }
+        }
+        "MouseWheel" => {
+            let x = tokens.next().and_then(|t| t.parse::<f32>().ok());
+            let y = tokens.next().and_then(|t| t.parse::<f32>().ok());
+
+            match (x, y) {
+                (Some(x), Some(y)) => Some(Line::Event(Event::MouseWheel(x, y))),
+                _ => None,
+            }
+        }
+        "ReceivedCharacter" => {
+            tokens.next()
+                .and_then(|t| t.parse::<u32>().ok())
+                .and_then(::std::char::from_u32)
+                .map(|c| Line::Event(Event::ReceivedCharacter(c)))
+        }
+        "GamepadAxisMoved" => {
+            let axis = tokens.next().and_then(axis_from_str);
+            let value = tokens.next().and_then(|t| t.parse::<f32>().ok());
+
+            match (axis, value) {
+                (Some(axis), Some(value)) => {
+                    Some(Line::Event(Event::GamepadAxisMoved(axis, value)))
+                }
+                _ => None,
+            }
+        }
+        "Focused" => {
+            tokens.next()
+                .and_then(|t| t.parse::<bool>().ok())
+                .map(|focused| Line::Event(Event::Focused(focused)))
+        }
+        _ => {
+            let mut split = head.split('.');
+            let kind = split.next();
+            let state = split.next().and_then(element_state_from_str);
+            let name = split.next();
+
+            match (kind, state, name) {
+                (Some("Key"), Some(state), Some(name)) => {
+                    let key = key_from_str(name);
+                    let scan_code = tokens.next().and_then(|t| t.parse::<u8>().ok());
+
+                    match (key, scan_code) {
+                        (Some(key), Some(scan_code)) => {
+                            Some(Line::Event(Event::KeyboardInput(state, scan_code, Some(key))))
+                        }
+                        _ => None,
+                    }
+                }
+                (Some("MouseButton"), Some(state), Some(name)) => {
+                    button_from_name(name)
+                        .map(|button| Line::Event(Event::MouseInput(state, button)))
+                }
+                (Some("GamepadButton"), Some(state), Some(name)) => {
+                    gamepad_button_from_str(name)
+                        .map(|button| Line::Event(Event::GamepadInput(state, button)))
+                }
+                _ => None,
+            }
+        }
+    };
+
+    match tokens.next() {
+        Some(_) => None,
+        None => line,
+    }
+}
+
+fn button_name(button: MouseButton) -> String {
+    match button {
+        MouseButton::Other(code) => format!("Other{}", code),
+        button => mouse_button_to_str(button).to_string(),
+    }
+}
+
+fn button_from_name(name: &str) -> Option<MouseButton> {
+    if name.starts_with("Other") {
+        name["Other".len()..].parse::<u8>().ok().map(MouseButton::Other)
+    } else {
+        mouse_button_from_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use event::{Key, GamepadButton, Axis};
+    use state::InputState;
+    use interaction::InteractionBuilder;
+    use error::ErrorKind;
+
+    /// A writer whose content can still be read once it has been given to a recorder.
+    #[derive(Clone)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn frames() -> Vec<RecordedFrame> {
+        use event::ElementState::*;
+
+        vec![RecordedFrame {
+                 delta: 0.016,
+                 events: vec![Event::KeyboardInput(Pressed, 57, Some(Key::Space)),
+                              Event::MouseMoved(10, 20),
+                              Event::MouseMoved(15, 22),
+                              Event::MouseWheel(0., -1.),
+                              Event::ReceivedCharacter('a'),
+                              Event::ReceivedCharacter(' '),
+                              Event::GamepadInput(Pressed, GamepadButton::South),
+                              Event::GamepadAxisMoved(Axis::LeftStickX, -0.5)],
+             },
+             RecordedFrame {
+                 delta: 0.02,
+                 events: vec![Event::KeyboardInput(Released, 57, Some(Key::Space)),
+                              Event::MouseInput(Pressed, MouseButton::Left),
+                              Event::MouseInput(Pressed, MouseButton::Other(4)),
+                              Event::Focused(false)],
+             }]
+    }
+
+    fn assert_same_state(a: &InputState, b: &InputState) {
+        assert_eq!(a.mouse_position(), b.mouse_position());
+        assert_eq!(a.mouse_delta(), b.mouse_delta());
+        assert_eq!(a.scroll_delta(), b.scroll_delta());
+        assert_eq!(a.text(), b.text());
+        assert_eq!(a.is_window_focused(), b.is_window_focused());
+        assert_eq!(a.is_key_held(&Key::Space), b.is_key_held(&Key::Space));
+        assert_eq!(a.is_key_pressed(&Key::Space), b.is_key_pressed(&Key::Space));
+        assert_eq!(a.is_key_released(&Key::Space), b.is_key_released(&Key::Space));
+        for button in &[MouseButton::Left, MouseButton::Other(4)] {
+            assert_eq!(a.is_mouse_button_held(button), b.is_mouse_button_held(button));
+            assert_eq!(a.is_mouse_button_pressed(button), b.is_mouse_button_pressed(button));
+        }
+        assert_eq!(a.is_gamepad_button_held(&GamepadButton::South),
+                   b.is_gamepad_button_held(&GamepadButton::South));
+        assert_eq!(a.axis(Axis::LeftStickX), b.axis(Axis::LeftStickX));
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let buffer = SharedBuffer(Arc::new(Mutex::new(Vec::new())));
+        let mut recorded = Inputs::new(InteractionBuilder::new());
+        let mut replayed = Inputs::new(InteractionBuilder::new());
+
+        recorded.start_recording(buffer.clone());
+        let mut frame_states = Vec::new();
+        for frame in frames() {
+            recorded.new_frame(frame.delta);
+            for event in &frame.events {
+                recorded.handle_event(event);
+            }
+
+            let state = recorded.state();
+            frame_states.push((state.mouse_delta(),
+                               state.scroll_delta(),
+                               state.text().to_string(),
+                               state.is_key_pressed(&Key::Space),
+                               state.is_key_released(&Key::Space)));
+        }
+        recorded.stop_recording().unwrap();
+
+        let log = buffer.0.lock().unwrap().clone();
+        let mut player = InputPlayer::load(&log[..]).unwrap();
+        let expected = frames();
+        assert_eq!(player.remaining_frames(), expected.len());
+        for frame in &expected {
+            let loaded = player.next_frame().unwrap();
+            assert_eq!(loaded.delta, frame.delta);
+            assert_eq!(loaded.events, frame.events);
+        }
+
+        let mut player = InputPlayer::load(&log[..]).unwrap();
+        for (frame, frame_state) in expected.iter().zip(frame_states) {
+            assert_eq!(player.play_frame(&mut replayed), Some(frame.delta));
+
+            let state = replayed.state();
+            assert_eq!((state.mouse_delta(),
+                        state.scroll_delta(),
+                        state.text().to_string(),
+                        state.is_key_pressed(&Key::Space),
+                        state.is_key_released(&Key::Space)),
+                       frame_state);
+        }
+        assert_eq!(player.play_frame(&mut replayed), None);
+        assert_same_state(recorded.state(), replayed.state());
+    }
+
+    fn load_error_line(record: &str) -> Option<usize> {
+        match InputPlayer::load(record.as_bytes()) {
+            Ok(_) => None,
+            Err(error) => {
+                match *error.kind() {
+                    ErrorKind::RecordFormat(line) => Some(line),
+                    _ => panic!("unexpected error: {}", error),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_load_malformed() {
+        assert_eq!(load_error_line("Frame 0.1\n\nMouseMoved 1 2\n"), None);
+        assert_eq!(load_error_line("MouseMoved 1 2\nFrame 0.1\n"), Some(1));
+        assert_eq!(load_error_line("Frame\n"), Some(1));
+        assert_eq!(load_error_line("Frame 0.1 0.2\n"), Some(1));
+        assert_eq!(load_error_line("Frame 0.1\n\nMouseMoved 1\n"), Some(3));
+        assert_eq!(load_error_line("Frame 0.1\nKey.Pressed.Nope 12\n"), Some(2));
+        assert_eq!(load_error_line("Frame 0.1\nKey.Held.Space 57\n"), Some(2));
+        assert_eq!(load_error_line("Frame 0.1\nKey.Pressed.Space\n"), Some(2));
+        assert_eq!(load_error_line("Frame 0.1\nMouseButton.Pressed.Other\n"), Some(2));
+        // Surrogates are not characters
+        assert_eq!(load_error_line("Frame 0.1\nReceivedCharacter 55296\n"), Some(2));
+        assert_eq!(load_error_line("Frame 0.1\nGamepadAxisMoved Wheel 0.5\n"), Some(2));
+        assert_eq!(load_error_line("Frame 0.1\nFocused maybe\n"), Some(2));
+    }
+}
@@ -1,11 +1,13 @@
-use std::collections::HashSet;
-use cgmath::Point2;
-use glutin::{MouseButton, VirtualKeyCode, ElementState};
+use std::collections::{HashSet, HashMap};
+use std::hash::Hash;
+use cgmath::{Point2, Vector2};
+use event::{MouseButton, Key, ElementState, GamepadButton, Axis};
 
 pub struct InputState {
     mouse: MouseState,
     keyboard: KeyboardState,
     window: WindowState,
+    gamepad: GamepadState,
 }
 
 impl InputState {
@@ -14,41 +16,88 @@ impl InputState {
             mouse: MouseState::new(),
             keyboard: KeyboardState::new(),
             window: WindowState::new(),
+            gamepad: GamepadState::new(),
         }
     }
 
+    /// Resets the per frame state: deltas, typed text and pressed or released edges.
+    pub(crate) fn new_frame(&mut self) {
+        self.mouse.delta = Vector2::new(0, 0);
+        self.mouse.scroll = Vector2::new(0., 0.);
+        self.mouse.buttons.new_frame();
+        self.keyboard.keys.new_frame();
+        self.keyboard.text.clear();
+        self.gamepad.buttons.new_frame();
+    }
+
     pub(crate) fn update_mouse_position(&mut self, position: Point2<i32>) {
+        if self.mouse.position_known {
+            self.mouse.delta = self.mouse.delta + (position - self.mouse.position);
+        }
+
         self.mouse.position = position;
+        self.mouse.position_known = true;
     }
 
     pub fn mouse_position(&self) -> Point2<i32> {
         self.mouse.position
     }
 
-    pub(crate) fn update_mouse_button(&mut self, button: MouseButton, state: ElementState) {
-        use glutin::ElementState::*;
+    /// Returns how much the mouse moved during the frame.
+    pub fn mouse_delta(&self) -> Vector2<i32> {
+        self.mouse.delta
+    }
 
-        match state {
-            Pressed => self.mouse.buttons.insert(button),
-            Released => self.mouse.buttons.remove(&button),
-        };
+    pub(crate) fn update_scroll(&mut self, delta: Vector2<f32>) {
+        self.mouse.scroll = self.mouse.scroll + delta;
+    }
+
+    /// Returns how much the mouse wheel scrolled during the frame, in lines.
+    pub fn scroll_delta(&self) -> Vector2<f32> {
+        self.mouse.scroll
+    }
+
+    pub(crate) fn update_mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        self.mouse.buttons.update(button, state);
     }
 
     pub fn is_mouse_button_held(&self, button: &MouseButton) -> bool {
-        self.mouse.buttons.contains(button)
+        self.mouse.buttons.is_held(button)
     }
 
-    pub(crate) fn update_key(&mut self, key: VirtualKeyCode, state: ElementState) {
-        use glutin::ElementState::*;
+    pub fn is_mouse_button_pressed(&self, button: &MouseButton) -> bool {
+        self.mouse.buttons.is_pressed(button)
+    }
 
-        match state {
-            Pressed => self.keyboard.keys.insert(key),
-            Released => self.keyboard.keys.remove(&key),
-        };
+    pub fn is_mouse_button_released(&self, button: &MouseButton) -> bool {
+        self.mouse.buttons.is_released(button)
+    }
+
+    pub(crate) fn update_key(&mut self, key: Key, state: ElementState) {
+        self.keyboard.keys.update(key, state);
+    }
+
+    pub fn is_key_held(&self, key: &Key) -> bool {
+        self.keyboard.keys.is_held(key)
+    }
+
+    /// Returns true if the key started being held during the frame.
+    pub fn is_key_pressed(&self, key: &Key) -> bool {
+        self.keyboard.keys.is_pressed(key)
+    }
+
+    /// Returns true if the key stopped being held during the frame.
+    pub fn is_key_released(&self, key: &Key) -> bool {
+        self.keyboard.keys.is_released(key)
+    }
+
+    pub(crate) fn push_character(&mut self, c: char) {
+        self.keyboard.text.push(c);
     }
 
-    pub fn is_key_held(&self, key: &VirtualKeyCode) -> bool {
-        self.keyboard.keys.contains(key)
+    /// Returns the text typed during the frame.
+    pub fn text(&self) -> &str {
+        &self.keyboard.text
     }
 
     pub(crate) fn update_window_focus(&mut self, focused: bool) {
@@ -58,29 +107,116 @@ impl InputState {
     pub fn is_window_focused(&self) -> bool {
         self.window.focused
     }
+
+    pub(crate) fn update_gamepad_button(&mut self, button: GamepadButton, state: ElementState) {
+        self.gamepad.buttons.update(button, state);
+    }
+
+    pub fn is_gamepad_button_held(&self, button: &GamepadButton) -> bool {
+        self.gamepad.buttons.is_held(button)
+    }
+
+    pub fn is_gamepad_button_pressed(&self, button: &GamepadButton) -> bool {
+        self.gamepad.buttons.is_pressed(button)
+    }
+
+    pub fn is_gamepad_button_released(&self, button: &GamepadButton) -> bool {
+        self.gamepad.buttons.is_released(button)
+    }
+
+    pub(crate) fn update_axis(&mut self, axis: Axis, value: f32) {
+        self.gamepad.axes.insert(axis, value);
+    }
+
+    /// Returns the raw value of `axis`, without any dead zone applied.
+    pub fn axis(&self, axis: Axis) -> f32 {
+        self.gamepad.axes.get(&axis).cloned().unwrap_or(0.)
+    }
+}
+
+/// Tracks the held buttons, and the ones pressed or released during the frame.
+pub(crate) struct Buttons<B: Eq + Hash> {
+    held: HashSet<B>,
+    pressed: HashSet<B>,
+    released: HashSet<B>,
+}
+
+impl<B: Eq + Hash + Copy> Buttons<B> {
+    pub fn new() -> Self {
+        Buttons {
+            held: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+        }
+    }
+
+    pub fn update(&mut self, button: B, state: ElementState) {
+        use event::ElementState::*;
+
+        // Repeated press events of a held button are not edges
+        match state {
+            Pressed => {
+                if self.held.insert(button) {
+                    self.pressed.insert(button);
+                }
+            }
+            Released => {
+                if self.held.remove(&button) {
+                    self.released.insert(button);
+                }
+            }
+        }
+    }
+
+    pub fn is_held(&self, button: &B) -> bool {
+        self.held.contains(button)
+    }
+
+    pub fn is_pressed(&self, button: &B) -> bool {
+        self.pressed.contains(button)
+    }
+
+    pub fn is_released(&self, button: &B) -> bool {
+        self.released.contains(button)
+    }
+
+    pub fn new_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
 }
 
 pub(crate) struct MouseState {
     position: Point2<i32>,
-    buttons: HashSet<MouseButton>,
+    position_known: bool,
+    delta: Vector2<i32>,
+    scroll: Vector2<f32>,
+    buttons: Buttons<MouseButton>,
 }
 
 impl MouseState {
     pub fn new() -> Self {
         MouseState {
             position: Point2::new(0, 0),
-            buttons: HashSet::new(),
+            position_known: false,
+            delta: Vector2::new(0, 0),
+            scroll: Vector2::new(0., 0.),
+            buttons: Buttons::new(),
         }
     }
 }
 
 pub(crate) struct KeyboardState {
-    keys: HashSet<VirtualKeyCode>,
+    keys: Buttons<Key>,
+    text: String,
 }
 
 impl KeyboardState {
     pub fn new() -> Self {
-        KeyboardState { keys: HashSet::new() }
+        KeyboardState {
+            keys: Buttons::new(),
+            text: String::new(),
+        }
     }
 }
 
@@ -92,4 +228,94 @@ impl WindowState {
     pub fn new() -> Self {
         WindowState { focused: true }
     }
+}
+
+pub(crate) struct GamepadState {
+    buttons: Buttons<GamepadButton>,
+    axes: HashMap<Axis, f32>,
+}
+
+impl GamepadState {
+    pub fn new() -> Self {
+        GamepadState {
+            buttons: Buttons::new(),
+            axes: HashMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use event::ElementState::*;
+
+    #[test]
+    fn test_button_edges() {
+        let mut state = InputState::new();
+
+        state.update_key(Key::A, Pressed);
+        assert!(state.is_key_pressed(&Key::A));
+        assert!(state.is_key_held(&Key::A));
+
+        // The edges only last one frame, and repeated presses are not edges
+        state.new_frame();
+        state.update_key(Key::A, Pressed);
+        assert!(!state.is_key_pressed(&Key::A));
+        assert!(state.is_key_held(&Key::A));
+
+        state.update_key(Key::A, Released);
+        assert!(state.is_key_released(&Key::A));
+        assert!(!state.is_key_held(&Key::A));
+
+        state.new_frame();
+        assert!(!state.is_key_released(&Key::A));
+
+        // Released without being held, for example when the window got the focus late
+        state.update_mouse_button(MouseButton::Left, Released);
+        assert!(!state.is_mouse_button_released(&MouseButton::Left));
+
+        // Pressed and released in the same frame
+        state.update_gamepad_button(GamepadButton::South, Pressed);
+        state.update_gamepad_button(GamepadButton::South, Released);
+        assert!(state.is_gamepad_button_pressed(&GamepadButton::South));
+        assert!(state.is_gamepad_button_released(&GamepadButton::South));
+        assert!(!state.is_gamepad_button_held(&GamepadButton::South));
+    }
+
+    #[test]
+    fn test_mouse_delta() {
+        let mut state = InputState::new();
+
+        // The first position is not a move
+        state.update_mouse_position(Point2::new(10, 10));
+        assert_eq!(state.mouse_delta(), Vector2::new(0, 0));
+
+        state.update_mouse_position(Point2::new(15, 8));
+        state.update_mouse_position(Point2::new(20, 4));
+        assert_eq!(state.mouse_delta(), Vector2::new(10, -6));
+        assert_eq!(state.mouse_position(), Point2::new(20, 4));
+
+        state.new_frame();
+        assert_eq!(state.mouse_delta(), Vector2::new(0, 0));
+        assert_eq!(state.mouse_position(), Point2::new(20, 4));
+
+        state.update_mouse_position(Point2::new(19, 4));
+        assert_eq!(state.mouse_delta(), Vector2::new(-1, 0));
+    }
+
+    #[test]
+    fn test_scroll_and_text_reset() {
+        let mut state = InputState::new();
+
+        state.update_scroll(Vector2::new(0., 1.));
+        state.update_scroll(Vector2::new(0.5, 2.));
+        state.push_character('h');
+        state.push_character('i');
+        assert_eq!(state.scroll_delta(), Vector2::new(0.5, 3.));
+        assert_eq!(state.text(), "hi");
+
+        state.new_frame();
+        assert_eq!(state.scroll_delta(), Vector2::new(0., 0.));
+        assert_eq!(state.text(), "");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,19 @@
+extern crate lazybox_settings as settings;
+
+use std::env;
+use settings::SettingsBuilder;
+
+// Try `LAZYBOX_GRAPHICS__FPS_CAP=30 cargo run --example layers -- --set audio.time_limit=60`
+fn main() {
+    let s = SettingsBuilder::new("examples/defaults.yml")
+        .optional_file("examples/overrides.yml")
+        .env("LAZYBOX")
+        .args(env::args().skip(1))
+        .build()
+        .unwrap();
+
+    println!("graphics.fps_cap = {:?}", s["graphics"]["fps_cap"]);
+    println!("  from {}", s.origin("graphics.fps_cap").unwrap());
+    println!("audio.time_limit = {:?}", s["audio"]["time_limit"]);
+    println!("  from {}", s.origin("audio.time_limit").unwrap());
+}
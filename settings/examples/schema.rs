@@ -0,0 +1,20 @@
+extern crate lazybox_settings as settings;
+
+use settings::{Settings, Schema};
+
+fn main() {
+    let schema = Schema::from_file("examples/schema.yml").unwrap();
+    print!("{}", schema.render());
+
+    let mut s = Settings::new("examples/defaults.yml").unwrap();
+    s.set_schema(schema).unwrap();
+    s.override_with("examples/overrides.yml").unwrap();
+
+    let strict = Schema::map()
+        .field("graphics", Schema::map()
+            .field("fps_cap", Schema::integer_in(1, 30).nullable())
+            .field("effects", Schema::array(Schema::one_of(&["bloom"]))));
+    if let Err(e) = s.set_schema(strict) {
+        println!("\n{}", e);
+    }
+}
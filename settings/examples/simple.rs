@@ -20,4 +20,16 @@ fn main() {
     assert!(a["high_quality"].as_bool() == Some(false));
     assert!(a["time_limit"].as_i64() == None);
     assert!(!s["inputs"]["stuff"][0].is_valid());
+
+    assert!(s.path("graphics.effects[1]").as_str() == Some("fxaa"));
+    assert!(!s.path("graphics.effects[2]").is_valid());
+    assert!(s.get_or("audio.time_limit", 30).unwrap() == 30);
+    assert!(s.get_or("audio.volume", 1.0).unwrap() == 1.0);
+
+    for effect in e.as_array().unwrap() {
+        println!("effect: {:?}", effect);
+    }
+    for (key, value) in a.as_map().unwrap() {
+        println!("audio.{}: {:?}", key, value);
+    }
 }
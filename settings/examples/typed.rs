@@ -0,0 +1,33 @@
+extern crate lazybox_settings as settings;
+#[macro_use]
+extern crate serde_derive;
+
+use settings::Settings;
+
+#[derive(Debug, Deserialize)]
+struct Graphics {
+    fps_cap: Option<u32>,
+    accelerate: bool,
+    effects: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Audio {
+    high_quality: bool,
+    time_limit: u32,
+}
+
+fn main() {
+    let mut s = Settings::new("examples/defaults.yml").unwrap();
+    s.override_with("examples/overrides.yml").unwrap();
+
+    let graphics: Graphics = s.get("graphics").unwrap();
+    println!("{:#?}", graphics);
+    assert!(graphics.fps_cap == Some(60));
+    assert!(graphics.effects == ["bloom", "fxaa"]);
+
+    match s.get::<Audio>("audio") {
+        Ok(audio) => println!("{:#?}", audio),
+        Err(e) => println!("{}", e),
+    }
+}
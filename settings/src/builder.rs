@@ -0,0 +1,303 @@
+//! Settings built from several sources
+//!
+//! Sources are applied in the order they are added, each one overriding the
+//! values of the previous ones:
+//!
+//! ```ignore
+//! let settings = SettingsBuilder::new("defaults.yml")
+//!     .optional_file("user.yml")
+//!     .env("LAZYBOX")
+//!     .args(std::env::args())
+//!     .build()?;
+//! ```
+//!
+//! `LAZYBOX_GRAPHICS__FPS_CAP=30` and `--set graphics.fps_cap=30` both set
+//! `graphics.fps_cap`, their values are parsed as YAML.
+
+use std::{env, fmt};
+use std::path::PathBuf;
+use yaml_rust::{Yaml, YamlLoader};
+use yaml_rust::yaml::Hash;
+
+use {Error, Layer, Schema, Settings, ValueMap};
+
+/// Where the values of a layer come from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Origin {
+    /// The defaults or an override file
+    File(PathBuf),
+    /// Yaml given to `Settings::from_yaml` or `Settings::override_yaml`
+    Yaml,
+    /// An environment variable, by name
+    Environment(String),
+    /// A `--set key=value` argument, by `key=value`
+    Argument(String),
+    /// The values changed by `Settings::set`
+    Runtime,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Origin::File(ref path) => write!(f, "file `{}`", path.display()),
+            &Origin::Yaml => write!(f, "yaml"),
+            &Origin::Environment(ref name) => write!(f, "environment variable `{}`", name),
+            &Origin::Argument(ref arg) => write!(f, "argument `--set {}`", arg),
+            &Origin::Runtime => write!(f, "runtime change"),
+        }
+    }
+}
+
+enum Source {
+    File { path: PathBuf, optional: bool },
+    Yaml(Yaml),
+    Environment(String),
+    Arguments(Vec<String>),
+}
+
+pub struct SettingsBuilder {
+    defaults: PathBuf,
+    sources: Vec<Source>,
+    schema: Option<Schema>,
+}
+
+impl SettingsBuilder {
+    pub fn new<P: Into<PathBuf>>(defaults_path: P) -> Self {
+        SettingsBuilder {
+            defaults: defaults_path.into(),
+            sources: Vec::new(),
+            schema: None,
+        }
+    }
+
+    pub fn file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.sources.push(Source::File {
+            path: path.into(),
+            optional: false,
+        });
+        self
+    }
+
+    /// Adds a file that is skipped if it does not exist, such as the settings of a user.
+    pub fn optional_file<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.sources.push(Source::File {
+            path: path.into(),
+            optional: true,
+        });
+        self
+    }
+
+    pub fn yaml(mut self, yaml: Yaml) -> Self {
+        self.sources.push(Source::Yaml(yaml));
+        self
+    }
+
+    /// Adds the environment variables starting with `prefix` followed by `_`.
+    ///
+    /// The rest of the name is the lowercase key, with `__` between the keys of nested maps.
+    /// The variables whose key is not in the settings, such as `LAZYBOX_LOG`, are ignored
+    /// since they may be meant for something else.
+    pub fn env<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.sources.push(Source::Environment(prefix.into()));
+        self
+    }
+
+    /// Adds the `--set key=value` and `--set=key=value` arguments, the others are ignored.
+    pub fn args<I>(mut self, args: I) -> Self
+        where I: IntoIterator,
+              I::Item: Into<String>
+    {
+        self.sources.push(Source::Arguments(args.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Validates the defaults and every source against `schema`.
+    pub fn schema(mut self, schema: Schema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    pub fn build(self) -> Result<Settings, Error> {
+        let mut settings = try!(Settings::new(&self.defaults));
+        if let Some(schema) = self.schema {
+            try!(settings.set_schema(schema));
+        }
+
+        for source in self.sources {
+            match source {
+                Source::File { path, optional } => {
+                    if !optional || path.exists() {
+                        try!(settings.override_with(path));
+                    }
+                }
+                Source::Yaml(yaml) => try!(settings.override_yaml(yaml)),
+                Source::Environment(prefix) => {
+                    for (origin, yaml) in env_layers(&prefix, &settings) {
+                        try!(settings.override_layer(Layer::new(origin, yaml)));
+                    }
+                }
+                Source::Arguments(args) => {
+                    for (origin, yaml) in try!(arg_layers(&args)) {
+                        try!(settings.override_layer(Layer::new(origin, yaml)));
+                    }
+                }
+            }
+        }
+
+        Ok(settings)
+    }
+}
+
+fn env_layers(prefix: &str, values: &ValueMap) -> Vec<(Origin, Yaml)> {
+    let prefix = format!("{}_", prefix);
+    let mut vars: Vec<_> = env::vars().filter(|&(ref name, _)| name.starts_with(&prefix)).collect();
+    vars.sort();
+
+    vars.into_iter()
+        .filter_map(|(name, value)| {
+            let keys: Vec<_> = name[prefix.len()..].split("__").map(str::to_lowercase).collect();
+            if !values.path(&keys.join(".")).is_valid() {
+                return None;
+            }
+
+            let yaml = nested_yaml(&keys, parse_value(&value));
+            Some((Origin::Environment(name), yaml))
+        })
+        .collect()
+}
+
+fn arg_layers(args: &[String]) -> Result<Vec<(Origin, Yaml)>, Error> {
+    let mut layers = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let set = if arg == "--set" {
+            match args.next() {
+                Some(set) => &set[..],
+                None => return Err(Error::InvalidArgument(arg.clone())),
+            }
+        } else if arg.starts_with("--set=") {
+            &arg["--set=".len()..]
+        } else {
+            continue;
+        };
+
+        let (key, value) = match set.find('=') {
+            Some(i) if i > 0 => (&set[..i], &set[i + 1..]),
+            _ => return Err(Error::InvalidArgument(set.to_string())),
+        };
+        let keys: Vec<_> = key.split('.').map(str::to_string).collect();
+        layers.push((Origin::Argument(set.to_string()), nested_yaml(&keys, parse_value(value))));
+    }
+
+    Ok(layers)
+}
+
+/// Parses a value as YAML, falling back to a string.
+fn parse_value(value: &str) -> Yaml {
+    match YamlLoader::load_from_str(value).map(|docs| docs.into_iter().next()) {
+        Ok(Some(yaml)) => yaml,
+        _ => Yaml::String(value.to_string()),
+    }
+}
+
+/// Nests `value` in a map for each key.
+pub(crate) fn nested_yaml(keys: &[String], value: Yaml) -> Yaml {
+    keys.iter().rev().fold(value, |value, key| {
+        let mut h = Hash::new();
+        h.insert(Yaml::String(key.clone()), value);
+        Yaml::Hash(h)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+    use yaml_rust::YamlLoader;
+    use Error;
+    use super::*;
+
+    fn write(name: &str, source: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("settings-builder-{}.yml", name));
+        File::create(&path).and_then(|mut f| f.write_all(source.as_bytes())).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_layer_order() {
+        let defaults = write("order-defaults",
+                             "graphics:\n  fps_cap: 60\n  vsync: false\n  scale: 1\naudio:\n  \
+                              volume: 1.0\n  muted: false\n");
+        let user = write("order-user", "graphics:\n  vsync: true\n  fps_cap: 120\n");
+        env::set_var("SETTINGS_ORDER_GRAPHICS__FPS_CAP", "30");
+        env::set_var("SETTINGS_ORDER_AUDIO__MUTED", "true");
+        env::set_var("SETTINGS_ORDER_LOG", "debug");
+
+        let args = vec!["game", "--set", "graphics.fps_cap=45", "--set=audio.volume=0.5"];
+        let yaml = YamlLoader::load_from_str("graphics:\n  scale: 2\n  vsync: false\n")
+            .unwrap()
+            .remove(0);
+        let mut settings = SettingsBuilder::new(&defaults)
+            .optional_file(env::temp_dir().join("settings-builder-missing.yml"))
+            .file(&user)
+            .yaml(yaml)
+            .env("SETTINGS_ORDER")
+            .args(args)
+            .build()
+            .unwrap();
+        fs::remove_file(&defaults).unwrap();
+        fs::remove_file(&user).unwrap();
+
+        assert_eq!(settings.get::<u32>("graphics.fps_cap"), Ok(45));
+        assert_eq!(settings.get::<bool>("graphics.vsync"), Ok(false));
+        assert_eq!(settings.get::<f64>("graphics.scale"), Ok(2.));
+        assert_eq!(settings.get::<f64>("audio.volume"), Ok(0.5));
+        assert_eq!(settings.get::<bool>("audio.muted"), Ok(true));
+        assert!(!settings.path("log").is_valid());
+
+        assert_eq!(settings.origin("graphics.fps_cap"),
+                   Some(&Origin::Argument("graphics.fps_cap=45".to_string())));
+        assert_eq!(settings.origin("graphics.scale"), Some(&Origin::Yaml));
+        assert_eq!(settings.origin("audio.muted"),
+                   Some(&Origin::Environment("SETTINGS_ORDER_AUDIO__MUTED".to_string())));
+        assert_eq!(settings.origin("audio"),
+                   Some(&Origin::Argument("audio.volume=0.5".to_string())));
+        assert_eq!(settings.origin("graphics.missing"), None);
+
+        settings.set("graphics.vsync", true).unwrap();
+        assert_eq!(settings.origin("graphics.vsync"), Some(&Origin::Runtime));
+        assert_eq!(settings.origin("graphics.fps_cap"),
+                   Some(&Origin::Argument("graphics.fps_cap=45".to_string())));
+    }
+
+    #[test]
+    fn test_file_origin() {
+        let defaults = write("origin-defaults", "graphics:\n  fps_cap: 60\n  vsync: false\n");
+        let user = write("origin-user", "graphics:\n  vsync: true\n");
+        let settings = SettingsBuilder::new(&defaults).file(&user).build().unwrap();
+        fs::remove_file(&defaults).unwrap();
+        fs::remove_file(&user).unwrap();
+
+        assert_eq!(settings.origin("graphics.fps_cap"), Some(&Origin::File(defaults)));
+        assert_eq!(settings.origin("graphics.vsync"), Some(&Origin::File(user)));
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let defaults = write("arguments-defaults", "graphics:\n  fps_cap: 60\n");
+        let args = [("--set", "--set"), ("--set=fps_cap", "fps_cap"), ("--set==1", "=1")];
+        for &(arg, invalid) in &args {
+            let result = SettingsBuilder::new(&defaults).args(vec!["game", arg]).build();
+            assert_eq!(result.map(|_| ()), Err(Error::InvalidArgument(invalid.to_string())));
+        }
+
+        let result = SettingsBuilder::new(&defaults)
+            .args(vec!["--set", "graphics.vsync=true"])
+            .build();
+        assert_eq!(result.map(|_| ()), Err(Error::InvalidOverride("graphics.vsync".to_string())));
+        fs::remove_file(&defaults).unwrap();
+    }
+}
@@ -0,0 +1,129 @@
+//! Notifications of changed values
+
+use std::sync::mpsc::{self, Sender, Receiver};
+
+use {de, Value, ValueMap};
+
+/// A subscribed value that changed.
+#[derive(Clone, Debug)]
+pub struct Change {
+    /// The subscribed key, the change may be in one of its values.
+    pub key: String,
+    /// The new value.
+    pub value: Value,
+}
+
+pub(crate) struct Subscribers {
+    subscribers: Vec<(String, Sender<Change>)>,
+}
+
+impl Subscribers {
+    pub(crate) fn new() -> Self {
+        Subscribers { subscribers: Vec::new() }
+    }
+
+    pub(crate) fn subscribe(&mut self, key: &str) -> Receiver<Change> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.push((key.to_string(), sender));
+        receiver
+    }
+
+    /// Sends the changes from `old` to `new`, and forgets the dropped receivers.
+    pub(crate) fn notify(&mut self, old: &ValueMap, new: &ValueMap) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+
+        let mut changed = Vec::new();
+        changed_keys(old, new, "", &mut changed);
+
+        self.subscribers.retain(|&(ref key, ref sender)| {
+            if !changed.iter().any(|c| contains(key, c) || contains(c, key)) {
+                return true;
+            }
+
+            let change = Change {
+                key: key.clone(),
+                value: new.path(key).clone(),
+            };
+            sender.send(change).is_ok()
+        });
+    }
+}
+
+/// Returns true if `key` is `parent` or one of its values.
+fn contains(parent: &str, key: &str) -> bool {
+    key.starts_with(parent) &&
+    (key.len() == parent.len() || key[parent.len()..].starts_with(|c| c == '.' || c == '['))
+}
+
+fn changed_keys(old: &ValueMap, new: &ValueMap, path: &str, changed: &mut Vec<String>) {
+    for (key, value) in &new.0 {
+        match (&old[key], value) {
+            (&Value::Map(ref old), &Value::Map(ref new)) => {
+                changed_keys(old, new, &de::child_path(path, key), changed);
+            }
+            (old, new) => {
+                if old != new {
+                    changed.push(de::child_path(path, key));
+                }
+            }
+        }
+    }
+
+    for key in old.0.keys().filter(|key| !new.0.contains_key(*key)) {
+        changed.push(de::child_path(path, key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::Receiver;
+    use yaml_rust::YamlLoader;
+    use {Settings, Value};
+    use super::*;
+
+    fn settings() -> Settings {
+        let source = "audio:\n  volume: 1.0\n  muted: false\ngraphics:\n  effects: [bloom]\n";
+        Settings::from_yaml(YamlLoader::load_from_str(source).unwrap().remove(0)).unwrap()
+    }
+
+    fn changes(receiver: &Receiver<Change>) -> Vec<(String, Value)> {
+        receiver.try_iter().map(|change| (change.key, change.value)).collect()
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let mut settings = settings();
+        let volume = settings.subscribe("audio.volume");
+        let audio = settings.subscribe("audio");
+        let effect = settings.subscribe("graphics.effects[0]");
+
+        settings.set("audio.volume", 0.5).unwrap();
+        assert_eq!(changes(&volume), [("audio.volume".to_string(), Value::Real(0.5))]);
+        assert_eq!(changes(&audio).len(), 1);
+        assert_eq!(changes(&effect), []);
+
+        // Setting the same value changes nothing
+        settings.set("audio.volume", 0.5).unwrap();
+        settings.set("audio.muted", true).unwrap();
+        assert_eq!(changes(&volume), []);
+        assert_eq!(changes(&audio)[0].1["muted"], Value::Boolean(true));
+
+        let yaml = YamlLoader::load_from_str("graphics:\n  effects: [fxaa]\n").unwrap().remove(0);
+        settings.override_yaml(yaml).unwrap();
+        assert_eq!(changes(&effect), [("graphics.effects[0]".to_string(), "fxaa".into())]);
+        assert_eq!(changes(&audio), []);
+    }
+
+    #[test]
+    fn test_unsubscribe() {
+        let mut settings = settings();
+        let volume = settings.subscribe("audio.volume");
+        drop(settings.subscribe("audio.volume"));
+
+        settings.set("audio.volume", 0.5).unwrap();
+        assert_eq!(settings.subscribers.subscribers.len(), 1);
+        assert_eq!(changes(&volume).len(), 1);
+    }
+}
@@ -0,0 +1,483 @@
+//! Deserialization of values into serde types
+//!
+//! Errors carry the path of the value that failed, such as `graphics.fps_cap`
+//! or `graphics.effects[1]`.
+
+use std::{error, fmt};
+use std::collections::hash_map;
+use std::iter::Enumerate;
+use std::slice;
+use serde::de::{self, Deserialize, Visitor, SeqVisitor, MapVisitor, EnumVisitor, VariantVisitor};
+use serde::de::value::ValueDeserializer;
+
+use {Error, Value};
+
+/// Deserializes `value`, found at `path`.
+pub(crate) fn from_value<T: Deserialize>(value: &Value, path: &str) -> Result<T, Error> {
+    T::deserialize(&mut Deserializer::new(value, path.to_string())).map_err(|e| {
+        Error::Deserialize {
+            path: e.path.unwrap_or_else(|| path.to_string()),
+            message: e.message,
+        }
+    })
+}
+
+#[derive(Debug)]
+pub(crate) struct DeError {
+    /// The innermost value that failed, set when the error leaves its deserializer.
+    path: Option<String>,
+    message: String,
+    /// The visitor could not accept a value, such as a too large integer.
+    invalid_type: bool,
+}
+
+impl DeError {
+    fn at(mut self, path: &str) -> Self {
+        if self.path.is_none() {
+            self.path = Some(path.to_string());
+        }
+        self
+    }
+}
+
+impl fmt::Display for DeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.path {
+            Some(ref path) => write!(f, "{}: {}", path, self.message),
+            None => self.message.fmt(f),
+        }
+    }
+}
+
+impl error::Error for DeError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
+
+impl de::Error for DeError {
+    fn custom<T: Into<String>>(message: T) -> Self {
+        DeError {
+            path: None,
+            message: message.into(),
+            invalid_type: false,
+        }
+    }
+
+    fn end_of_stream() -> Self {
+        de::Error::custom("unexpected end of value")
+    }
+
+    fn invalid_type(ty: de::Type) -> Self {
+        DeError {
+            invalid_type: true,
+            ..de::Error::custom(format!("unexpected {:?}", ty).to_lowercase())
+        }
+    }
+
+    fn invalid_length(len: usize) -> Self {
+        de::Error::custom(format!("unexpected length {}", len))
+    }
+
+    fn unknown_variant(variant: &str) -> Self {
+        de::Error::custom(format!("unknown variant `{}`", variant))
+    }
+
+    fn unknown_field(field: &str) -> Self {
+        de::Error::custom(format!("unknown field `{}`", field))
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        de::Error::custom(format!("missing field `{}`", field))
+    }
+}
+
+pub(crate) fn child_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+struct Deserializer<'a> {
+    value: &'a Value,
+    path: String,
+}
+
+impl<'a> Deserializer<'a> {
+    fn new(value: &'a Value, path: String) -> Self {
+        Deserializer {
+            value: value,
+            path: path,
+        }
+    }
+
+    fn error(&self, message: String) -> DeError {
+        DeError {
+            path: Some(self.path.clone()),
+            message: message,
+            invalid_type: false,
+        }
+    }
+
+    /// Deserializes the value if its kind is the one expected by the visitor.
+    fn deserialize_kind<V, F>(&mut self, expected: &str, is_kind: F, visitor: V)
+                              -> Result<V::Value, DeError>
+        where V: Visitor,
+              F: FnOnce(&Value) -> bool
+    {
+        if is_kind(self.value) {
+            de::Deserializer::deserialize(self, visitor)
+        } else {
+            let message = format!("expected {}, found {}", expected, self.value.kind());
+            Err(self.error(message))
+        }
+    }
+
+    fn deserialize_integer<V: Visitor>(&mut self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_kind("integer", |v| v.as_i64().is_some(), visitor)
+    }
+
+    fn deserialize_real<V: Visitor>(&mut self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_kind("real", |v| v.as_f64().is_some() || v.as_i64().is_some(), visitor)
+    }
+
+    fn deserialize_string_kind<V: Visitor>(&mut self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_kind("string", |v| v.as_str().is_some(), visitor)
+    }
+
+    fn deserialize_array<V: Visitor>(&mut self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_kind("array", |v| v.as_array().is_some(), visitor)
+    }
+
+    fn deserialize_map_kind<V: Visitor>(&mut self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_kind("map", |v| v.as_map().is_some(), visitor)
+    }
+}
+
+macro_rules! deserialize_kinds {
+    ($($method:ident => $kind:ident),*) => {
+        $(
+            fn $method<V: Visitor>(&mut self, visitor: V) -> Result<V::Value, DeError> {
+                self.$kind(visitor)
+            }
+        )*
+    }
+}
+
+impl<'a> de::Deserializer for Deserializer<'a> {
+    type Error = DeError;
+
+    fn deserialize<V: Visitor>(&mut self, mut visitor: V) -> Result<V::Value, DeError> {
+        let result = match self.value {
+            &Value::Real(r) => visitor.visit_f64(r),
+            &Value::Integer(i) => visitor.visit_i64(i),
+            &Value::String(ref s) => visitor.visit_str(s),
+            &Value::Boolean(b) => visitor.visit_bool(b),
+            &Value::Array(ref a) => {
+                visitor.visit_seq(SeqDeserializer {
+                    values: a.0.iter().enumerate(),
+                    path: &self.path,
+                })
+            }
+            &Value::Map(ref m) => {
+                visitor.visit_map(MapDeserializer {
+                    entries: m.0.iter(),
+                    value: None,
+                    path: &self.path,
+                })
+            }
+            &Value::None => visitor.visit_unit(),
+            &Value::NotFound => Err(de::Error::custom("no value")),
+        };
+
+        result.map_err(|e| match self.value {
+            &Value::Integer(i) if e.invalid_type && e.path.is_none() => {
+                self.error(format!("{} is out of range", i))
+            }
+            _ => e.at(&self.path),
+        })
+    }
+
+    deserialize_kinds! {
+        deserialize_usize => deserialize_integer,
+        deserialize_u8 => deserialize_integer,
+        deserialize_u16 => deserialize_integer,
+        deserialize_u32 => deserialize_integer,
+        deserialize_u64 => deserialize_integer,
+        deserialize_isize => deserialize_integer,
+        deserialize_i8 => deserialize_integer,
+        deserialize_i16 => deserialize_integer,
+        deserialize_i32 => deserialize_integer,
+        deserialize_i64 => deserialize_integer,
+        deserialize_f32 => deserialize_real,
+        deserialize_f64 => deserialize_real,
+        deserialize_char => deserialize_string_kind,
+        deserialize_str => deserialize_string_kind,
+        deserialize_string => deserialize_string_kind,
+        deserialize_seq => deserialize_array,
+        deserialize_map => deserialize_map_kind
+    }
+
+    fn deserialize_bool<V: Visitor>(&mut self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_kind("boolean", |v| v.as_bool().is_some(), visitor)
+    }
+
+    fn deserialize_unit<V: Visitor>(&mut self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_kind("null", |v| if let &Value::None = v { true } else { false }, visitor)
+    }
+
+    fn deserialize_option<V: Visitor>(&mut self, mut visitor: V) -> Result<V::Value, DeError> {
+        match self.value {
+            &Value::None | &Value::NotFound => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_seq_fixed_size<V: Visitor>(&mut self, _: usize, visitor: V)
+                                              -> Result<V::Value, DeError> {
+        self.deserialize_array(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor>(&mut self, visitor: V) -> Result<V::Value, DeError> {
+        de::Deserializer::deserialize(self, visitor)
+    }
+
+    fn deserialize_unit_struct<V: Visitor>(&mut self, _: &'static str, visitor: V)
+                                           -> Result<V::Value, DeError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor>(&mut self, _: &'static str, mut visitor: V)
+                                              -> Result<V::Value, DeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor>(&mut self, _: &'static str, _: usize, visitor: V)
+                                            -> Result<V::Value, DeError> {
+        self.deserialize_array(visitor)
+    }
+
+    fn deserialize_struct<V: Visitor>(&mut self,
+                                      _: &'static str,
+                                      _: &'static [&'static str],
+                                      visitor: V)
+                                      -> Result<V::Value, DeError> {
+        self.deserialize_map_kind(visitor)
+    }
+
+    fn deserialize_struct_field<V: Visitor>(&mut self, visitor: V) -> Result<V::Value, DeError> {
+        self.deserialize_string_kind(visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor>(&mut self, _: usize, visitor: V)
+                                     -> Result<V::Value, DeError> {
+        self.deserialize_array(visitor)
+    }
+
+    /// Unit variants are strings, other variants are maps with a single key.
+    fn deserialize_enum<V: EnumVisitor>(&mut self,
+                                        _: &'static str,
+                                        _: &'static [&'static str],
+                                        mut visitor: V)
+                                        -> Result<V::Value, DeError> {
+        let variant = match self.value {
+            &Value::String(ref s) => {
+                VariantDeserializer {
+                    variant: s,
+                    value: None,
+                    path: &self.path,
+                }
+            }
+            &Value::Map(ref m) if m.0.len() == 1 => {
+                let (variant, value) = m.0.iter().next().unwrap();
+                VariantDeserializer {
+                    variant: variant,
+                    value: Some(value),
+                    path: &self.path,
+                }
+            }
+            value => {
+                let message = format!("expected enum variant, found {}", value.kind());
+                return Err(self.error(message));
+            }
+        };
+
+        visitor.visit(variant).map_err(|e| e.at(&self.path))
+    }
+
+    fn deserialize_ignored_any<V: Visitor>(&mut self, mut visitor: V)
+                                           -> Result<V::Value, DeError> {
+        visitor.visit_unit()
+    }
+}
+
+struct SeqDeserializer<'a> {
+    values: Enumerate<slice::Iter<'a, Value>>,
+    path: &'a str,
+}
+
+impl<'a> SeqVisitor for SeqDeserializer<'a> {
+    type Error = DeError;
+
+    fn visit<T: Deserialize>(&mut self) -> Result<Option<T>, DeError> {
+        match self.values.next() {
+            Some((i, value)) => {
+                let path = format!("{}[{}]", self.path, i);
+                T::deserialize(&mut Deserializer::new(value, path)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn end(&mut self) -> Result<(), DeError> {
+        match self.values.len() {
+            0 => Ok(()),
+            _ => Err(de::Error::custom("too many elements")),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.values.size_hint()
+    }
+}
+
+struct MapDeserializer<'a> {
+    entries: hash_map::Iter<'a, String, Value>,
+    /// The value of the last visited key.
+    value: Option<(&'a String, &'a Value)>,
+    path: &'a str,
+}
+
+impl<'a> MapVisitor for MapDeserializer<'a> {
+    type Error = DeError;
+
+    fn visit_key<K: Deserialize>(&mut self) -> Result<Option<K>, DeError> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some((key, value));
+                K::deserialize(&mut key.as_str().into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn visit_value<V: Deserialize>(&mut self) -> Result<V, DeError> {
+        match self.value.take() {
+            Some((key, value)) => {
+                V::deserialize(&mut Deserializer::new(value, child_path(self.path, key)))
+            }
+            None => Err(de::Error::custom("value visited before its key")),
+        }
+    }
+
+    fn end(&mut self) -> Result<(), DeError> {
+        Ok(())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
+struct VariantDeserializer<'a> {
+    variant: &'a str,
+    value: Option<&'a Value>,
+    path: &'a str,
+}
+
+impl<'a> VariantDeserializer<'a> {
+    fn deserializer(&self) -> Result<Deserializer<'a>, DeError> {
+        match self.value {
+            Some(value) => Ok(Deserializer::new(value, child_path(self.path, self.variant))),
+            None => Err(de::Error::custom(format!("variant `{}` has no value", self.variant))),
+        }
+    }
+}
+
+impl<'a> VariantVisitor for VariantDeserializer<'a> {
+    type Error = DeError;
+
+    fn visit_variant<V: Deserialize>(&mut self) -> Result<V, DeError> {
+        V::deserialize(&mut self.variant.into_deserializer())
+    }
+
+    fn visit_unit(&mut self) -> Result<(), DeError> {
+        match self.value {
+            None | Some(&Value::None) => Ok(()),
+            Some(_) => Err(de::Error::custom(format!("variant `{}` takes no value", self.variant))),
+        }
+    }
+
+    fn visit_newtype<T: Deserialize>(&mut self) -> Result<T, DeError> {
+        T::deserialize(&mut try!(self.deserializer()))
+    }
+
+    fn visit_tuple<V: Visitor>(&mut self, _: usize, visitor: V) -> Result<V::Value, DeError> {
+        try!(self.deserializer()).deserialize_array(visitor)
+    }
+
+    fn visit_struct<V: Visitor>(&mut self, _: &'static [&'static str], visitor: V)
+                                -> Result<V::Value, DeError> {
+        try!(self.deserializer()).deserialize_map_kind(visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use yaml_rust::YamlLoader;
+    use {Error, Settings};
+
+    fn settings() -> Settings {
+        let source = "graphics:\n  fps_cap: 60\n  scale: 1\n  vsync: ~\n  \
+                      effects: [bloom, fxaa]\n  window: [800, 600]\n  \
+                      limits: {textures: 64, lights: 8}\n";
+        Settings::from_yaml(YamlLoader::load_from_str(source).unwrap().remove(0)).unwrap()
+    }
+
+    fn error(path: &str, message: &str) -> Error {
+        Error::Deserialize {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_get() {
+        let settings = settings();
+        assert_eq!(settings.get::<u32>("graphics.fps_cap"), Ok(60));
+        assert_eq!(settings.get::<f32>("graphics.scale"), Ok(1.));
+        assert_eq!(settings.get::<Option<bool>>("graphics.vsync"), Ok(None));
+        assert_eq!(settings.get::<Option<bool>>("graphics.missing"), Ok(None));
+        assert_eq!(settings.get::<Vec<String>>("graphics.effects"),
+                   Ok(vec!["bloom".to_string(), "fxaa".to_string()]));
+        assert_eq!(settings.get::<(u32, u32)>("graphics.window"), Ok((800, 600)));
+
+        let limits: HashMap<String, u8> = settings.get("graphics.limits").unwrap();
+        assert_eq!(limits.len(), 2);
+        assert_eq!(limits["textures"], 64);
+    }
+
+    #[test]
+    fn test_error_path() {
+        let settings = settings();
+        assert_eq!(settings.get::<String>("graphics.fps_cap"),
+                   Err(error("graphics.fps_cap", "expected string, found integer")));
+        assert_eq!(settings.get::<u32>("graphics.missing"),
+                   Err(error("graphics.missing", "expected integer, found no value")));
+        assert_eq!(settings.get::<Vec<u32>>("graphics.effects"),
+                   Err(error("graphics.effects[0]", "expected integer, found string")));
+        match settings.get::<HashMap<String, bool>>("graphics.limits") {
+            Err(Error::Deserialize { path, message }) => {
+                assert!(path == "graphics.limits.textures" || path == "graphics.limits.lights");
+                assert_eq!(message, "expected boolean, found integer");
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(settings.get::<u8>("graphics.window[0]"),
+                   Err(error("graphics.window[0]", "800 is out of range")));
+        assert!(settings.get::<(u32, u32, u32)>("graphics.window").is_err());
+    }
+}
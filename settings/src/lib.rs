@@ -1,61 +1,362 @@
 #![feature(pub_restricted)]
 
+extern crate serde;
 extern crate yaml_rust;
 
+mod builder;
+mod change;
+mod de;
+mod load;
+mod schema;
 mod value;
+pub use self::builder::*;
+pub use self::change::Change;
+pub use self::schema::*;
 pub use self::value::*;
 
-use std::{ops, fmt};
-use std::path::Path;
+use std::{error, io, ops, fmt};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
 use yaml_rust::Yaml;
+use yaml_rust::yaml::Hash;
+
+use change::Subscribers;
+use load::Lines;
+use value::Segment;
 
 pub struct Settings {
     values: ValueMap,
+    layers: Vec<Layer>,
+    schema: Option<Schema>,
+    subscribers: Subscribers,
+}
+
+/// The defaults or an override, kept to be applied again on reload.
+#[derive(Clone)]
+struct Layer {
+    origin: Origin,
+    yaml: Yaml,
+    lines: Lines,
+}
+
+impl Layer {
+    fn new(origin: Origin, yaml: Yaml) -> Self {
+        Layer {
+            origin: origin,
+            yaml: yaml,
+            lines: Lines::default(),
+        }
+    }
+
+    fn read(path: &Path) -> Result<Self, Error> {
+        let (yaml, lines) = try!(load::read(path));
+        Ok(Layer {
+            origin: Origin::File(path.to_path_buf()),
+            yaml: yaml,
+            lines: lines,
+        })
+    }
+
+    /// Adds the file and line of the value to an error caused by this layer.
+    fn locate(&self, error: Error) -> Error {
+        match self.origin {
+            Origin::File(ref path) => error.in_file(path, &self.lines),
+            _ => error,
+        }
+    }
 }
 
 impl Settings {
     pub fn new<P: AsRef<Path>>(defaults_path: P) -> Result<Self, Error> {
-        Self::from_yaml(Self::read_yaml(defaults_path.as_ref()))
+        let layer = try!(Layer::read(defaults_path.as_ref()));
+        let values = try!(Self::values_from_yaml(layer.yaml.clone()).map_err(|e| layer.locate(e)));
+
+        Ok(Self::with_defaults(values, layer))
     }
 
     pub fn override_with<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
-        self.override_yaml(Self::read_yaml(path.as_ref()))
+        let layer = try!(Layer::read(path.as_ref()));
+        self.override_layer(layer)
+    }
+
+    pub fn from_yaml(yaml: Yaml) -> Result<Self, Error> {
+        let values = try!(Self::values_from_yaml(yaml.clone()));
+        Ok(Self::with_defaults(values, Layer::new(Origin::Yaml, yaml)))
+    }
+
+    fn with_defaults(values: ValueMap, layer: Layer) -> Self {
+        Settings {
+            values: values,
+            layers: vec![layer],
+            schema: None,
+            subscribers: Subscribers::new(),
+        }
+    }
+
+    /// Applies an override, the values are left untouched if it is not valid.
+    pub fn override_yaml(&mut self, yaml: Yaml) -> Result<(), Error> {
+        self.override_layer(Layer::new(Origin::Yaml, yaml))
+    }
+
+    fn override_layer(&mut self, layer: Layer) -> Result<(), Error> {
+        let mut values = self.values.clone();
+        try!(Self::override_values(&mut values, layer.yaml.clone()).map_err(|e| layer.locate(e)));
+
+        // The runtime changes stay on top of the other layers.
+        let index = match self.runtime_layer() {
+            Some(runtime) => {
+                try!(Self::override_values(&mut values, runtime.yaml.clone()));
+                self.layers.len() - 1
+            }
+            None => self.layers.len(),
+        };
+        {
+            let mut layers: Vec<_> = self.layers.iter().collect();
+            layers.insert(index, &layer);
+            try!(self.validate(&values, &layers));
+        }
+
+        self.update(values);
+        self.layers.insert(index, layer);
+
+        Ok(())
+    }
+
+    /// Changes the value at `key`, such as `audio.volume`.
+    ///
+    /// The key is a path of map keys, array items cannot be set on their own, an index such as
+    /// `graphics.effects[1]` is rejected with `Error::InvalidPath`.
+    ///
+    /// The changes are kept over reloads and applied after the overrides, they are saved by
+    /// `save_overrides`.
+    pub fn set<V: Into<Value>>(&mut self, key: &str, value: V) -> Result<(), Error> {
+        let keys = match value::parse_path(key) {
+            Some(segments) => {
+                let keys = segments.into_iter().map(|segment| match segment {
+                    Segment::Key(key) => Some(key.to_string()),
+                    Segment::Index(_) => None,
+                });
+                try!(keys.collect::<Option<Vec<_>>>().ok_or(Error::InvalidPath(key.to_string())))
+            }
+            None => return Err(Error::InvalidPath(key.to_string())),
+        };
+        let yaml = builder::nested_yaml(&keys, value.into().to_yaml());
+
+        let mut values = self.values.clone();
+        try!(Self::override_values(&mut values, yaml.clone()));
+        try!(self.validate(&values, &[]));
+
+        if self.runtime_layer().is_none() {
+            self.layers.push(Layer::new(Origin::Runtime, Yaml::Hash(Hash::new())));
+        }
+        merge_yaml(&mut self.layers.last_mut().unwrap().yaml, yaml);
+
+        self.update(values);
+        Ok(())
     }
 
-    fn read_yaml(path: &Path) -> Yaml {
+    /// Sends a `Change` when the value at `key`, or one of its values, is changed by `set`,
+    /// an override or a reload.
+    ///
+    /// Dropping the receiver cancels the subscription.
+    pub fn subscribe(&mut self, key: &str) -> Receiver<Change> {
+        self.subscribers.subscribe(key)
+    }
+
+    /// Returns the values of the override files and the runtime changes that differ from the
+    /// defaults.
+    ///
+    /// The environment variables and the arguments only apply to this run, they are left out.
+    pub fn overrides(&self) -> Result<ValueMap, Error> {
+        let (defaults, overrides) = self.layers.split_first().unwrap();
+        let defaults = try!(Self::values_from_yaml(defaults.yaml.clone()));
+
+        let mut values = defaults.clone();
+        for layer in overrides {
+            match layer.origin {
+                Origin::Environment(_) | Origin::Argument(_) => {}
+                _ => try!(Self::override_values(&mut values, layer.yaml.clone())),
+            }
+        }
+
+        Ok(values.diff(&defaults))
+    }
+
+    /// Writes the overrides, to be loaded with `override_with`.
+    pub fn save_overrides<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
         use std::fs::File;
         use std::io::prelude::*;
-        use yaml_rust::YamlLoader;
 
-        let mut f = File::open(path).unwrap();
-        let mut s = String::new();
-        f.read_to_string(&mut s).unwrap();
+        let overrides = try!(self.overrides()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string())));
+        let mut f = try!(File::create(path));
+        f.write_all(overrides.render().as_bytes())
+    }
 
-        let docs = YamlLoader::load_from_str(&s).unwrap();
-        docs.into_iter().next().unwrap_or(Yaml::Null)
+    /// Returns the runtime changes, always the last layer.
+    fn runtime_layer(&self) -> Option<&Layer> {
+        self.layers.last().and_then(|layer| match layer.origin {
+            Origin::Runtime => Some(layer),
+            _ => None,
+        })
     }
 
-    pub fn from_yaml(yaml: Yaml) -> Result<Self, Error> {
+    fn update(&mut self, values: ValueMap) {
+        self.subscribers.notify(&self.values, &values);
+        self.values = values;
+    }
+
+    /// Checks the current values and every following override or reload against `schema`.
+    ///
+    /// The schema is not set if the current values or its defaults do not match it.
+    pub fn set_schema(&mut self, schema: Schema) -> Result<(), Error> {
+        try!(schema.check_defaults());
+        {
+            let layers: Vec<_> = self.layers.iter().collect();
+            try!(Self::validate_with(&schema, &self.values, &layers));
+        }
+        self.schema = Some(schema);
+
+        Ok(())
+    }
+
+    pub fn schema(&self) -> Option<&Schema> {
+        self.schema.as_ref()
+    }
+
+    /// Checks `values` against the schema, `layers` are the layers that made them.
+    fn validate(&self, values: &ValueMap, layers: &[&Layer]) -> Result<(), Error> {
+        match self.schema {
+            Some(ref schema) => Self::validate_with(schema, values, layers),
+            None => Ok(()),
+        }
+    }
+
+    fn validate_with(schema: &Schema, values: &ValueMap, layers: &[&Layer]) -> Result<(), Error> {
+        schema.validate(values).map_err(|errors| {
+            let errors = errors.into_iter().map(|error| Self::locate_schema_error(error, layers));
+            Error::Schema(errors.collect())
+        })
+    }
+
+    /// Adds the file and line of the value to an error, if it was last set by a file.
+    fn locate_schema_error(mut error: SchemaError, layers: &[&Layer]) -> SchemaError {
+        let location = {
+            let segments = value::parse_path(&error.path).unwrap_or(Vec::new());
+            let layer = layers.iter().rev().find(|layer| Self::sets(&layer.yaml, &segments));
+
+            layer.and_then(|layer| match layer.origin {
+                Origin::File(ref path) => Some((path.clone(), layer.lines.line(&error.path))),
+                _ => None,
+            })
+        };
+
+        error.location = location;
+        error
+    }
+
+    /// Returns the defaults and override files, in the order they were applied.
+    ///
+    /// Watch them to call `reload` when they change.
+    pub fn files(&self) -> Vec<&Path> {
+        self.layers.iter()
+            .filter_map(|layer| match layer.origin {
+                Origin::File(ref path) => Some(path.as_path()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the layer that set the value at the path `key`, such as `graphics.fps_cap`.
+    ///
+    /// A map is set by the last layer that changed one of its values, an array by the last
+    /// layer that replaced it.
+    pub fn origin(&self, key: &str) -> Option<&Origin> {
+        let segments = match value::parse_path(key) {
+            Some(segments) => segments,
+            None => return None,
+        };
+
+        self.layers.iter()
+            .rev()
+            .find(|layer| Self::sets(&layer.yaml, &segments))
+            .map(|layer| &layer.origin)
+    }
+
+    /// Returns true if `yaml` sets the value at `segments`, or one of its parents.
+    fn sets(yaml: &Yaml, segments: &[Segment]) -> bool {
+        match (yaml, segments.split_first()) {
+            (&Yaml::Hash(ref h), Some((&Segment::Key(key), rest))) => {
+                h.get(&Yaml::String(key.to_string())).map_or(false, |yaml| Self::sets(yaml, rest))
+            }
+            (&Yaml::Hash(_), Some(_)) |
+            (&Yaml::Null, Some(_)) => false,
+            _ => true,
+        }
+    }
+
+    /// Reads the files again and applies every override on top of the new defaults.
+    ///
+    /// The values are left untouched if the files are no longer valid.
+    pub fn reload(&mut self) -> Result<(), Error> {
+        let mut layers = Vec::with_capacity(self.layers.len());
+        for layer in &self.layers {
+            layers.push(match layer.origin {
+                Origin::File(ref path) => try!(Layer::read(path)),
+                _ => layer.clone(),
+            });
+        }
+
+        let values = {
+            let (defaults, overrides) = layers.split_first().unwrap();
+            let mut values = try!(Self::values_from_yaml(defaults.yaml.clone())
+                .map_err(|e| defaults.locate(e)));
+            for layer in overrides {
+                try!(Self::override_values(&mut values, layer.yaml.clone())
+                    .map_err(|e| layer.locate(e)));
+            }
+            values
+        };
+        try!(self.validate(&values, &layers.iter().collect::<Vec<_>>()));
+
+        self.layers = layers;
+        self.update(values);
+        Ok(())
+    }
+
+    fn values_from_yaml(yaml: Yaml) -> Result<ValueMap, Error> {
         match yaml {
-            Yaml::Hash(h) => Ok(Settings {
-                values: try!(ValueMap::from(h)),
-            }),
-            Yaml::Null => Ok(Settings {
-                values: ValueMap::empty(),
-            }),
+            Yaml::Hash(h) => ValueMap::from(h, ""),
+            Yaml::Null => Ok(ValueMap::empty()),
             _ => Err(Error::InvalidRoot),
         }
     }
 
-    pub fn override_yaml(&mut self, yaml: Yaml) -> Result<(), Error> {
+    fn override_values(values: &mut ValueMap, yaml: Yaml) -> Result<(), Error> {
         match yaml {
-            Yaml::Hash(h) => self.values.override_with(h),
+            Yaml::Hash(h) => values.override_with(h, ""),
             Yaml::Null => Ok(()),
             _ => Err(Error::InvalidRoot),
         }
     }
 }
 
+/// Merges `yaml` into `target`, the values of `yaml` replace the ones of `target`.
+fn merge_yaml(target: &mut Yaml, yaml: Yaml) {
+    match (target, yaml) {
+        (&mut Yaml::Hash(ref mut target), Yaml::Hash(h)) => {
+            for (k, v) in h {
+                if target.contains_key(&k) {
+                    merge_yaml(target.get_mut(&k).unwrap(), v);
+                } else {
+                    target.insert(k, v);
+                }
+            }
+        }
+        (target, yaml) => *target = yaml,
+    }
+}
+
 impl fmt::Debug for Settings {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         self.values.fmt(f)
@@ -71,8 +372,220 @@ impl ops::Deref for Settings {
 pub enum Error {
     /// The root must be an hash or null
     InvalidRoot,
-    /// Keys must be strings
-    InvalidKey,
-    /// Trying to override a non-existent value
-    InvalidOverride,
+    /// Keys must be strings, in the map at this path
+    InvalidKey(String),
+    /// Trying to override a non-existent value, at this path
+    InvalidOverride(String),
+    /// A key given to `set` that is not a path of map keys, such as `graphics.effects[1]`
+    InvalidPath(String),
+    /// A real or a tagged value such as `!!int` that could not be parsed, at this path
+    InvalidValue(String),
+    /// The merge key `<<` must be a map or an array of maps, in the map at this path
+    InvalidMerge(String),
+    /// An alias that was not replaced by its anchored value, at this path
+    UnresolvedAlias(String),
+    /// A file could not be read
+    Io {
+        path: PathBuf,
+        message: String,
+    },
+    /// A file is not valid YAML
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// An error caused by a file, at the line of the value
+    File {
+        path: PathBuf,
+        line: usize,
+        error: Box<Error>,
+    },
+    /// A `--set` argument is not of the form `key=value`
+    InvalidArgument(String),
+    /// The schema definition is not valid
+    InvalidSchema(SchemaError),
+    /// The values that do not match the schema
+    Schema(Vec<SchemaError>),
+    /// The value at `path` does not match the requested type
+    Deserialize {
+        path: String,
+        message: String,
+    },
+}
+
+impl Error {
+    /// Returns the path of the value that caused the error.
+    fn key(&self) -> Option<&str> {
+        match self {
+            &Error::InvalidRoot => Some(""),
+            &Error::InvalidKey(ref key) |
+            &Error::InvalidOverride(ref key) |
+            &Error::InvalidPath(ref key) |
+            &Error::InvalidValue(ref key) |
+            &Error::InvalidMerge(ref key) |
+            &Error::UnresolvedAlias(ref key) => Some(key),
+            &Error::InvalidSchema(ref error) => Some(&error.path),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn in_file(self, path: &Path, lines: &Lines) -> Self {
+        match self.key().map(|key| lines.line(key)) {
+            Some(line) => {
+                Error::File {
+                    path: path.to_path_buf(),
+                    line: line,
+                    error: Box::new(self),
+                }
+            }
+            None => self,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &Error::Io { ref path, ref message } => {
+                write!(f, "could not read `{}`: {}", path.display(), message)
+            }
+            &Error::Parse { ref path, line, column, ref message } => {
+                write!(f, "{}:{}:{}: {}", path.display(), line, column, message)
+            }
+            &Error::File { ref path, line, ref error } => {
+                write!(f, "{}:{}: {}", path.display(), line, error)
+            }
+            &Error::InvalidArgument(ref arg) => write!(f, "invalid argument `{}`", arg),
+            &Error::InvalidSchema(ref error) => write!(f, "invalid schema, {}", error),
+            &Error::Schema(ref errors) => {
+                let errors: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
+                errors.join("\n").fmt(f)
+            }
+            &Error::Deserialize { ref path, ref message } => write!(f, "{}: {}", path, message),
+            _ => {
+                let description = error::Error::description(self);
+                match self.key() {
+                    Some(key) if !key.is_empty() => write!(f, "{} at `{}`", description, key),
+                    _ => description.fmt(f),
+                }
+            }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match self {
+            &Error::InvalidRoot => "the root must be a hash or null",
+            &Error::InvalidKey(_) => "keys must be strings",
+            &Error::InvalidOverride(_) => "trying to override a non-existent value",
+            &Error::InvalidPath(_) => "only map keys can be set",
+            &Error::InvalidValue(_) => "invalid value",
+            &Error::InvalidMerge(_) => "the merge key `<<` must be a map or an array of maps",
+            &Error::UnresolvedAlias(_) => "unresolved alias",
+            &Error::Io { .. } => "a file could not be read",
+            &Error::Parse { .. } => "invalid yaml",
+            &Error::File { ref error, .. } => error.description(),
+            &Error::InvalidArgument(_) => "a `--set` argument is not of the form `key=value`",
+            &Error::InvalidSchema(_) => "the schema is not valid",
+            &Error::Schema(_) => "values do not match the schema",
+            &Error::Deserialize { .. } => "the value does not match the requested type",
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match self {
+            &Error::File { ref error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+    use yaml_rust::YamlLoader;
+    use super::*;
+
+    fn write(path: &Path, source: &str) {
+        File::create(path).and_then(|mut f| f.write_all(source.as_bytes())).unwrap();
+    }
+
+    fn yaml(source: &str) -> Yaml {
+        YamlLoader::load_from_str(source).unwrap().remove(0)
+    }
+
+    fn settings(source: &str) -> Settings {
+        Settings::from_yaml(yaml(source)).unwrap()
+    }
+
+    #[test]
+    fn test_set() {
+        let mut settings = settings("audio:\n  volume: 0.5\ngraphics:\n  effects: [bloom, fxaa]\n");
+        settings.set("audio.volume", 0.25).unwrap();
+        assert_eq!(settings.get::<f64>("audio.volume"), Ok(0.25));
+
+        let paths = ["graphics.effects[1]", "graphics..effects", "", "[0]"];
+        for path in &paths {
+            assert_eq!(settings.set(path, "smaa"), Err(Error::InvalidPath(path.to_string())));
+        }
+        assert_eq!(settings.get::<Vec<String>>("graphics.effects"),
+                   Ok(vec!["bloom".to_string(), "fxaa".to_string()]));
+    }
+
+    #[test]
+    fn test_save_overrides() {
+        let defaults = env::temp_dir().join("settings-save-defaults.yml");
+        let saved = env::temp_dir().join("settings-save-overrides.yml");
+        write(&defaults,
+              "player:\n  name: Player\n  \"key: bindings\":\n    save: ctrl+s\n    \
+               quit: ctrl+q\naudio:\n  volume: 1.0\n  muted: false\n");
+
+        let mut settings = SettingsBuilder::new(&defaults)
+            .args(vec!["--set", "audio.muted=true"])
+            .build()
+            .unwrap();
+        let name = "a: \"b\" #c\n\\ 'd'";
+        settings.set("player.name", name).unwrap();
+        settings.set("player.key: bindings.save", "ctrl+shift+s").unwrap();
+        settings.set("audio.volume", 1.0).unwrap();
+        settings.save_overrides(&saved).unwrap();
+
+        let mut source = String::new();
+        File::open(&saved).and_then(|mut f| f.read_to_string(&mut source)).unwrap();
+        assert!(!source.contains("audio"), "{}", source);
+        assert!(!source.contains("quit"), "{}", source);
+
+        let mut loaded = Settings::new(&defaults).unwrap();
+        loaded.override_with(&saved).unwrap();
+        fs::remove_file(&defaults).unwrap();
+        fs::remove_file(&saved).unwrap();
+
+        assert_eq!(loaded.get::<String>("player.name"), Ok(name.to_string()));
+        assert_eq!(loaded.overrides(), settings.overrides());
+        assert_eq!(loaded.get::<bool>("audio.muted"), Ok(false));
+    }
+
+    #[test]
+    fn test_reload() {
+        let defaults = env::temp_dir().join("settings-reload-defaults.yml");
+        write(&defaults, "audio:\n  volume: 1.0\n  muted: false\n");
+        let mut settings = Settings::new(&defaults).unwrap();
+        settings.set("audio.muted", true).unwrap();
+        let volume = settings.subscribe("audio.volume");
+
+        write(&defaults, "audio:\n  volume: 0.5\n  muted: false\n");
+        settings.reload().unwrap();
+        assert_eq!(volume.try_recv().map(|change| change.value), Ok(Value::Real(0.5)));
+        assert_eq!(settings.get::<bool>("audio.muted"), Ok(true));
+
+        write(&defaults, "audio:\n  volume: [\n");
+        assert!(settings.reload().is_err());
+        fs::remove_file(&defaults).unwrap();
+        assert_eq!(settings.get::<f64>("audio.volume"), Ok(0.5));
+    }
 }
@@ -0,0 +1,272 @@
+//! Reading of YAML files, with the line of each value
+
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use yaml_rust::{Event, Yaml, YamlLoader};
+use yaml_rust::parser::{MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+use {de, Error};
+
+/// The line of each value of a file, by key path.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Lines(HashMap<String, usize>);
+
+impl Lines {
+    /// Returns the line of the value at `key`, or of its closest parent in the file.
+    pub(crate) fn line(&self, key: &str) -> usize {
+        let mut key = key;
+        loop {
+            if let Some(&line) = self.0.get(key) {
+                return line;
+            }
+
+            match key.rfind(|c| c == '.' || c == '[') {
+                Some(i) => key = &key[..i],
+                None => return 1,
+            }
+        }
+    }
+}
+
+/// Reads a file of a single document, aliases are replaced by their anchored values.
+pub(crate) fn read(path: &Path) -> Result<(Yaml, Lines), Error> {
+    let io_error = |e: ::std::io::Error| {
+        Error::Io {
+            path: path.to_path_buf(),
+            message: e.to_string(),
+        }
+    };
+
+    let mut source = String::new();
+    try!(File::open(path).and_then(|mut f| f.read_to_string(&mut source)).map_err(io_error));
+
+    let docs = try!(YamlLoader::load_from_str(&source).map_err(|e| {
+        Error::Parse {
+            path: path.to_path_buf(),
+            line: e.marker().line(),
+            column: e.marker().col() + 1,
+            message: e.description().to_string(),
+        }
+    }));
+
+    let mut collector = LineCollector {
+        frames: Vec::new(),
+        lines: HashMap::new(),
+        documents: Vec::new(),
+    };
+    // The source was already parsed without errors.
+    let _ = Parser::new(source.chars()).load(&mut collector, true);
+
+    // The lines of the other documents would be mixed with the first one.
+    if let Some(mark) = collector.documents.get(1) {
+        return Err(Error::Parse {
+            path: path.to_path_buf(),
+            line: mark.line(),
+            column: mark.col() + 1,
+            message: "a file must contain a single document".to_string(),
+        });
+    }
+
+    let yaml = match docs.into_iter().next() {
+        Some(Yaml::BadValue) | None => Yaml::Null,
+        Some(yaml) => yaml,
+    };
+    Ok((yaml, Lines(collector.lines)))
+}
+
+struct Frame {
+    path: String,
+    kind: FrameKind,
+}
+
+enum FrameKind {
+    /// The last key, and whether the next node is its value.
+    Map { key: Option<String>, value: bool },
+    /// The index of the next item.
+    Sequence(usize),
+}
+
+struct LineCollector {
+    frames: Vec<Frame>,
+    lines: HashMap<String, usize>,
+    /// The start of each document.
+    documents: Vec<Marker>,
+}
+
+impl LineCollector {
+    /// Returns the path of a node starting at `mark`, `scalar` is its value if it is a scalar.
+    fn start_node(&mut self, scalar: Option<&str>, mark: Marker) -> String {
+        let frame = match self.frames.last_mut() {
+            Some(frame) => frame,
+            None => return String::new(),
+        };
+
+        match frame.kind {
+            FrameKind::Sequence(ref mut index) => {
+                let path = format!("{}[{}]", frame.path, index);
+                *index += 1;
+                self.lines.insert(path.clone(), mark.line());
+                path
+            }
+            FrameKind::Map { ref mut key, value: false } => {
+                *key = scalar.map(str::to_string);
+                let path = de::child_path(&frame.path, scalar.unwrap_or("?"));
+                self.lines.insert(path.clone(), mark.line());
+                path
+            }
+            FrameKind::Map { ref key, value: true } => {
+                de::child_path(&frame.path, key.as_ref().map_or("?", |key| &key[..]))
+            }
+        }
+    }
+
+    /// Alternates between the keys and values of the parent map.
+    fn end_node(&mut self) {
+        if let Some(&mut Frame { kind: FrameKind::Map { ref mut value, .. }, .. }) =
+               self.frames.last_mut() {
+            *value = !*value;
+        }
+    }
+}
+
+impl MarkedEventReceiver for LineCollector {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(ref s, ..) => {
+                self.start_node(Some(s), mark);
+                self.end_node();
+            }
+            Event::Alias(_) => {
+                self.start_node(None, mark);
+                self.end_node();
+            }
+            Event::MappingStart(_) => {
+                let path = self.start_node(None, mark);
+                self.frames.push(Frame {
+                    path: path,
+                    kind: FrameKind::Map { key: None, value: false },
+                });
+            }
+            Event::SequenceStart(_) => {
+                let path = self.start_node(None, mark);
+                self.frames.push(Frame {
+                    path: path,
+                    kind: FrameKind::Sequence(0),
+                });
+            }
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.frames.pop();
+                self.end_node();
+            }
+            Event::DocumentStart => self.documents.push(mark),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+    use {Error, Settings};
+    use super::*;
+
+    fn file(name: &str, source: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("settings-load-{}.yml", name));
+        File::create(&path).and_then(|mut f| f.write_all(source.as_bytes())).unwrap();
+        path
+    }
+
+    fn settings(name: &str, source: &str) -> Result<Settings, Error> {
+        let path = file(name, source);
+        let result = Settings::new(&path);
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    fn file_error(name: &str, source: &str) -> (usize, Error) {
+        match settings(name, source) {
+            Err(Error::File { path, line, error }) => {
+                assert_eq!(path, env::temp_dir().join(format!("settings-load-{}.yml", name)));
+                (line, *error)
+            }
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+    }
+
+    fn read_source(name: &str, source: &str) -> Result<(Yaml, Lines), Error> {
+        let path = file(name, source);
+        let result = read(&path);
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    #[test]
+    fn test_lines() {
+        let (yaml, lines) = read_source("lines", "a:\n  b: 1\n  c: [2, 3]\n").unwrap();
+        assert_eq!(yaml["a"]["b"].as_i64(), Some(1));
+        assert_eq!(lines.line("a.b"), 2);
+        assert_eq!(lines.line("a.c[1]"), 3);
+        assert_eq!(lines.line("a.d"), 1);
+    }
+
+    #[test]
+    fn test_multiple_documents() {
+        match read_source("documents", "a: 1\n---\nb: 2\n") {
+            Err(Error::Parse { line, .. }) => assert_eq!(line, 2),
+            result => panic!("unexpected result {:?}", result),
+        }
+
+        let (yaml, _) = read_source("document", "---\na: 1\n...\n").unwrap();
+        assert_eq!(yaml["a"].as_i64(), Some(1));
+    }
+
+    #[test]
+    fn test_aliases() {
+        let source = "
+base: &base
+  volume: 1.0
+  muted: false
+music: *base
+sfx:
+  <<: *base
+  volume: 0.5
+voice:
+  <<: [{volume: 0.8}, *base]
+";
+        let settings = settings("aliases", source).unwrap();
+        assert_eq!(settings.get::<f64>("music.volume"), Ok(1.));
+        assert_eq!(settings.get::<f64>("sfx.volume"), Ok(0.5));
+        assert_eq!(settings.get::<bool>("sfx.muted"), Ok(false));
+        assert_eq!(settings.get::<f64>("voice.volume"), Ok(0.8));
+        assert!(!settings.path("sfx.<<").is_valid());
+    }
+
+    #[test]
+    fn test_file_errors() {
+        assert_eq!(file_error("float", "audio:\n  muted: false\n  volume: !!float loud\n"),
+                   (3, Error::InvalidValue("audio.volume".to_string())));
+        assert_eq!(file_error("merge", "audio:\n  volume: 1.0\nsfx:\n  <<: 1\n"),
+                   (3, Error::InvalidMerge("sfx".to_string())));
+        assert_eq!(file_error("merge-items", "sfx:\n  muted: true\n  <<: [{a: 1}, 2]\n"),
+                   (1, Error::InvalidMerge("sfx".to_string())));
+        assert_eq!(file_error("key", "audio:\n  volume: 1.0\n  [1]: 2\n"),
+                   (1, Error::InvalidKey("audio".to_string())));
+        assert_eq!(file_error("root", "- 1\n"), (1, Error::InvalidRoot));
+
+        match settings("alias", "audio:\n  volume: *volume\n") {
+            Err(Error::Parse { line, .. }) => assert_eq!(line, 2),
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+        match Settings::new(env::temp_dir().join("settings-load-missing.yml")) {
+            Err(Error::Io { .. }) => {}
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+    }
+}
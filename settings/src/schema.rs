@@ -0,0 +1,621 @@
+//! Types, ranges and documentation of settings
+//!
+//! A schema is declared in Rust:
+//!
+//! ```ignore
+//! let schema = Schema::map()
+//!     .field("graphics", Schema::map()
+//!         .field("fps_cap", Schema::integer_in(1, 300).nullable().default(60)
+//!             .doc("Frame rate limit, null for none")));
+//! ```
+//!
+//! or in YAML, where a node without a `type` string is a map of fields:
+//!
+//! ```yaml
+//! graphics:
+//!   fps_cap:
+//!     type: integer
+//!     min: 1
+//!     max: 300
+//!     nullable: true
+//!     default: 60
+//!     doc: Frame rate limit, null for none
+//! ```
+//!
+//! The other types are `any`, `boolean`, `real`, `string`, `enum` with its
+//! `values`, `array` with the schema of its `items`, and `map` with its `fields`.
+//!
+//! `type` is reserved: a map with a string field named `type` must be declared
+//! with `type: map` and its `fields`.
+//!
+//! Defaults must match their schema. The values without a default are rendered
+//! as null if they are nullable, and commented out otherwise.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use yaml_rust::Yaml;
+use yaml_rust::yaml::Hash;
+
+use {de, load, Error, Value, ValueMap};
+use value::render_key;
+
+#[derive(Clone, Debug)]
+pub struct Schema {
+    kind: Kind,
+    doc: Option<String>,
+    default: Option<Value>,
+    nullable: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Kind {
+    Any,
+    Boolean,
+    Integer { min: Option<i64>, max: Option<i64> },
+    Real { min: Option<f64>, max: Option<f64> },
+    String,
+    Enum(Vec<String>),
+    Array(Box<Schema>),
+    /// The fields, in the order they are rendered.
+    Map(Vec<(String, Schema)>),
+}
+
+/// A value that does not match its schema.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SchemaError {
+    pub path: String,
+    pub message: String,
+    /// The file and line of the value, if it was set by a file.
+    pub location: Option<(PathBuf, usize)>,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((ref file, line)) = self.location {
+            try!(write!(f, "{}:{}: ", file.display(), line));
+        }
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl Schema {
+    fn new(kind: Kind) -> Self {
+        Schema {
+            kind: kind,
+            doc: None,
+            default: None,
+            nullable: false,
+        }
+    }
+
+    pub fn any() -> Self {
+        Self::new(Kind::Any)
+    }
+
+    pub fn boolean() -> Self {
+        Self::new(Kind::Boolean)
+    }
+
+    pub fn integer() -> Self {
+        Self::new(Kind::Integer { min: None, max: None })
+    }
+
+    pub fn integer_in(min: i64, max: i64) -> Self {
+        Self::new(Kind::Integer { min: Some(min), max: Some(max) })
+    }
+
+    /// Integers are accepted as reals.
+    pub fn real() -> Self {
+        Self::new(Kind::Real { min: None, max: None })
+    }
+
+    pub fn real_in(min: f64, max: f64) -> Self {
+        Self::new(Kind::Real { min: Some(min), max: Some(max) })
+    }
+
+    pub fn string() -> Self {
+        Self::new(Kind::String)
+    }
+
+    /// A string among `values`.
+    pub fn one_of(values: &[&str]) -> Self {
+        Self::new(Kind::Enum(values.iter().map(|v| v.to_string()).collect()))
+    }
+
+    pub fn array(items: Schema) -> Self {
+        Self::new(Kind::Array(Box::new(items)))
+    }
+
+    /// A map without fields, add them with `field`.
+    pub fn map() -> Self {
+        Self::new(Kind::Map(Vec::new()))
+    }
+
+    /// Adds a field to a map, replacing the previous one with the same name.
+    ///
+    /// # Panics
+    /// If the schema is not a map.
+    pub fn field<S: Into<String>>(mut self, name: S, schema: Schema) -> Self {
+        let name = name.into();
+        match self.kind {
+            Kind::Map(ref mut fields) => {
+                match fields.iter().position(|&(ref n, _)| *n == name) {
+                    Some(i) => fields[i].1 = schema,
+                    None => fields.push((name, schema)),
+                }
+            }
+            _ => panic!("`{}` is a field of a schema that is not a map", name),
+        }
+        self
+    }
+
+    pub fn doc<S: Into<String>>(mut self, doc: S) -> Self {
+        self.doc = Some(doc.into());
+        self
+    }
+
+    /// Sets the value written by `render`, it is checked when the schema is set.
+    pub fn default<V: Into<Value>>(mut self, value: V) -> Self {
+        self.default = Some(value.into());
+        self
+    }
+
+    /// Accepts null besides the values of the schema.
+    pub fn nullable(mut self) -> Self {
+        self.nullable = true;
+        self
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let (yaml, lines) = try!(load::read(path));
+        Self::from_yaml(yaml).map_err(|e| e.in_file(path, &lines))
+    }
+
+    pub fn from_yaml(yaml: Yaml) -> Result<Self, Error> {
+        let schema = match yaml {
+            Yaml::Hash(h) => try!(Self::fields_from_yaml(h, "")),
+            Yaml::Null => Self::map(),
+            _ => return Err(Error::InvalidSchema(invalid("", "the root must be a map"))),
+        };
+
+        try!(schema.check_defaults());
+        Ok(schema)
+    }
+
+    fn fields_from_yaml(h: Hash, path: &str) -> Result<Self, Error> {
+        let mut schema = Self::map();
+        for (k, v) in h {
+            match k {
+                Yaml::String(name) => {
+                    let field = try!(Self::node_from_yaml(v, &de::child_path(path, &name)));
+                    schema = schema.field(name, field);
+                }
+                _ => return Err(Error::InvalidKey(path.to_string())),
+            }
+        }
+
+        Ok(schema)
+    }
+
+    fn node_from_yaml(yaml: Yaml, path: &str) -> Result<Self, Error> {
+        let h = match yaml {
+            Yaml::Hash(h) => h,
+            _ => return Err(Error::InvalidSchema(invalid(path, "expected a schema"))),
+        };
+
+        // A `type` that is not a string is the schema of a field named `type`
+        let type_key = Yaml::String("type".to_string());
+        let kind = match h.get(&type_key) {
+            Some(&Yaml::String(ref kind)) => kind.clone(),
+            _ => return Self::fields_from_yaml(h, path),
+        };
+
+        let get = |key: &str| h.get(&Yaml::String(key.to_string()));
+        let invalid_option = |key: &str| {
+            Error::InvalidSchema(invalid(path, &format!("invalid `{}`", key)))
+        };
+
+        let mut schema = match &kind[..] {
+            "any" => Self::any(),
+            "boolean" => Self::boolean(),
+            "integer" => {
+                Self::new(Kind::Integer {
+                    min: try!(option(get("min"), Yaml::as_i64).ok_or(invalid_option("min"))),
+                    max: try!(option(get("max"), Yaml::as_i64).ok_or(invalid_option("max"))),
+                })
+            }
+            "real" => {
+                Self::new(Kind::Real {
+                    min: try!(option(get("min"), yaml_f64).ok_or(invalid_option("min"))),
+                    max: try!(option(get("max"), yaml_f64).ok_or(invalid_option("max"))),
+                })
+            }
+            "string" => Self::string(),
+            "enum" => {
+                let values: Option<Option<Vec<_>>> = get("values")
+                    .and_then(Yaml::as_vec)
+                    .map(|values| values.iter().map(Yaml::as_str).collect());
+                match values.and_then(|values| values) {
+                    Some(values) => Self::one_of(&values),
+                    None => return Err(invalid_option("values")),
+                }
+            }
+            "array" => {
+                match get("items") {
+                    Some(items) => {
+                        let path = format!("{}[]", path);
+                        Self::array(try!(Self::node_from_yaml(items.clone(), &path)))
+                    }
+                    None => Self::array(Self::any()),
+                }
+            }
+            "map" => {
+                match get("fields") {
+                    Some(&Yaml::Hash(ref fields)) => {
+                        try!(Self::fields_from_yaml(fields.clone(), path))
+                    }
+                    Some(_) => return Err(invalid_option("fields")),
+                    None => Self::map(),
+                }
+            }
+            _ => {
+                let message = format!("unknown type `{}`", kind);
+                return Err(Error::InvalidSchema(invalid(path, &message)));
+            }
+        };
+
+        if let Some(doc) = get("doc") {
+            schema.doc = Some(try!(doc.as_str().ok_or(invalid_option("doc"))).to_string());
+        }
+        if let Some(nullable) = get("nullable") {
+            schema.nullable = try!(nullable.as_bool().ok_or(invalid_option("nullable")));
+        }
+        if let Some(default) = get("default") {
+            schema.default = Some(try!(Value::from(default.clone(), path)));
+        }
+
+        Ok(schema)
+    }
+
+    /// Checks that every default matches its schema.
+    pub(crate) fn check_defaults(&self) -> Result<(), Error> {
+        let mut errors = Vec::new();
+        self.check_default("", &mut errors);
+
+        match errors.into_iter().next() {
+            Some(error) => {
+                let message = format!("invalid default, {}", error.message);
+                Err(Error::InvalidSchema(invalid(&error.path, &message)))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn check_default(&self, path: &str, errors: &mut Vec<SchemaError>) {
+        if let Some(ref default) = self.default {
+            self.validate_value(default, path, errors);
+        }
+
+        match self.kind {
+            Kind::Array(ref items) => items.check_default(&format!("{}[]", path), errors),
+            Kind::Map(ref fields) => {
+                for &(ref name, ref schema) in fields {
+                    schema.check_default(&de::child_path(path, name), errors);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks every value, returns the errors of all the values that do not match.
+    pub fn validate(&self, values: &ValueMap) -> Result<(), Vec<SchemaError>> {
+        let mut errors = Vec::new();
+        match self.kind {
+            Kind::Map(ref fields) => validate_fields(fields, values, "", &mut errors),
+            _ => errors.push(invalid("", "the root schema must be a map")),
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    fn validate_value(&self, value: &Value, path: &str, errors: &mut Vec<SchemaError>) {
+        let mismatch = |found: &str| {
+            invalid(path, &format!("expected {}, found {}", self.describe(), found))
+        };
+
+        match (&self.kind, value) {
+            (_, &Value::None) if self.nullable => {}
+            (&Kind::Any, _) => {}
+            (&Kind::Boolean, &Value::Boolean(_)) => {}
+            (&Kind::Integer { min, max }, &Value::Integer(i)) => {
+                if min.map_or(false, |min| i < min) || max.map_or(false, |max| i > max) {
+                    errors.push(mismatch(&i.to_string()));
+                }
+            }
+            (&Kind::Real { min, max }, &Value::Real(_)) |
+            (&Kind::Real { min, max }, &Value::Integer(_)) => {
+                let r = value.as_f64().unwrap_or_else(|| value.as_i64().unwrap() as f64);
+                if min.map_or(false, |min| r < min) || max.map_or(false, |max| r > max) {
+                    errors.push(mismatch(&r.to_string()));
+                }
+            }
+            (&Kind::String, &Value::String(_)) => {}
+            (&Kind::Enum(ref values), &Value::String(ref s)) => {
+                if !values.contains(s) {
+                    errors.push(mismatch(&format!("`{}`", s)));
+                }
+            }
+            (&Kind::Array(ref items), &Value::Array(ref array)) => {
+                for (i, item) in array.0.iter().enumerate() {
+                    items.validate_value(item, &format!("{}[{}]", path, i), errors);
+                }
+            }
+            (&Kind::Map(ref fields), &Value::Map(ref map)) => {
+                validate_fields(fields, map, path, errors);
+            }
+            _ => errors.push(mismatch(value.kind())),
+        }
+    }
+
+    /// Describes the accepted values, such as `integer from 1 to 300`.
+    fn describe(&self) -> String {
+        let description = match self.kind {
+            Kind::Any => "any value".to_string(),
+            Kind::Boolean => "boolean".to_string(),
+            Kind::Integer { min, max } => describe_range("integer", min, max),
+            Kind::Real { min, max } => describe_range("real", min, max),
+            Kind::String => "string".to_string(),
+            Kind::Enum(ref values) => {
+                let values: Vec<_> = values.iter().map(|v| format!("`{}`", v)).collect();
+                format!("one of {}", values.join(", "))
+            }
+            Kind::Array(ref items) => format!("array of {}", items.describe()),
+            Kind::Map(_) => "map".to_string(),
+        };
+
+        if self.nullable {
+            format!("{} or null", description)
+        } else {
+            description
+        }
+    }
+
+    /// Renders a defaults file, with the documentation of each value in comments.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        if let Kind::Map(ref fields) = self.kind {
+            render_fields(fields, 0, &mut out);
+        }
+        out
+    }
+}
+
+fn invalid(path: &str, message: &str) -> SchemaError {
+    SchemaError {
+        path: path.to_string(),
+        message: message.to_string(),
+        location: None,
+    }
+}
+
+/// Returns `None` if the option is present but invalid.
+fn option<T, F>(yaml: Option<&Yaml>, f: F) -> Option<Option<T>>
+    where F: FnOnce(&Yaml) -> Option<T>
+{
+    match yaml {
+        Some(yaml) => f(yaml).map(Some),
+        None => Some(None),
+    }
+}
+
+fn yaml_f64(yaml: &Yaml) -> Option<f64> {
+    yaml.as_f64().or(yaml.as_i64().map(|i| i as f64))
+}
+
+fn validate_fields(fields: &[(String, Schema)],
+                   map: &ValueMap,
+                   path: &str,
+                   errors: &mut Vec<SchemaError>) {
+    for &(ref name, ref schema) in fields {
+        let field_path = de::child_path(path, name);
+        match map.0.get(name) {
+            Some(value) => schema.validate_value(value, &field_path, errors),
+            None => errors.push(invalid(&field_path, "missing value")),
+        }
+    }
+
+    let mut unknown: Vec<_> = map.0
+        .keys()
+        .filter(|key| !fields.iter().any(|&(ref name, _)| name == *key))
+        .collect();
+    unknown.sort();
+    for key in unknown {
+        errors.push(invalid(&de::child_path(path, key), "unknown key"));
+    }
+}
+
+fn describe_range<T: fmt::Display>(kind: &str, min: Option<T>, max: Option<T>) -> String {
+    match (min, max) {
+        (Some(min), Some(max)) => format!("{} from {} to {}", kind, min, max),
+        (Some(min), None) => format!("{} of at least {}", kind, min),
+        (None, Some(max)) => format!("{} of at most {}", kind, max),
+        (None, None) => kind.to_string(),
+    }
+}
+
+fn render_fields(fields: &[(String, Schema)], depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+
+    for (i, &(ref name, ref schema)) in fields.iter().enumerate() {
+        if i > 0 && depth == 0 {
+            out.push('\n');
+        }
+
+        if let Some(ref doc) = schema.doc {
+            for line in doc.lines() {
+                out.push_str(&format!("{}# {}\n", indent, line));
+            }
+        }
+
+        match schema.kind {
+            Kind::Map(ref fields) if !fields.is_empty() => {
+                out.push_str(&format!("{}{}:\n", indent, render_key(name)));
+                render_fields(fields, depth + 1, out);
+            }
+            Kind::Map(_) => out.push_str(&format!("{}{}: {{}}\n", indent, render_key(name))),
+            _ => {
+                out.push_str(&format!("{}# {}\n", indent, schema.describe()));
+                match schema.default {
+                    Some(ref value) => {
+                        let value = value.render();
+                        out.push_str(&format!("{}{}: {}\n", indent, render_key(name), value));
+                    }
+                    None if schema.nullable => {
+                        out.push_str(&format!("{}{}: null\n", indent, render_key(name)));
+                    }
+                    // Null would not be valid, the value has to be written by hand
+                    None => out.push_str(&format!("{}# {}:\n", indent, render_key(name))),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use yaml_rust::{Yaml, YamlLoader};
+    use {Error, Settings};
+    use super::*;
+
+    fn yaml(source: &str) -> Yaml {
+        YamlLoader::load_from_str(source).unwrap().remove(0)
+    }
+
+    fn schema(source: &str) -> Result<Schema, Error> {
+        Schema::from_yaml(yaml(source))
+    }
+
+    fn graphics() -> Schema {
+        Schema::map().field("graphics", Schema::map()
+            .field("fps_cap", Schema::integer_in(1, 300).nullable().default(60))
+            .field("effects",
+                   Schema::array(Schema::one_of(&["bloom", "fxaa"])).default(vec!["bloom"]))
+            .field("scale", Schema::real_in(0.5, 2.).default(1)))
+    }
+
+    fn paths(errors: &[SchemaError]) -> Vec<&str> {
+        errors.iter().map(|e| &e.path[..]).collect()
+    }
+
+    #[test]
+    fn test_yaml_schema() {
+        let source = "
+graphics:
+  fps_cap:
+    type: integer
+    min: 1
+    max: 300
+    nullable: true
+    default: 60
+  effects:
+    type: array
+    items:
+      type: enum
+      values: [bloom, fxaa]
+    default: [bloom]
+  scale:
+    type: real
+    min: 0.5
+    max: 2
+    default: 1
+";
+        assert_eq!(schema(source).unwrap().render(), graphics().render());
+    }
+
+    #[test]
+    fn test_validate() {
+        let settings = Settings::from_yaml(yaml(&graphics().render())).unwrap();
+        assert_eq!(graphics().validate(&settings), Ok(()));
+
+        let settings = Settings::from_yaml(yaml("graphics:\n  fps_cap: 400\n  effects: [smaa, \
+                                                 fxaa, 1]\n  scale: ~\n  vsync: true\n"))
+            .unwrap();
+        let errors = graphics().validate(&settings).unwrap_err();
+        assert_eq!(paths(&errors),
+                   ["graphics.fps_cap",
+                    "graphics.effects[0]",
+                    "graphics.effects[2]",
+                    "graphics.scale",
+                    "graphics.vsync"]);
+        assert_eq!(errors[0].message, "expected integer from 1 to 300 or null, found 400");
+        assert_eq!(errors[4].message, "unknown key");
+    }
+
+    #[test]
+    fn test_settings_schema() {
+        let mut settings = Settings::from_yaml(yaml(&graphics().render())).unwrap();
+        settings.set_schema(graphics()).unwrap();
+
+        match settings.override_yaml(yaml("graphics:\n  fps_cap: a\n  scale: 3\n")) {
+            Err(Error::Schema(errors)) => {
+                assert_eq!(paths(&errors), ["graphics.fps_cap", "graphics.scale"]);
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert_eq!(settings.get::<u32>("graphics.fps_cap"), Ok(60));
+
+        assert!(settings.set("graphics.scale", 0.25).is_err());
+        settings.set("graphics.fps_cap", Value::None).unwrap();
+
+        let mut settings = Settings::from_yaml(yaml("graphics: {}\n")).unwrap();
+        match settings.set_schema(graphics()) {
+            Err(Error::Schema(errors)) => assert_eq!(errors.len(), 3),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(settings.schema().is_none());
+    }
+
+    #[test]
+    fn test_render_without_default() {
+        let schema = Schema::map()
+            .field("fps_cap", Schema::integer_in(1, 300).nullable())
+            .field("title", Schema::string())
+            .field("volume", Schema::real_in(0., 1.).default(0.5));
+
+        let rendered = schema.render();
+        assert!(rendered.contains("\nfps_cap: null\n"), "{}", rendered);
+        assert!(rendered.contains("\n# title:\n"), "{}", rendered);
+        assert!(rendered.contains("\nvolume: 0.5\n"), "{}", rendered);
+    }
+
+    #[test]
+    fn test_type_field() {
+        let source = "entity:\n  type:\n    type: string\n  count:\n    type: integer\n";
+        let rendered = schema(source).unwrap().render();
+        assert!(rendered.contains("entity:\n  # string\n  # type:\n"), "{}", rendered);
+
+        match schema("kind:\n  type: [1]\n") {
+            Err(Error::InvalidSchema(error)) => assert_eq!(error.path, "kind.type"),
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_invalid_default() {
+        match schema("fps_cap:\n  type: integer\n  max: 300\n  default: 400\n") {
+            Err(Error::InvalidSchema(error)) => {
+                assert_eq!(error.path, "fps_cap");
+                assert!(error.message.starts_with("invalid default"), "{}", error.message);
+            }
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+
+        let schema = Schema::map().field("modes", Schema::array(Schema::one_of(&["a", "b"]))
+            .default(vec!["c"]));
+        match schema.check_defaults() {
+            Err(Error::InvalidSchema(error)) => assert_eq!(error.path, "modes[0]"),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+}
@@ -1,9 +1,11 @@
-use std::{fmt, ops};
-use std::collections::HashMap;
+use std::{fmt, ops, slice};
+use std::collections::{hash_map, HashMap};
+use serde::Deserialize;
 use yaml_rust::yaml::{self, Yaml};
 
-use Error;
+use {de, Error};
 
+#[derive(Clone, PartialEq)]
 pub enum Value {
     Real(f64),
     Integer(i64),
@@ -15,8 +17,10 @@ pub enum Value {
     NotFound,
 }
 
-pub struct ValueArray(Vec<Value>);
-pub struct ValueMap(HashMap<String, Value>);
+#[derive(Clone, PartialEq)]
+pub struct ValueArray(pub(crate) Vec<Value>);
+#[derive(Clone, PartialEq)]
+pub struct ValueMap(pub(crate) HashMap<String, Value>);
 
 impl Value {
     pub fn as_f64(&self) -> Option<f64> {
@@ -67,6 +71,19 @@ impl Value {
         }
     }
 
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            &Value::Real(_) => "real",
+            &Value::Integer(_) => "integer",
+            &Value::String(_) => "string",
+            &Value::Boolean(_) => "boolean",
+            &Value::Array(_) => "array",
+            &Value::Map(_) => "map",
+            &Value::None => "null",
+            &Value::NotFound => "no value",
+        }
+    }
+
     pub fn is_valid(&self) -> bool {
         if let &Value::NotFound = self {
             false
@@ -74,6 +91,95 @@ impl Value {
             true
         }
     }
+
+    /// Returns the value at a path such as `effects[1]` or `[0].size`.
+    pub fn path(&self, path: &str) -> &Value {
+        match parse_path(path) {
+            Some(segments) => segments.iter().fold(self, |value, segment| &value[segment]),
+            None => NOT_FOUND,
+        }
+    }
+}
+
+/// A map key or an array index in a path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+/// Splits a path such as `graphics.effects[1]`, returns `None` if it is malformed.
+pub(crate) fn parse_path(path: &str) -> Option<Vec<Segment>> {
+    let mut segments = Vec::new();
+    for (i, part) in path.split('.').enumerate() {
+        let (key, indices) = match part.find('[') {
+            Some(j) => (&part[..j], &part[j..]),
+            None => (part, ""),
+        };
+
+        if !key.is_empty() {
+            segments.push(Segment::Key(key));
+        } else if i > 0 || indices.is_empty() {
+            return None;
+        }
+
+        if !indices.is_empty() && !indices.ends_with(']') {
+            return None;
+        }
+        for index in indices.split_terminator(']') {
+            if !index.starts_with('[') {
+                return None;
+            }
+            match index[1..].parse() {
+                Ok(index) => segments.push(Segment::Index(index)),
+                Err(_) => return None,
+            }
+        }
+    }
+
+    Some(segments)
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Boolean(b)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(i: i32) -> Self {
+        Value::Integer(i as i64)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::Integer(i)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(r: f64) -> Self {
+        Value::Real(r)
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(s: &'a str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(v: Vec<T>) -> Self {
+        Value::Array(ValueArray(v.into_iter().map(Into::into).collect()))
+    }
 }
 
 impl fmt::Debug for Value {
@@ -123,6 +229,16 @@ impl ops::Index<usize> for Value {
     }
 }
 
+impl<'a, 'b> ops::Index<&'b Segment<'a>> for Value {
+    type Output = Value;
+    fn index(&self, segment: &'b Segment<'a>) -> &Value {
+        match segment {
+            &Segment::Key(key) => &self[key],
+            &Segment::Index(index) => &self[index],
+        }
+    }
+}
+
 impl ops::Index<usize> for ValueArray {
     type Output = Value;
     fn index(&self, i: usize) -> &Value {
@@ -138,84 +254,420 @@ impl<'a> ops::Index<&'a str> for ValueMap {
 }
 
 impl Value {
-    pub(crate) fn from(v: Yaml) -> Result<Self, Error> {
+    /// Converts the yaml found at `path`.
+    pub(crate) fn from(v: Yaml, path: &str) -> Result<Self, Error> {
         Ok(match v {
-            Yaml::Real(s) => Value::Real(parse_float(s)),
+            Yaml::Real(s) => Value::Real(try!(parse_float(s, path))),
             Yaml::Integer(i) => Value::Integer(i),
             Yaml::String(s) => Value::String(s),
             Yaml::Boolean(b) => Value::Boolean(b),
-            Yaml::Array(a) => Value::Array(try!(ValueArray::from(a))),
-            Yaml::Hash(h) => Value::Map(try!(ValueMap::from(h))),
-            Yaml::Alias(_) => panic!("yaml aliases are not supported"),
+            Yaml::Array(a) => Value::Array(try!(ValueArray::from(a, path))),
+            Yaml::Hash(h) => Value::Map(try!(ValueMap::from(h, path))),
+            Yaml::Alias(_) => return Err(Error::UnresolvedAlias(path.to_string())),
             Yaml::Null => Value::None,
-            Yaml::BadValue => Value::NotFound,
+            Yaml::BadValue => return Err(Error::InvalidValue(path.to_string())),
         })
     }
 
-    pub(crate) fn override_with(&mut self, v: Yaml) -> Result<(), Error> {
+    pub(crate) fn override_with(&mut self, v: Yaml, path: &str) -> Result<(), Error> {
         let v = match self {
             &mut Value::Map(ref mut m) => {
                 match v {
-                    Yaml::Hash(h) => return m.override_with(h),
+                    Yaml::Hash(h) => return m.override_with(h, path),
                     _ => v,
                 }
             }
             _ => v,
         };
 
-        *self = try!(Self::from(v));
+        *self = try!(Self::from(v, path));
         Ok(())
     }
 }
 
-fn parse_float(s: String) -> f64 {
-    match s.parse::<f64>() {
-        Ok(v) => v,
-        Err(_) => panic!("yaml float couldn't be parsed"),
+impl Value {
+    pub(crate) fn to_yaml(&self) -> Yaml {
+        match self {
+            &Value::Real(r) => Yaml::Real(format!("{:?}", r)),
+            &Value::Integer(i) => Yaml::Integer(i),
+            &Value::String(ref s) => Yaml::String(s.clone()),
+            &Value::Boolean(b) => Yaml::Boolean(b),
+            &Value::Array(ref a) => Yaml::Array(a.0.iter().map(Value::to_yaml).collect()),
+            &Value::Map(ref m) => {
+                let entries = m.0.iter().map(|(k, v)| (Yaml::String(k.clone()), v.to_yaml()));
+                Yaml::Hash(entries.collect())
+            }
+            &Value::None => Yaml::Null,
+            &Value::NotFound => Yaml::BadValue,
+        }
+    }
+
+    /// Renders the value in the YAML flow style.
+    pub(crate) fn render(&self) -> String {
+        match self {
+            &Value::Real(r) => render_real(r),
+            &Value::Integer(i) => i.to_string(),
+            &Value::String(ref s) => quote(s),
+            &Value::Boolean(b) => b.to_string(),
+            &Value::Array(ref a) => {
+                let items: Vec<_> = a.0.iter().map(Value::render).collect();
+                format!("[{}]", items.join(", "))
+            }
+            &Value::Map(ref m) => {
+                let mut entries: Vec<_> = m.0
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", render_key(k), v.render()))
+                    .collect();
+                entries.sort();
+                format!("{{{}}}", entries.join(", "))
+            }
+            &Value::None | &Value::NotFound => "null".to_string(),
+        }
+    }
+}
+
+fn render_real(r: f64) -> String {
+    if r.is_nan() {
+        ".nan".to_string()
+    } else if r.is_infinite() {
+        if r > 0. { ".inf" } else { "-.inf" }.to_string()
+    } else {
+        format!("{:?}", r)
+    }
+}
+
+/// Writes a string as a YAML double-quoted scalar.
+fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            // The characters YAML does not allow as they are
+            '\u{0}'...'\u{1f}' | '\u{7f}'...'\u{9f}' => {
+                out.push_str(&format!("\\x{:02x}", c as u32))
+            }
+            '\u{fffe}' | '\u{ffff}' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Writes a map key, quoted unless it is read back as the same plain string.
+pub(crate) fn render_key(key: &str) -> String {
+    let plain = match key.chars().next() {
+        Some('a'...'z') | Some('A'...'Z') | Some('_') => {
+            key.chars().all(|c| match c {
+                'a'...'z' | 'A'...'Z' | '0'...'9' | '_' | '-' => true,
+                _ => false,
+            }) && !["true", "false", "null"].contains(&key)
+        }
+        _ => false,
+    };
+
+    if plain { key.to_string() } else { quote(key) }
+}
+
+/// Parses a real like yaml-rust, which also accepts `.inf` and `.nan`.
+fn parse_float(s: String, path: &str) -> Result<f64, Error> {
+    match Yaml::Real(s).as_f64() {
+        Some(v) => Ok(v),
+        None => Err(Error::InvalidValue(path.to_string())),
+    }
+}
+
+/// Adds the values of the maps under the merge key `<<` that are not in `h`.
+fn merge_keys(mut h: yaml::Hash, path: &str) -> Result<yaml::Hash, Error> {
+    let merged = match h.remove(&Yaml::String("<<".to_string())) {
+        Some(Yaml::Hash(m)) => vec![m],
+        Some(Yaml::Array(a)) => {
+            let mut maps = Vec::with_capacity(a.len());
+            for v in a {
+                match v {
+                    Yaml::Hash(m) => maps.push(m),
+                    _ => return Err(Error::InvalidMerge(path.to_string())),
+                }
+            }
+            maps
+        }
+        Some(_) => return Err(Error::InvalidMerge(path.to_string())),
+        None => return Ok(h),
+    };
+
+    for m in merged {
+        for (k, v) in m {
+            if !h.contains_key(&k) {
+                h.insert(k, v);
+            }
+        }
     }
+
+    Ok(h)
 }
 
 impl ValueArray {
-    pub(crate) fn from(a: yaml::Array) -> Result<Self, Error> {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> slice::Iter<Value> {
+        self.0.iter()
+    }
+
+    pub(crate) fn from(a: yaml::Array, path: &str) -> Result<Self, Error> {
         let mut array = Vec::with_capacity(a.len());
-        for v in a {
-            array.push(try!(Value::from(v)));
+        for (i, v) in a.into_iter().enumerate() {
+            array.push(try!(Value::from(v, &format!("{}[{}]", path, i))));
         }
 
         Ok(ValueArray(array))
     }
 }
 
+impl<'a> IntoIterator for &'a ValueArray {
+    type Item = &'a Value;
+    type IntoIter = slice::Iter<'a, Value>;
+    fn into_iter(self) -> slice::Iter<'a, Value> {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a ValueMap {
+    type Item = (&'a String, &'a Value);
+    type IntoIter = hash_map::Iter<'a, String, Value>;
+    fn into_iter(self) -> hash_map::Iter<'a, String, Value> {
+        self.iter()
+    }
+}
+
 impl ValueMap {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+
+    /// Iterates over the keys, in no particular order.
+    pub fn keys(&self) -> hash_map::Keys<String, Value> {
+        self.0.keys()
+    }
+
+    /// Iterates over the keys and values, in no particular order.
+    pub fn iter(&self) -> hash_map::Iter<String, Value> {
+        self.0.iter()
+    }
+
+    /// Returns the value at a path such as `graphics.effects[1]`.
+    pub fn path(&self, path: &str) -> &Value {
+        match parse_path(path).as_ref().and_then(|segments| segments.split_first()) {
+            Some((&Segment::Key(key), rest)) => {
+                rest.iter().fold(&self[key], |value, segment| &value[segment])
+            }
+            _ => NOT_FOUND,
+        }
+    }
+
+    /// Deserializes the value at the path `key`, a missing value is only accepted by an
+    /// `Option`.
+    pub fn get<T: Deserialize>(&self, key: &str) -> Result<T, Error> {
+        de::from_value(self.path(key), key)
+    }
+
+    /// Deserializes the value at the path `key`, or returns `default` if it is missing or null.
+    pub fn get_or<T: Deserialize>(&self, key: &str, default: T) -> Result<T, Error> {
+        match self.path(key) {
+            &Value::NotFound | &Value::None => Ok(default),
+            value => de::from_value(value, key),
+        }
+    }
+
+    /// Renders the map in the YAML block style, with sorted keys.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.render_block(0, &mut out);
+        out
+    }
+
+    fn render_block(&self, depth: usize, out: &mut String) {
+        let mut keys: Vec<_> = self.0.keys().collect();
+        keys.sort();
+
+        for key in keys {
+            let indent = "  ".repeat(depth);
+            match &self.0[key] {
+                &Value::Map(ref m) if !m.0.is_empty() => {
+                    out.push_str(&format!("{}{}:\n", indent, render_key(key)));
+                    m.render_block(depth + 1, out);
+                }
+                value => {
+                    out.push_str(&format!("{}{}: {}\n", indent, render_key(key), value.render()))
+                }
+            }
+        }
+    }
+
+    /// Returns the values that differ from `base`.
+    pub(crate) fn diff(&self, base: &ValueMap) -> ValueMap {
+        let mut diff = HashMap::new();
+        for (key, value) in &self.0 {
+            match (value, &base[key]) {
+                (&Value::Map(ref m), &Value::Map(ref base)) => {
+                    let m = m.diff(base);
+                    if !m.0.is_empty() {
+                        diff.insert(key.clone(), Value::Map(m));
+                    }
+                }
+                (value, default) => {
+                    if value != default {
+                        diff.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+
+        ValueMap(diff)
+    }
+
     pub(crate) fn empty() -> Self {
         ValueMap(HashMap::new())
     }
 
-    pub(crate) fn from(h: yaml::Hash) -> Result<Self, Error> {
+    pub(crate) fn from(h: yaml::Hash, path: &str) -> Result<Self, Error> {
+        let h = try!(merge_keys(h, path));
         let mut map = HashMap::with_capacity(h.len());
         for (k, v) in h {
             match k {
-                Yaml::String(s) => { map.insert(s, try!(Value::from(v))); }
-                _ => return Err(Error::InvalidKey),
+                Yaml::String(s) => {
+                    let value = try!(Value::from(v, &de::child_path(path, &s)));
+                    map.insert(s, value);
+                }
+                _ => return Err(Error::InvalidKey(path.to_string())),
             }
         }
 
         Ok(ValueMap(map))
     }
 
-    pub(crate) fn override_with(&mut self, h: yaml::Hash) -> Result<(), Error> {
-        for (k, v) in h {
+    pub(crate) fn override_with(&mut self, h: yaml::Hash, path: &str) -> Result<(), Error> {
+        for (k, v) in try!(merge_keys(h, path)) {
             match k {
-                Yaml::String(ref s) =>
+                Yaml::String(ref s) => {
+                    let path = de::child_path(path, s);
                     if let Some(current) = self.0.get_mut(s) {
-                       try!(current.override_with(v));
+                        try!(current.override_with(v, &path));
                     } else {
-                        return Err(Error::InvalidOverride);
-                    },
-                _ => return Err(Error::InvalidKey),
+                        return Err(Error::InvalidOverride(path));
+                    }
+                }
+                _ => return Err(Error::InvalidKey(path.to_string())),
             }
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use yaml_rust::YamlLoader;
+    use {Error, Settings};
+    use super::*;
+
+    fn settings() -> Settings {
+        let source = "graphics:\n  effects: [bloom, {name: fxaa, quality: 2}]\n  \
+                      window: {width: 800, height: 600}\n  vsync: ~\n";
+        Settings::from_yaml(YamlLoader::load_from_str(source).unwrap().remove(0)).unwrap()
+    }
+
+    #[test]
+    fn test_parse_path() {
+        assert_eq!(parse_path("a.b[1][0].c"),
+                   Some(vec![Segment::Key("a"),
+                             Segment::Key("b"),
+                             Segment::Index(1),
+                             Segment::Index(0),
+                             Segment::Key("c")]));
+        assert_eq!(parse_path("[2].a"), Some(vec![Segment::Index(2), Segment::Key("a")]));
+        for path in &["", "a.", ".a", "a..b", "a.[1]", "a[1", "a[x]", "a[1]b"] {
+            assert_eq!(parse_path(path), None, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_path() {
+        let settings = settings();
+        assert_eq!(settings.path("graphics.effects[0]").as_str(), Some("bloom"));
+        assert_eq!(settings.path("graphics.effects[1].quality").as_i64(), Some(2));
+        assert_eq!(settings.path("graphics.window.width"),
+                   &settings["graphics"]["window"]["width"]);
+        assert_eq!(settings.path("graphics.vsync"), &Value::None);
+        for path in &["graphics.effects[2]", "graphics.window[0]", "graphics.missing", "[0]"] {
+            assert!(!settings.path(path).is_valid(), "{}", path);
+        }
+
+        let effects = &settings["graphics"]["effects"];
+        assert_eq!(effects.path("[1].name").as_str(), Some("fxaa"));
+        assert_eq!(settings["graphics"].path("window.height").as_i64(), Some(600));
+    }
+
+    #[test]
+    fn test_get_or() {
+        let settings = settings();
+        assert_eq!(settings.get_or("graphics.window.width", 640), Ok(800));
+        assert_eq!(settings.get_or("graphics.window.depth", 24), Ok(24));
+        assert_eq!(settings.get_or("graphics.vsync", true), Ok(true));
+        assert_eq!(settings.get_or("graphics.effects[5]", "smaa".to_string()),
+                   Ok("smaa".to_string()));
+        assert_eq!(settings.get_or("graphics.effects[0]", 1),
+                   Err(Error::Deserialize {
+                       path: "graphics.effects[0]".to_string(),
+                       message: "expected integer, found string".to_string(),
+                   }));
+    }
+
+    #[test]
+    fn test_iterators() {
+        let settings = settings();
+        let effects = settings.path("graphics.effects").as_array().unwrap();
+        assert_eq!(effects.len(), 2);
+        assert!(!effects.is_empty());
+        assert_eq!(effects.iter().filter_map(Value::as_str).collect::<Vec<_>>(), ["bloom"]);
+        assert_eq!(effects[1].as_map().map(ValueMap::len), Some(2));
+
+        let window = settings.path("graphics.window").as_map().unwrap();
+        assert!(window.contains_key("width"));
+        let mut keys: Vec<_> = window.keys().collect();
+        keys.sort();
+        assert_eq!(keys, ["height", "width"]);
+
+        let mut entries: Vec<_> = window.into_iter()
+            .map(|(key, value)| (&key[..], value.as_i64().unwrap()))
+            .collect();
+        entries.sort();
+        assert_eq!(entries, [("height", 600), ("width", 800)]);
+
+        let mut count = 0;
+        for value in effects {
+            assert!(value.is_valid());
+            count += 1;
+        }
+        assert_eq!(count, 2);
+        assert_eq!(settings.iter().count(), 1);
+    }
+}
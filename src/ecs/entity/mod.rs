@@ -120,6 +120,33 @@ impl Pool {
         self.to_be_recycled.push(entity);
     }
 
+    /// Returns the counter and the available entities, in recycling order.
+    ///
+    /// The available entities are popped and pushed back, so it needs exclusive access.
+    pub fn snapshot(&mut self) -> (usize, Vec<Entity>) {
+        let mut availables = Vec::new();
+        while let Some(entity) = self.availables.try_pop() {
+            availables.push(entity);
+        }
+
+        for &entity in &availables {
+            self.availables.push(entity);
+        }
+
+        (self.counter.load(Ordering::Relaxed), availables)
+    }
+
+    pub fn restore(&mut self, counter: usize, availables: &[Entity]) {
+        self.counter = AtomicUsize::new(counter);
+
+        while let Some(_) = self.availables.try_pop() {}
+        while let Some(_) = self.to_be_recycled.try_pop() {}
+
+        for &entity in availables {
+            self.availables.push(entity);
+        }
+    }
+
     pub fn push_freed<F>(&self, mut hook: F)
         where F: FnMut(Entity) -> bool
     {
@@ -237,6 +264,35 @@ impl Entities {
             self.spawn(entity);
         }
     }
+
+    /// Saves the alive entities and the recycling state.
+    ///
+    /// It must be called between two commits, pending spawns and removes are not saved.
+    pub fn snapshot(&mut self) -> EntitiesSnapshot {
+        let (counter, availables) = self.pool.snapshot();
+
+        EntitiesSnapshot {
+            counter: counter,
+            availables: availables,
+            versions: self.versions.clone(),
+        }
+    }
+
+    /// Restores the entities from a snapshot, discarding pending spawns and removes.
+    pub fn restore(&mut self, snapshot: &EntitiesSnapshot) {
+        self.pool.restore(snapshot.counter, &snapshot.availables);
+        self.versions.clone_from(&snapshot.versions);
+
+        while let Some(_) = self.spawns.try_pop() {}
+    }
+}
+
+/// The saved state of `Entities`.
+#[derive(Clone, Debug)]
+pub struct EntitiesSnapshot {
+    counter: usize,
+    availables: Vec<Entity>,
+    versions: VecMap<Version>,
 }
 
 
@@ -373,6 +429,34 @@ mod tests {
         assert!(EntityRef(new_entity) != removed);
     }
 
+    #[test]
+    fn test_pool_snapshot_restore() {
+        let mut pool = Pool::new();
+
+        let first = pool.acquire();
+        let second = pool.acquire();
+        pool.free(first);
+        pool.free(second);
+        pool.push_freed(|_| true);
+
+        let (counter, availables) = pool.snapshot();
+        assert_eq!(counter, 2);
+        assert_eq!(availables, vec![first, second]);
+
+        // Taking the snapshot leaves the recycling order untouched
+        assert_eq!(pool.snapshot(), (counter, availables.clone()));
+
+        let recycled = pool.acquire();
+        assert_eq!(recycled, first.next_version());
+        pool.acquire();
+        pool.acquire();
+
+        pool.restore(counter, &availables);
+        assert_eq!(pool.acquire(), recycled);
+        assert_eq!(pool.acquire(), second.next_version());
+        assert_eq!(pool.acquire(), Entity(2, 0));
+    }
+
     fn remove_later_one_entity(entities: &mut Entities) -> EntityRef {
         let entity = entities.create();
         let entity_ref = entities.spawn(entity);
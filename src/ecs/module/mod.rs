@@ -16,8 +16,25 @@ pub trait Module<Cx: Send>: Any + Send + Sync {
     }
 
     fn commit(&mut self, args: &CommitArgs, context: &mut Cx);
+
+    /// Called before each fixed update, such as to save the values that are interpolated
+    /// between two fixed updates.
+    fn begin_fixed_update(&mut self, _context: &mut Cx) {}
+
+    /// Saves the module data so that it can be restored later.
+    ///
+    /// Modules that can not be rolled back return `None`, which is the default.
+    fn snapshot(&self) -> Option<ModuleSnapshot> {
+        None
+    }
+
+    /// Restores the module data from a snapshot made by `Module::snapshot`.
+    fn restore(&mut self, _snapshot: &ModuleSnapshot) {}
 }
 
+/// The saved data of a module.
+pub type ModuleSnapshot = Box<Any + Send + Sync>;
+
 impl<Cx: Send> Module<Cx> {
     #[inline]
     pub fn is<M: Module<Cx>>(&self) -> bool {
@@ -94,6 +111,29 @@ impl<Cx: Send> Modules<Cx> {
         }
     }
 
+    pub fn begin_fixed_update(&mut self, cx: &mut Cx) {
+        for (_, module) in &mut self.modules {
+            module.begin_fixed_update(cx);
+        }
+    }
+
+    pub fn snapshot(&self) -> FnvHashMap<ModuleType, ModuleSnapshot> {
+        self.modules
+            .iter()
+            .filter_map(|(&module_type, module)| {
+                module.snapshot().map(|snapshot| (module_type, snapshot))
+            })
+            .collect()
+    }
+
+    pub fn restore(&mut self, snapshots: &FnvHashMap<ModuleType, ModuleSnapshot>) {
+        for (module_type, snapshot) in snapshots {
+            if let Some(module) = self.modules.get_mut(module_type) {
+                module.restore(snapshot);
+            }
+        }
+    }
+
     pub fn iter(&self) -> Iter<Cx> {
         Iter { inner: self.modules.iter() }
     }
@@ -107,6 +107,7 @@ impl Slot {
 
 struct ActionGraphBuilder {
     heads: Vec<NodeIndex>,
+    order: Vec<NodeIndex>,
     execution_dag: Dag<Slot, LinkType, Index>,
     writes: FnvHashMap<ComponentType, NodeIndex>,
     reads: FnvHashMap<ComponentType, Vec<NodeIndex>>,
@@ -119,6 +120,7 @@ impl ActionGraphBuilder {
             writes: FnvHashMap::default(),
             reads: FnvHashMap::default(),
             heads: Vec::new(),
+            order: Vec::new(),
         }
     }
 
@@ -127,6 +129,7 @@ impl ActionGraphBuilder {
                     reads: &ComponentTypes,
                     writes: &ComponentTypes) {
         let node = self.execution_dag.add_node(Slot::new(processor_index));
+        self.order.push(node);
 
         let read_dependencies = self.add_read_dependencies(node, reads);
         let write_dependencies = self.add_write_dependencies(node, writes);
@@ -206,6 +209,7 @@ impl ActionGraphBuilder {
     pub fn build(self) -> ActionGraph {
         ActionGraph {
             heads: self.heads,
+            order: self.order,
             execution_dag: self.execution_dag,
         }
     }
@@ -214,10 +218,45 @@ impl ActionGraphBuilder {
 
 pub struct ActionGraph {
     heads: Vec<NodeIndex>,
+    // Registration order, dependencies are always registered before their dependents
+    order: Vec<NodeIndex>,
     execution_dag: Dag<Slot, LinkType, Index>,
 }
 
 impl ActionGraph {
+    fn for_each_mut<F, Cx: Context>(&self,
+                       processors: &Processors<Cx>,
+                       state: &State<Cx>,
+                       commit: Commit<Cx>,
+                       cx: &Cx,
+                       f: F)
+        where F: Fn(&State<Cx>, Commit<Cx>, &Cx, &mut Processor<Cx>)
+    {
+        for &node in &self.order {
+            let slot = &self.execution_dag[node];
+            let mut processor = processors.take(slot.processor).unwrap();
+
+            f(state, commit, cx, &mut *processor);
+
+            processors.put(slot.processor, processor);
+        }
+    }
+
+    fn run<F, Cx: Context>(&self,
+                           mode: ExecutionMode,
+                           processors: &Processors<Cx>,
+                           state: &State<Cx>,
+                           commit: Commit<Cx>,
+                           cx: &Cx,
+                           f: F)
+        where F: Fn(&State<Cx>, Commit<Cx>, &Cx, &mut Processor<Cx>) + Sync + Send
+    {
+        match mode {
+            ExecutionMode::Parallel => self.par_for_each_mut(processors, state, commit, cx, f),
+            ExecutionMode::Deterministic => self.for_each_mut(processors, state, commit, cx, f),
+        }
+    }
+
     fn par_for_each_mut<F, Cx: Context>(&self,
                            processors: &Processors<Cx>,
                            state: &State<Cx>,
@@ -272,10 +311,23 @@ pub enum UpdateType {
     Both,
 }
 
+/// Defines how the processors of an update are executed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExecutionMode {
+    /// Processors are run in parallel as soon as their dependencies are done.
+    Parallel,
+    /// Processors are run one after the other, in registration order.
+    ///
+    /// Two runs of the same update on the same state will give the same result,
+    /// which is needed by lockstep networking and replays.
+    Deterministic,
+}
+
 pub struct SchedulerBuilder<Cx: Context> {
     processors: Processors<Cx>,
     updates: ActionGraphBuilder,
     fixed_updates: ActionGraphBuilder,
+    fixed_update_mode: ExecutionMode,
 }
 
 impl<Cx: Context> SchedulerBuilder<Cx> {
@@ -283,13 +335,20 @@ impl<Cx: Context> SchedulerBuilder<Cx> {
         SchedulerBuilder {
             processors: Processors::new(),
             updates: ActionGraphBuilder::new(),
-            fixed_updates: ActionGraphBuilder::new()
+            fixed_updates: ActionGraphBuilder::new(),
+            fixed_update_mode: ExecutionMode::Parallel,
         }
     }
 
+    /// Sets the execution mode of the fixed updates, `Parallel` by default.
+    pub fn fixed_update_mode(&mut self, mode: ExecutionMode) -> &mut Self {
+        self.fixed_update_mode = mode;
+        self
+    }
+
     pub fn register<P: Processor<Cx>>(&mut self, processor: P, update_type: UpdateType) -> &mut Self {
         {
-            let &mut SchedulerBuilder { ref mut processors, ref mut updates, ref mut fixed_updates } = self;
+            let &mut SchedulerBuilder { ref mut processors, ref mut updates, ref mut fixed_updates, .. } = self;
             processors.push(Box::new(processor), |index, processor| {
                 let reads = processor.reads();
                 let writes = processor.writes();
@@ -315,6 +374,7 @@ impl<Cx: Context> SchedulerBuilder<Cx> {
             processors: self.processors,
             updates: self.updates.build(),
             fixed_updates: self.fixed_updates.build(),
+            fixed_update_mode: self.fixed_update_mode,
         }
     }
 }
@@ -323,9 +383,18 @@ pub struct Scheduler<Cx: Context> {
     processors: Processors<Cx>,
     updates: ActionGraph,
     fixed_updates: ActionGraph,
+    fixed_update_mode: ExecutionMode,
 }
 
 impl<Cx: Context> Scheduler<Cx> {
+    pub fn fixed_update_mode(&self) -> ExecutionMode {
+        self.fixed_update_mode
+    }
+
+    pub fn set_fixed_update_mode(&mut self, mode: ExecutionMode) {
+        self.fixed_update_mode = mode;
+    }
+
     pub fn update(&mut self, state: &mut State<Cx>, context: &mut Cx, delta: f32) {
         let mut update = state.update();
 
@@ -337,10 +406,11 @@ impl<Cx: Context> Scheduler<Cx> {
     }
 
     pub fn fixed_update(&mut self, state: &mut State<Cx>, context: &mut Cx) {
-        let mut update = state.update();
+        let mut update = state.fixed_update(context);
+        let mode = self.fixed_update_mode;
 
         update.commit(context, |state, commit, context| {
-            self.fixed_updates.par_for_each_mut(&self.processors, state, commit, context, |state, commit, context, processor| {
+            self.fixed_updates.run(mode, &self.processors, state, commit, context, |state, commit, context, processor| {
                 processor.fixed_update(state, commit, context);
             });
         });
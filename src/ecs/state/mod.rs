@@ -1,7 +1,9 @@
 mod builder;
 pub mod update_queue;
+pub mod snapshot;
 
 pub use self::builder::StateBuilder;
+pub use self::snapshot::{StateSnapshot, Snapshots};
 pub use self::update_queue::Monitors as UpdateMonitors;
 
 use ecs::entity::{Entities, Entity, EntityRef, Accessor};
@@ -84,10 +86,35 @@ impl<Cx: Send> State<Cx> {
     }
 
 
+    /// Saves the state so that it can be rolled back later.
+    ///
+    /// Only the modules supporting snapshots are saved.
+    pub fn snapshot(&mut self) -> StateSnapshot {
+        StateSnapshot::new(self.entities.snapshot(),
+                           self.update_queues.snapshot(),
+                           self.modules.snapshot())
+    }
+
+    /// Rolls the state back to a snapshot.
+    pub fn restore(&mut self, snapshot: &StateSnapshot) {
+        self.entities.restore(snapshot.entities());
+        self.update_queues.restore(snapshot.monitors());
+        self.modules.restore(snapshot.modules());
+
+        self.groups.commit(&self.update_queues.monitors());
+        self.update_queues.clear_flags();
+    }
+
     pub fn update(&mut self) -> Update<Cx> {
         Update { state: self }
     }
 
+    /// Starts a fixed update, after letting the modules prepare for it.
+    pub fn fixed_update(&mut self, cx: &mut Cx) -> Update<Cx> {
+        self.modules.begin_fixed_update(cx);
+        Update { state: self }
+    }
+
     fn commit(&mut self, cx: &mut Cx) {
         let world_removes = self.entities.push_removes();
 
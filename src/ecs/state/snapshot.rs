@@ -0,0 +1,187 @@
+//! State snapshots for rollbacks
+//!
+//! Snapshots are made between two updates, usually after each fixed update,
+//! and are tied to the `FrameClock` tick they were made at.
+
+use std::collections::VecDeque;
+use fnv::FnvHashMap;
+use frameclock::Tick;
+use ecs::entity::EntitiesSnapshot;
+use ecs::module::{ModuleType, ModuleSnapshot};
+use ecs::module::ComponentType;
+use ecs::policy::IdSet;
+use ecs::state::State;
+
+/// A saved `State`.
+pub struct StateSnapshot {
+    entities: EntitiesSnapshot,
+    monitors: FnvHashMap<ComponentType, IdSet>,
+    modules: FnvHashMap<ModuleType, ModuleSnapshot>,
+}
+
+impl StateSnapshot {
+    pub(crate) fn new(entities: EntitiesSnapshot,
+                      monitors: FnvHashMap<ComponentType, IdSet>,
+                      modules: FnvHashMap<ModuleType, ModuleSnapshot>)
+                      -> Self {
+        StateSnapshot {
+            entities: entities,
+            monitors: monitors,
+            modules: modules,
+        }
+    }
+
+    pub(crate) fn entities(&self) -> &EntitiesSnapshot {
+        &self.entities
+    }
+
+    pub(crate) fn monitors(&self) -> &FnvHashMap<ComponentType, IdSet> {
+        &self.monitors
+    }
+
+    pub(crate) fn modules(&self) -> &FnvHashMap<ModuleType, ModuleSnapshot> {
+        &self.modules
+    }
+}
+
+/// A ring buffer of the last state snapshots.
+pub struct Snapshots {
+    capacity: usize,
+    snapshots: VecDeque<(Tick, StateSnapshot)>,
+}
+
+impl Snapshots {
+    /// Creates a new ring buffer keeping at most `capacity` snapshots.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "the snapshots capacity must not be zero");
+
+        Snapshots {
+            capacity: capacity,
+            snapshots: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Saves the state as it is at `tick`.
+    pub fn record<Cx: Send>(&mut self, tick: Tick, state: &mut State<Cx>) {
+        self.push(tick, state.snapshot());
+    }
+
+    /// Pushes a snapshot made at `tick`, dropping the oldest one if the buffer is full.
+    ///
+    /// The snapshots made at `tick` or later are discarded, since they belong to an outdated timeline.
+    pub fn push(&mut self, tick: Tick, snapshot: StateSnapshot) {
+        self.discard_from(tick);
+
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+
+        self.snapshots.push_back((tick, snapshot));
+    }
+
+    /// Returns the snapshot made at `tick`.
+    pub fn get(&self, tick: Tick) -> Option<&StateSnapshot> {
+        self.snapshots
+            .iter()
+            .find(|&&(snapshot_tick, _)| snapshot_tick == tick)
+            .map(|&(_, ref snapshot)| snapshot)
+    }
+
+    /// Rolls the state back to the snapshot made at `tick`.
+    ///
+    /// The snapshots made after `tick` are discarded so that they can be recorded again
+    /// while re-simulating. Returns false if there is no snapshot for `tick`.
+    pub fn rollback<Cx: Send>(&mut self, tick: Tick, state: &mut State<Cx>) -> bool {
+        match self.get(tick) {
+            Some(snapshot) => state.restore(snapshot),
+            None => return false,
+        }
+
+        self.discard_from(tick + 1);
+        true
+    }
+
+    /// Returns the tick of the oldest snapshot that can be rolled back to.
+    pub fn oldest_tick(&self) -> Option<Tick> {
+        self.snapshots.front().map(|&(tick, _)| tick)
+    }
+
+    /// Returns the tick of the most recent snapshot.
+    pub fn latest_tick(&self) -> Option<Tick> {
+        self.snapshots.back().map(|&(tick, _)| tick)
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+
+    fn discard_from(&mut self, tick: Tick) {
+        while self.snapshots.back().map(|&(last, _)| last >= tick).unwrap_or(false) {
+            self.snapshots.pop_back();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::entity::EntityRef;
+    use ecs::state::{State, StateBuilder};
+
+    fn spawn(state: &mut State<()>) -> EntityRef {
+        let mut entity_ref = None;
+        state.update().commit(&mut (), |_, commit, _| {
+            entity_ref = Some(commit.spawn_later().entity_ref());
+        });
+
+        entity_ref.unwrap()
+    }
+
+    fn remove(state: &mut State<()>, entity_ref: EntityRef) {
+        state.update().commit(&mut (), |state, commit, _| {
+            let accessor = state.accessor(entity_ref).unwrap();
+            commit.remove_later(accessor);
+        });
+    }
+
+    #[test]
+    fn test_rollback() {
+        let mut state = StateBuilder::<()>::new().build();
+        let mut snapshots = Snapshots::new(4);
+
+        let first = spawn(&mut state);
+        snapshots.record(0, &mut state);
+
+        remove(&mut state, first);
+        let second = spawn(&mut state);
+        snapshots.record(1, &mut state);
+        assert!(state.accessor(first).is_none());
+
+        assert!(snapshots.rollback(0, &mut state));
+        assert!(state.accessor(first).is_some());
+        assert_eq!(snapshots.latest_tick(), Some(0));
+
+        // Re-simulating gives the same entities
+        remove(&mut state, first);
+        assert_eq!(spawn(&mut state), second);
+    }
+
+    #[test]
+    fn test_ring_capacity() {
+        let mut state = StateBuilder::<()>::new().build();
+        let mut snapshots = Snapshots::new(3);
+
+        for tick in 0..5 {
+            snapshots.record(tick, &mut state);
+        }
+
+        assert_eq!(snapshots.len(), 3);
+        assert_eq!(snapshots.oldest_tick(), Some(2));
+        assert!(snapshots.get(1).is_none());
+        assert!(snapshots.get(4).is_some());
+    }
+}
@@ -41,6 +41,11 @@ impl Monitor {
         }
     }
 
+    fn restore(&mut self, entities: &IdSet) {
+        self.modified = true;
+        self.entities.clone_from(entities);
+    }
+
     fn clear_modified_flag(&mut self) {
         self.modified = false;
     }
@@ -160,6 +165,24 @@ impl UpdateQueues {
         }
     }
 
+    /// Saves the entities of every monitor.
+    pub fn snapshot(&self) -> FnvHashMap<ComponentType, IdSet> {
+        self.queues
+            .iter()
+            .map(|(&component_type, queue)| (component_type, queue.monitor().entities().clone()))
+            .collect()
+    }
+
+    /// Restores the entities of the monitors, the monitors are flagged as modified.
+    pub fn restore(&mut self, snapshot: &FnvHashMap<ComponentType, IdSet>) {
+        for (component_type, queue) in &mut self.queues {
+            match snapshot.get(component_type) {
+                Some(entities) => queue.monitor_mut().restore(entities),
+                None => queue.monitor_mut().restore(&IdSet::new()),
+            }
+        }
+    }
+
     fn monitor(&self, component_type: ComponentType) -> RwLockReadGuard<Monitor> {
         self.queues
             .get(&component_type)
@@ -0,0 +1,127 @@
+//! The archetype module
+//!
+//! Entities that have exactly the same set of archetype components are grouped
+//! together in an archetype. Their components are stored column by column in
+//! fixed size chunks, which makes iterating over several components of the
+//! matching entities linear in memory.
+
+/// Implements the traits needed to store `$cmp` in the `ArchetypeModule`.
+#[macro_export]
+macro_rules! derive_archetype_component {
+    ($cmp:ident) => (
+        impl $crate::ecs::module::Template for $cmp {}
+
+        impl $crate::ecs::module::Component for $cmp {
+            type Module = $crate::modules::archetype::ArchetypeModule;
+            type Template = $cmp;
+        }
+
+        impl $crate::modules::archetype::ArchetypeComponent for $cmp {}
+    )
+}
+
+pub mod storage;
+
+pub use self::storage::{Archetypes, Archetype, Chunk, ColumnsMut, Chunks, ChunksMut, CHUNK_CAPACITY};
+
+use std::any::Any;
+use std::marker::PhantomData;
+use ecs::state::CommitArgs;
+use ecs::module::{Module, HasComponent, Component, Template};
+use ecs::module::{StorageLock, StorageReadGuard, StorageWriteGuard, ModuleSnapshot};
+
+/// A component stored by the `ArchetypeModule`.
+///
+/// You can use the `derive_archetype_component!` macro to implement it.
+pub trait ArchetypeComponent
+    : Component<Module = ArchetypeModule, Template = Self> + Template {
+}
+
+/// Applies the update queue of a registered component to the archetypes.
+trait Registration: Send + Sync {
+    fn commit(&self, args: &CommitArgs, archetypes: &mut Archetypes);
+}
+
+struct Registered<C: ArchetypeComponent>(PhantomData<C>);
+
+impl<C: ArchetypeComponent> Registration for Registered<C> {
+    fn commit(&self, args: &CommitArgs, archetypes: &mut Archetypes) {
+        let mut updates = args.update_reader_for::<C>();
+
+        while let Some((id, component)) = updates.next_attach_query() {
+            archetypes.insert(id, component);
+        }
+
+        while let Some(id) = updates.next_detach_query() {
+            archetypes.remove::<C>(id);
+        }
+    }
+}
+
+pub struct ArchetypeModule {
+    archetypes: StorageLock<Archetypes>,
+    registrations: Vec<Box<Registration>>,
+}
+
+impl ArchetypeModule {
+    pub fn new() -> Self {
+        ArchetypeModule {
+            archetypes: StorageLock::new(Archetypes::new()),
+            registrations: Vec::new(),
+        }
+    }
+
+    /// Registers the component `C` to be stored in archetypes.
+    ///
+    /// The component must also be registered in the `StateBuilder`.
+    pub fn register<C: ArchetypeComponent>(&mut self) {
+        self.archetypes.write().register::<C>();
+        self.registrations.push(Box::new(Registered::<C>(PhantomData)));
+    }
+
+    pub fn read(&self) -> StorageReadGuard<Archetypes> {
+        self.archetypes.read()
+    }
+
+    pub fn write(&self) -> StorageWriteGuard<Archetypes> {
+        self.archetypes.write()
+    }
+}
+
+impl<Cx: Send> Module<Cx> for ArchetypeModule {
+    fn commit(&mut self, args: &CommitArgs, _context: &mut Cx) {
+        let mut archetypes = self.archetypes.write();
+
+        for registration in &self.registrations {
+            registration.commit(args, &mut archetypes);
+        }
+
+        for entity in args.world_removes() {
+            archetypes.remove_entity(entity.id());
+        }
+    }
+
+    fn snapshot(&self) -> Option<ModuleSnapshot> {
+        Some(Box::new(self.archetypes.read().clone()))
+    }
+
+    fn restore(&mut self, snapshot: &ModuleSnapshot) {
+        let snapshot: &Any = &**snapshot;
+
+        if let Some(archetypes) = snapshot.downcast_ref::<Archetypes>() {
+            *self.archetypes.write() = archetypes.clone();
+        }
+    }
+}
+
+impl<C: ArchetypeComponent> HasComponent<C> for ArchetypeModule {
+    type Storage = Archetypes;
+
+    fn read(&self) -> StorageReadGuard<Self::Storage> {
+        self.archetypes.read()
+    }
+
+    fn write(&self) -> StorageWriteGuard<Self::Storage> {
+        self.archetypes.write()
+    }
+}
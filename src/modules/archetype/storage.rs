@@ -0,0 +1,821 @@
+//! The archetype storage
+//!
+use std::fmt::Debug;
+use std::vec;
+use mopa;
+use fnv::FnvHashMap;
+use vec_map::VecMap;
+
+use ecs::entity::Accessor;
+use ecs::entity::iter::{self, SliceIter};
+use ecs::group::Filter;
+use ecs::module::ComponentType;
+use ecs::policy::Id;
+use super::ArchetypeComponent;
+
+/// The maximum number of entities stored in a chunk.
+pub const CHUNK_CAPACITY: usize = 128;
+
+type ComponentIndex = usize;
+type ArchetypeIndex = usize;
+type ChunkIndex = usize;
+type Row = usize;
+
+/// A type-erased vector of components.
+trait Column: mopa::Any + Debug + Send + Sync {
+    /// Creates a new empty column holding the same component type.
+    fn empty(&self) -> Box<Column>;
+    fn boxed_clone(&self) -> Box<Column>;
+    fn swap_remove(&mut self, row: Row);
+    /// Removes the component at `row` and pushes it in `other`.
+    ///
+    /// **Panics** if `other` does not hold the same component type.
+    fn swap_remove_into(&mut self, row: Row, other: &mut Column);
+}
+mopafy!(Column);
+
+impl Clone for Box<Column> {
+    fn clone(&self) -> Self {
+        self.boxed_clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ColumnVec<C>(Vec<C>);
+
+impl<C: ArchetypeComponent> Column for ColumnVec<C> {
+    fn empty(&self) -> Box<Column> {
+        Box::new(ColumnVec::<C>(Vec::with_capacity(CHUNK_CAPACITY)))
+    }
+
+    fn boxed_clone(&self) -> Box<Column> {
+        Box::new(self.clone())
+    }
+
+    fn swap_remove(&mut self, row: Row) {
+        self.0.swap_remove(row);
+    }
+
+    fn swap_remove_into(&mut self, row: Row, other: &mut Column) {
+        let component = self.0.swap_remove(row);
+
+        other.downcast_mut::<ColumnVec<C>>()
+            .expect("the columns do not hold the same component type")
+            .0
+            .push(component);
+    }
+}
+
+/// The sorted set of components of an archetype.
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+struct Signature(Vec<ComponentIndex>);
+
+impl Signature {
+    fn with(&self, index: ComponentIndex) -> Self {
+        let mut indices = self.0.clone();
+        if let Err(position) = indices.binary_search(&index) {
+            indices.insert(position, index);
+        }
+
+        Signature(indices)
+    }
+
+    fn without(&self, index: ComponentIndex) -> Self {
+        let mut indices = self.0.clone();
+        if let Ok(position) = indices.binary_search(&index) {
+            indices.remove(position);
+        }
+
+        Signature(indices)
+    }
+
+    fn contains(&self, index: ComponentIndex) -> bool {
+        self.0.binary_search(&index).is_ok()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// A fixed size block of entities sharing the same archetype.
+///
+/// Each component is stored in its own contiguous column,
+/// the rows of every column belong to the same entity.
+#[derive(Debug, Clone)]
+pub struct Chunk {
+    types: Vec<ComponentType>,
+    entities: Vec<Id>,
+    columns: Vec<Box<Column>>,
+}
+
+impl Chunk {
+    fn new(types: Vec<ComponentType>, columns: Vec<Box<Column>>) -> Self {
+        Chunk {
+            types: types,
+            entities: Vec::with_capacity(CHUNK_CAPACITY),
+            columns: columns,
+        }
+    }
+
+    /// Returns the number of entities in the chunk.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    #[inline]
+    fn is_full(&self) -> bool {
+        self.entities.len() == CHUNK_CAPACITY
+    }
+
+    /// An iterator over the entities of the chunk, in row order.
+    pub fn entities(&self) -> SliceIter {
+        unsafe { iter::accessors_from_slice(&self.entities) }
+    }
+
+    /// Returns the column of the component `C`, if the chunk holds it.
+    pub fn column<C: ArchetypeComponent>(&self) -> Option<&[C]> {
+        self.position::<C>().map(|position| {
+            &self.columns[position]
+                .downcast_ref::<ColumnVec<C>>()
+                .expect("the column does not hold the expected component type")
+                .0[..]
+        })
+    }
+
+    /// Returns the mutable column of the component `C`, if the chunk holds it.
+    pub fn column_mut<C: ArchetypeComponent>(&mut self) -> Option<&mut [C]> {
+        match self.position::<C>() {
+            Some(position) => {
+                let column = self.columns[position]
+                    .downcast_mut::<ColumnVec<C>>()
+                    .expect("the column does not hold the expected component type");
+
+                Some(&mut column.0[..])
+            }
+            None => None,
+        }
+    }
+
+    /// Splits the chunk into columns that can be borrowed mutably at the same time.
+    pub fn columns_mut(&mut self) -> ColumnsMut {
+        ColumnsMut {
+            types: &self.types,
+            entities: &self.entities,
+            columns: self.columns.iter_mut().map(Some).collect(),
+        }
+    }
+
+    #[inline]
+    fn position<C: ArchetypeComponent>(&self) -> Option<usize> {
+        let component_type = ComponentType::of::<C>();
+        self.types.iter().position(|&t| t == component_type)
+    }
+
+    fn push<C: ArchetypeComponent>(&mut self, component: C) {
+        let position = self.position::<C>().expect("the chunk does not hold the component");
+
+        self.columns[position]
+            .downcast_mut::<ColumnVec<C>>()
+            .expect("the column does not hold the expected component type")
+            .0
+            .push(component);
+    }
+
+    fn set<C: ArchetypeComponent>(&mut self, row: Row, component: C) {
+        self.column_mut::<C>().expect("the chunk does not hold the component")[row] = component;
+    }
+
+    /// Removes a row, moving the components also held by `destination` into it.
+    ///
+    /// The moved entity is not pushed in the `destination` entities.
+    fn swap_remove_into(&mut self, row: Row, mut destination: Option<&mut Chunk>) {
+        for (component_type, column) in self.types.iter().zip(self.columns.iter_mut()) {
+            let target = destination.as_mut().and_then(|destination| {
+                destination.types
+                    .iter()
+                    .position(|t| t == component_type)
+                    .map(move |position| &mut destination.columns[position])
+            });
+
+            match target {
+                Some(target) => column.swap_remove_into(row, &mut **target),
+                None => column.swap_remove(row),
+            }
+        }
+
+        self.entities.swap_remove(row);
+    }
+}
+
+/// The columns of a chunk, each of them can be taken once.
+pub struct ColumnsMut<'a> {
+    types: &'a [ComponentType],
+    entities: &'a [Id],
+    columns: Vec<Option<&'a mut Box<Column>>>,
+}
+
+impl<'a> ColumnsMut<'a> {
+    /// An iterator over the entities of the chunk, in row order.
+    pub fn entities(&self) -> SliceIter<'a> {
+        unsafe { iter::accessors_from_slice(self.entities) }
+    }
+
+    /// Takes the column of the component `C`.
+    ///
+    /// Returns `None` if the chunk does not hold `C` or if the column has already been taken.
+    pub fn take<C: ArchetypeComponent>(&mut self) -> Option<&'a mut [C]> {
+        let component_type = ComponentType::of::<C>();
+
+        match self.types.iter().position(|&t| t == component_type) {
+            Some(position) => {
+                self.columns[position].take().map(|column| {
+                    &mut column.downcast_mut::<ColumnVec<C>>()
+                        .expect("the column does not hold the expected component type")
+                        .0[..]
+                })
+            }
+            None => None,
+        }
+    }
+}
+
+/// The location of an entity in the archetypes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Location {
+    archetype: ArchetypeIndex,
+    chunk: ChunkIndex,
+    row: Row,
+}
+
+/// A group of entities having the same set of components.
+#[derive(Debug, Clone)]
+pub struct Archetype {
+    signature: Signature,
+    types: Vec<ComponentType>,
+    chunks: Vec<Chunk>,
+}
+
+impl Archetype {
+    fn new(signature: Signature, types: Vec<ComponentType>) -> Self {
+        Archetype {
+            signature: signature,
+            types: types,
+            chunks: Vec::new(),
+        }
+    }
+
+    /// Returns the component types of this archetype.
+    pub fn component_types(&self) -> &[ComponentType] {
+        &self.types
+    }
+
+    /// Returns the chunks of this archetype.
+    ///
+    /// Every chunk is full except the last one.
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    /// Returns the number of entities in this archetype.
+    pub fn len(&self) -> usize {
+        self.chunks.iter().map(Chunk::len).sum()
+    }
+
+    /// Returns true if the entities of this archetype are matched by the `filter`.
+    pub fn matches(&self, filter: &Filter) -> bool {
+        filter.require.iter().all(|t| self.types.contains(t)) &&
+        !filter.reject.iter().any(|t| self.types.contains(t))
+    }
+
+    /// Returns the index of a chunk with some room left, allocating it if needed.
+    fn free_chunk(&mut self, prototypes: &[Box<Column>]) -> ChunkIndex {
+        let has_room = self.chunks.last().map(|chunk| !chunk.is_full()).unwrap_or(false);
+
+        if !has_room {
+            let columns = self.signature
+                .0
+                .iter()
+                .map(|&index| prototypes[index].empty())
+                .collect();
+
+            self.chunks.push(Chunk::new(self.types.clone(), columns));
+        }
+
+        self.chunks.len() - 1
+    }
+
+    /// Removes a row, moving the components also held by `destination` into it.
+    ///
+    /// The hole left in the chunk is filled with the last entity of the archetype, so
+    /// that only the last chunk is not full. The entities that moved are pushed in `moved`.
+    fn take_row(&mut self,
+                chunk_index: ChunkIndex,
+                row: Row,
+                destination: Option<&mut Chunk>,
+                moved: &mut Vec<(Id, ChunkIndex, Row)>) {
+        {
+            let chunk = &mut self.chunks[chunk_index];
+            chunk.swap_remove_into(row, destination);
+
+            if row < chunk.len() {
+                moved.push((chunk.entities[row], chunk_index, row));
+            }
+        }
+
+        let last_index = self.chunks.len() - 1;
+        if chunk_index != last_index {
+            let (head, tail) = self.chunks.split_at_mut(last_index);
+            let (chunk, last) = (&mut head[chunk_index], &mut tail[0]);
+
+            let last_row = last.len() - 1;
+            let entity = last.entities[last_row];
+            last.swap_remove_into(last_row, Some(&mut *chunk));
+            chunk.entities.push(entity);
+
+            moved.push((entity, chunk_index, chunk.len() - 1));
+        }
+
+        if self.chunks[last_index].len() == 0 {
+            self.chunks.pop();
+        }
+    }
+}
+
+/// The storage of the `ArchetypeModule`.
+#[derive(Debug, Clone)]
+pub struct Archetypes {
+    indices: FnvHashMap<ComponentType, ComponentIndex>,
+    component_types: Vec<ComponentType>,
+    prototypes: Vec<Box<Column>>,
+    archetypes: Vec<Archetype>,
+    signatures: FnvHashMap<Signature, ArchetypeIndex>,
+    locations: VecMap<Location>,
+}
+
+impl Archetypes {
+    /// Constructs a new empty `Archetypes` storage.
+    pub fn new() -> Self {
+        Archetypes {
+            indices: FnvHashMap::default(),
+            component_types: Vec::new(),
+            prototypes: Vec::new(),
+            archetypes: Vec::new(),
+            signatures: FnvHashMap::default(),
+            locations: VecMap::new(),
+        }
+    }
+
+    /// Registers the component `C`, it is a no-op if it is already registered.
+    pub fn register<C: ArchetypeComponent>(&mut self) {
+        let component_type = ComponentType::of::<C>();
+
+        if !self.indices.contains_key(&component_type) {
+            self.indices.insert(component_type, self.component_types.len());
+            self.component_types.push(component_type);
+            self.prototypes.push(Box::new(ColumnVec::<C>(Vec::new())));
+        }
+    }
+
+    /// Associates the component `C` to the entity.
+    ///
+    /// The entity is moved to the archetype including `C` if it did not have it yet,
+    /// in which case it returns true.
+    pub(crate) fn insert<C: ArchetypeComponent>(&mut self, entity: Id, component: C) -> bool {
+        let index = self.index_of::<C>();
+
+        match self.locations.get(entity as usize).cloned() {
+            Some(location) => {
+                if self.archetypes[location.archetype].signature.contains(index) {
+                    self.chunk_mut(location).set(location.row, component);
+                    return false;
+                }
+
+                let signature = self.archetypes[location.archetype].signature.with(index);
+                let target = self.archetype_for(signature);
+                let location = self.relocate(entity, location, Some(target))
+                    .expect("the entity has been moved nowhere");
+
+                self.chunk_mut(location).push(component);
+            }
+            None => {
+                let target = self.archetype_for(Signature(vec![index]));
+                let chunk_index = self.archetypes[target].free_chunk(&self.prototypes);
+
+                let location = {
+                    let chunk = &mut self.archetypes[target].chunks[chunk_index];
+                    chunk.entities.push(entity);
+                    chunk.push(component);
+
+                    Location {
+                        archetype: target,
+                        chunk: chunk_index,
+                        row: chunk.len() - 1,
+                    }
+                };
+
+                self.locations.insert(entity as usize, location);
+            }
+        }
+
+        true
+    }
+
+    /// Detaches the component `C` from the entity.
+    ///
+    /// The entity is moved to the archetype without `C`.
+    pub(crate) fn remove<C: ArchetypeComponent>(&mut self, entity: Id) {
+        let index = self.index_of::<C>();
+
+        if let Some(location) = self.locations.get(entity as usize).cloned() {
+            let signature = {
+                let signature = &self.archetypes[location.archetype].signature;
+                if !signature.contains(index) {
+                    return;
+                }
+
+                signature.without(index)
+            };
+
+            let target = if signature.is_empty() {
+                None
+            } else {
+                Some(self.archetype_for(signature))
+            };
+
+            self.relocate(entity, location, target);
+        }
+    }
+
+    /// Removes all the components of the entity.
+    pub(crate) fn remove_entity(&mut self, entity: Id) {
+        if let Some(location) = self.locations.get(entity as usize).cloned() {
+            self.relocate(entity, location, None);
+        }
+    }
+
+    /// Returns true if the component `C` is associated to the entity.
+    pub fn contains<'a, C: ArchetypeComponent>(&self, entity: Accessor<'a>) -> bool {
+        self.get::<C>(entity).is_some()
+    }
+
+    /// Returns an immutable access to the component `C` of the entity.
+    pub fn get<'a, C: ArchetypeComponent>(&self, entity: Accessor<'a>) -> Option<&C> {
+        self.locations.get(entity.index()).and_then(|location| {
+            self.archetypes[location.archetype].chunks[location.chunk]
+                .column::<C>()
+                .map(|column| &column[location.row])
+        })
+    }
+
+    /// Returns a mutable access to the component `C` of the entity.
+    pub fn get_mut<'a, C: ArchetypeComponent>(&mut self, entity: Accessor<'a>) -> Option<&mut C> {
+        match self.locations.get(entity.index()).cloned() {
+            Some(location) => {
+                self.chunk_mut(location)
+                    .column_mut::<C>()
+                    .map(|column| &mut column[location.row])
+            }
+            None => None,
+        }
+    }
+
+    /// Returns all the archetypes, including empty ones.
+    pub fn archetypes(&self) -> &[Archetype] {
+        &self.archetypes
+    }
+
+    /// An iterator over the chunks of the archetypes matched by the `filter`.
+    ///
+    /// Only the components stored in this module are taken into account.
+    pub fn chunks(&self, filter: &Filter) -> Chunks {
+        let indices = &self.indices;
+        let chunks: Vec<_> = self.archetypes
+            .iter()
+            .filter(|archetype| Self::stored_matches(indices, archetype, filter))
+            .flat_map(|archetype| archetype.chunks.iter())
+            .collect();
+
+        Chunks { inner: chunks.into_iter() }
+    }
+
+    /// A mutable iterator over the chunks of the archetypes matched by the `filter`.
+    ///
+    /// Only the components stored in this module are taken into account.
+    pub fn chunks_mut(&mut self, filter: &Filter) -> ChunksMut {
+        let indices = &self.indices;
+        let chunks: Vec<_> = self.archetypes
+            .iter_mut()
+            .filter(|archetype| Self::stored_matches(indices, archetype, filter))
+            .flat_map(|archetype| archetype.chunks.iter_mut())
+            .collect();
+
+        ChunksMut { inner: chunks.into_iter() }
+    }
+
+    /// Returns true if the `archetype` is matched by the `filter`, ignoring the components
+    /// that are not stored in this module.
+    fn stored_matches(indices: &FnvHashMap<ComponentType, ComponentIndex>,
+                      archetype: &Archetype,
+                      filter: &Filter)
+                      -> bool {
+        filter.require
+            .iter()
+            .filter(|t| indices.contains_key(t))
+            .all(|t| archetype.types.contains(t)) &&
+        !filter.reject
+            .iter()
+            .filter(|t| indices.contains_key(t))
+            .any(|t| archetype.types.contains(t))
+    }
+
+    fn index_of<C: ArchetypeComponent>(&self) -> ComponentIndex {
+        *self.indices
+            .get(&ComponentType::of::<C>())
+            .expect("the archetype component has not been registered")
+    }
+
+    #[inline]
+    fn chunk_mut(&mut self, location: Location) -> &mut Chunk {
+        &mut self.archetypes[location.archetype].chunks[location.chunk]
+    }
+
+    fn archetype_for(&mut self, signature: Signature) -> ArchetypeIndex {
+        if let Some(&index) = self.signatures.get(&signature) {
+            return index;
+        }
+
+        let types = signature.0
+            .iter()
+            .map(|&index| self.component_types[index])
+            .collect();
+
+        let index = self.archetypes.len();
+        self.archetypes.push(Archetype::new(signature.clone(), types));
+        self.signatures.insert(signature, index);
+
+        index
+    }
+
+    /// Moves the entity to the archetype `to`, or removes it if `to` is `None`.
+    ///
+    /// The components missing from the target archetype are dropped,
+    /// the new ones need to be pushed by the caller.
+    fn relocate(&mut self,
+                entity: Id,
+                from: Location,
+                to: Option<ArchetypeIndex>)
+                -> Option<Location> {
+        let mut moved = Vec::new();
+
+        let location = match to {
+            Some(to) => {
+                let chunk_index = self.archetypes[to].free_chunk(&self.prototypes);
+                let (source, target) = pair_mut(&mut self.archetypes, from.archetype, to);
+
+                let destination = &mut target.chunks[chunk_index];
+                source.take_row(from.chunk, from.row, Some(&mut *destination), &mut moved);
+                destination.entities.push(entity);
+
+                Some(Location {
+                    archetype: to,
+                    chunk: chunk_index,
+                    row: destination.len() - 1,
+                })
+            }
+            None => {
+                self.archetypes[from.archetype].take_row(from.chunk, from.row, None, &mut moved);
+                None
+            }
+        };
+
+        for (moved_entity, chunk, row) in moved {
+            self.locations.insert(moved_entity as usize,
+                                  Location {
+                                      archetype: from.archetype,
+                                      chunk: chunk,
+                                      row: row,
+                                  });
+        }
+
+        match location {
+            Some(location) => {
+                self.locations.insert(entity as usize, location);
+            }
+            None => {
+                self.locations.remove(entity as usize);
+            }
+        }
+
+        location
+    }
+}
+
+impl Default for Archetypes {
+    fn default() -> Self {
+        Archetypes::new()
+    }
+}
+
+/// Borrows two different elements of a slice mutably.
+fn pair_mut<T>(slice: &mut [T], first: usize, second: usize) -> (&mut T, &mut T) {
+    assert!(first != second);
+
+    if first < second {
+        let (head, tail) = slice.split_at_mut(second);
+        (&mut head[first], &mut tail[0])
+    } else {
+        let (head, tail) = slice.split_at_mut(first);
+        (&mut tail[0], &mut head[second])
+    }
+}
+
+/// An iterator over chunks.
+pub struct Chunks<'a> {
+    inner: vec::IntoIter<&'a Chunk>,
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a Chunk;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+/// A mutable iterator over chunks.
+pub struct ChunksMut<'a> {
+    inner: vec::IntoIter<&'a mut Chunk>,
+}
+
+impl<'a> Iterator for ChunksMut<'a> {
+    type Item = &'a mut Chunk;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ecs::entity::Accessor;
+    use ecs::group::Filter;
+    use ecs::policy::Id;
+    use modules::transform::Transform;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Position(usize);
+    derive_archetype_component!(Position);
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Velocity(usize);
+    derive_archetype_component!(Velocity);
+
+    fn new_archetypes() -> Archetypes {
+        let mut archetypes = Archetypes::new();
+        archetypes.register::<Position>();
+        archetypes.register::<Velocity>();
+
+        archetypes
+    }
+
+    fn accessor<'a>(entity: Id) -> Accessor<'a> {
+        unsafe { Accessor::new_unchecked(entity) }
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut archetypes = new_archetypes();
+
+        assert_eq!(archetypes.insert(0, Position(0)), true);
+        assert_eq!(archetypes.get::<Position>(accessor(0)), Some(&Position(0)));
+        assert_eq!(archetypes.get::<Velocity>(accessor(0)), None);
+
+        assert_eq!(archetypes.insert(0, Position(1)), false);
+        assert_eq!(archetypes.get_mut::<Position>(accessor(0)), Some(&mut Position(1)));
+    }
+
+    #[test]
+    fn test_insert_moves_to_archetype() {
+        let mut archetypes = new_archetypes();
+
+        archetypes.insert(0, Position(0));
+        archetypes.insert(1, Position(1));
+        archetypes.insert(0, Velocity(0));
+
+        assert_eq!(archetypes.get::<Position>(accessor(0)), Some(&Position(0)));
+        assert_eq!(archetypes.get::<Velocity>(accessor(0)), Some(&Velocity(0)));
+        assert_eq!(archetypes.get::<Position>(accessor(1)), Some(&Position(1)));
+
+        let lens: Vec<_> = archetypes.archetypes().iter().map(Archetype::len).collect();
+        assert_eq!(lens, vec![1, 1]);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut archetypes = new_archetypes();
+
+        archetypes.insert(0, Position(0));
+        archetypes.insert(0, Velocity(0));
+        archetypes.remove::<Velocity>(0);
+
+        assert_eq!(archetypes.get::<Position>(accessor(0)), Some(&Position(0)));
+        assert_eq!(archetypes.get::<Velocity>(accessor(0)), None);
+
+        archetypes.remove::<Position>(0);
+        assert_eq!(archetypes.get::<Position>(accessor(0)), None);
+    }
+
+    #[test]
+    fn test_remove_entity() {
+        let mut archetypes = new_archetypes();
+
+        archetypes.insert(0, Position(0));
+        archetypes.insert(1, Position(1));
+        archetypes.remove_entity(0);
+
+        assert_eq!(archetypes.get::<Position>(accessor(0)), None);
+        assert_eq!(archetypes.get::<Position>(accessor(1)), Some(&Position(1)));
+    }
+
+    #[test]
+    fn test_chunks_stay_packed() {
+        let mut archetypes = new_archetypes();
+        let count = CHUNK_CAPACITY + 2;
+
+        for entity in 0..count {
+            archetypes.insert(entity as Id, Position(entity));
+        }
+
+        archetypes.remove_entity(0);
+        archetypes.remove_entity(1);
+
+        let lens: Vec<_> = archetypes.archetypes()[0].chunks().iter().map(Chunk::len).collect();
+        assert_eq!(lens, vec![CHUNK_CAPACITY]);
+
+        for entity in 2..count {
+            assert_eq!(archetypes.get::<Position>(accessor(entity as Id)),
+                       Some(&Position(entity)));
+        }
+    }
+
+    #[test]
+    fn test_chunks_filter() {
+        let mut archetypes = new_archetypes();
+
+        archetypes.insert(0, Position(0));
+        archetypes.insert(1, Position(1));
+        archetypes.insert(1, Velocity(1));
+
+        let moving = Filter::new().require::<Position>().require::<Velocity>();
+        let entities: Vec<_> = archetypes.chunks(&moving).flat_map(Chunk::entities).collect();
+        assert_eq!(entities, vec![accessor(1)]);
+
+        let still = Filter::new().require::<Position>().reject::<Velocity>();
+        let entities: Vec<_> = archetypes.chunks(&still).flat_map(Chunk::entities).collect();
+        assert_eq!(entities, vec![accessor(0)]);
+    }
+
+    #[test]
+    fn test_chunks_mixed_filter() {
+        let mut archetypes = new_archetypes();
+
+        archetypes.insert(0, Position(0));
+        archetypes.insert(1, Position(1));
+        archetypes.insert(1, Velocity(1));
+
+        let filter = Filter::new().require::<Position>().require::<Transform>();
+        let entities: Vec<_> = archetypes.chunks(&filter).flat_map(Chunk::entities).collect();
+        assert_eq!(entities, vec![accessor(0), accessor(1)]);
+
+        let filter = Filter::new().require::<Position>().reject::<Velocity>().reject::<Transform>();
+        let lens: Vec<_> = archetypes.chunks_mut(&filter).map(|chunk| chunk.len()).collect();
+        assert_eq!(lens, vec![1]);
+    }
+
+    #[test]
+    fn test_columns_mut() {
+        let mut archetypes = new_archetypes();
+
+        archetypes.insert(0, Position(0));
+        archetypes.insert(0, Velocity(2));
+
+        let filter = Filter::new().require::<Position>().require::<Velocity>();
+        for chunk in archetypes.chunks_mut(&filter) {
+            let mut columns = chunk.columns_mut();
+            let positions = columns.take::<Position>().unwrap();
+            let velocities = columns.take::<Velocity>().unwrap();
+            assert!(columns.take::<Position>().is_none());
+
+            for (position, velocity) in positions.iter_mut().zip(velocities.iter()) {
+                position.0 += velocity.0;
+            }
+        }
+
+        assert_eq!(archetypes.get::<Position>(accessor(0)), Some(&Position(2)));
+    }
+}
@@ -7,7 +7,7 @@ use ecs::entity::Entities;
 use ecs::Context;
 use ecs::module::{Module, HasComponent};
 use ecs::module::{Component, Template, ComponentType};
-use ecs::module::{StorageReadGuard, StorageWriteGuard};
+use ecs::module::{StorageReadGuard, StorageWriteGuard, ModuleSnapshot};
 use fnv::FnvHashMap;
 use rayon;
 use std::fmt::Debug;
@@ -64,6 +64,29 @@ impl<Cx: Send> Module<Cx> for DataModule {
             }
         });
     }
+
+    fn snapshot(&self) -> Option<ModuleSnapshot> {
+        let storages: FnvHashMap<ComponentType, ModuleSnapshot> = self.handlers
+            .iter()
+            .filter_map(|(&component_type, handler)| {
+                handler.snapshot().map(|snapshot| (component_type, snapshot))
+            })
+            .collect();
+
+        Some(Box::new(storages))
+    }
+
+    fn restore(&mut self, snapshot: &ModuleSnapshot) {
+        let snapshot: &Any = &**snapshot;
+
+        if let Some(storages) = snapshot.downcast_ref::<FnvHashMap<ComponentType, ModuleSnapshot>>() {
+            for (component_type, storage) in storages {
+                if let Some(handler) = self.handlers.get_mut(component_type) {
+                    handler.restore(&**storage);
+                }
+            }
+        }
+    }
 }
 
 impl<C: DataComponent + Component> HasComponent<C> for DataModule {
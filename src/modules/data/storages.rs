@@ -2,7 +2,7 @@ use std::any::Any;
 use mopa;
 use ecs::entity::Accessor;
 use ecs::state::CommitArgs;
-use ecs::module::StorageLock;
+use ecs::module::{StorageLock, ModuleSnapshot};
 use std::fmt::Debug;
 use super::DataComponent;
 
@@ -15,6 +15,21 @@ pub trait Storage: Any + Debug + Send + Sync {
 
     fn insert<'a>(&mut self, accessor: Accessor<'a>, component: Self::Component) -> bool;
     fn remove<'a>(&mut self, accessor: Accessor<'a>);
+
+    /// Returns a copy of the storage, used to roll the state back.
+    ///
+    /// Storages that can not be copied return `None`, which is the default.
+    fn snapshot(&self) -> Option<Self>
+        where Self: Sized
+    {
+        None
+    }
+
+    /// Restores the storage from a copy made by `Storage::snapshot`.
+    fn restore(&mut self, _snapshot: &Self)
+        where Self: Sized
+    {
+    }
 }
 
 /// Represents a storage Handler.
@@ -22,6 +37,8 @@ pub trait Storage: Any + Debug + Send + Sync {
 /// This is used internally to abstract component storages.
 pub trait Handler: mopa::Any + Send + Debug + Sync {
     fn commit(&mut self, args: &CommitArgs);
+    fn snapshot(&self) -> Option<ModuleSnapshot>;
+    fn restore(&mut self, snapshot: &Any);
 }
 mopafy!(Handler);
 
@@ -56,4 +73,17 @@ impl<S: Storage> Handler for StorageHandler<S> {
             storage.remove(accessor);
         }
     }
+
+    fn snapshot(&self) -> Option<ModuleSnapshot> {
+        self.storage
+            .read()
+            .snapshot()
+            .map(|storage| Box::new(storage) as ModuleSnapshot)
+    }
+
+    fn restore(&mut self, snapshot: &Any) {
+        if let Some(storage) = snapshot.downcast_ref::<S>() {
+            self.storage.write().restore(storage);
+        }
+    }
 }
\ No newline at end of file
@@ -1,3 +1,5 @@
 pub mod data;
 pub mod storages;
 pub mod transform;
+#[macro_use]
+pub mod archetype;
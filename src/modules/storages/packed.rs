@@ -129,6 +129,14 @@ impl<V> Storage for Packed<V>
     fn remove<'a>(&mut self, key: Accessor<'a>) {
         Packed::<V>::remove(self, key);
     }
+
+    fn snapshot(&self) -> Option<Self> {
+        Some(self.clone())
+    }
+
+    fn restore(&mut self, snapshot: &Self) {
+        self.clone_from(snapshot);
+    }
 }
 
 impl<V> Default for Packed<V> {
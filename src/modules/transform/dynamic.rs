@@ -31,6 +31,22 @@ impl Transform {
     pub fn scale_vector(&self, v: Vector2<f32>) -> Vector2<f32> {
         Vector2::new(v.x * self.scale.x, v.y * self.scale.y)
     }
+
+    /// Blends `self` with `other` by `alpha`, from 0 for `self` to 1 for `other`.
+    ///
+    /// The rotation follows the shortest arc.
+    pub fn lerp(&self, other: &Transform, alpha: f32) -> Transform {
+        let mut rotation = (other.rotation - self.rotation).normalize();
+        if rotation > Rad::turn_div_2() {
+            rotation = rotation - Rad::full_turn();
+        }
+
+        Transform {
+            position: self.position + (other.position - self.position) * alpha,
+            rotation: (self.rotation + rotation * alpha).normalize(),
+            scale: self.scale + (other.scale - self.scale) * alpha,
+        }
+    }
 }
 
 impl Template for Transform {}
@@ -80,9 +96,11 @@ pub struct TransformTemplate {
 
 impl Template for TransformTemplate {}
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Instance {
     world: Transform,
+    // The world transform before the current fixed update
+    previous_world: Transform,
     local: Transform,
 
     parent: Option<usize>,
@@ -99,6 +117,7 @@ type InstanceIndex = usize;
 
 const INITIAL_STACK_CAPACITY: usize = 15;
 
+#[derive(Clone)]
 pub struct TransformStorage {
     instances: Vec<Instance>,
     entity_to_instance: VecMap<InstanceIndex>,
@@ -121,12 +140,13 @@ impl TransformStorage {
             .and_then(|entity_ref| entities.upgrade(entity_ref))
             .and_then(|accessor| self.entity_to_instance.get(accessor.id() as usize).cloned());
 
-        let instance_index = match self.entity_to_instance.entry(entity as usize) {
+        let (instance_index, inserted) = match self.entity_to_instance.entry(entity as usize) {
             Entry::Vacant(vacant) => {
                 let index = self.instances.len();
 
                 self.instances.push(Instance {
                     world: Transform::one(),
+                    previous_world: Transform::one(),
                     local: Transform::one(),
                     parent: None,
                     first_child: None,
@@ -135,13 +155,19 @@ impl TransformStorage {
                     entity: entity,
                 });
 
-                *vacant.insert(index)
+                (*vacant.insert(index), true)
             }
-            Entry::Occupied(mut occupied) => *occupied.get_mut(),
+            Entry::Occupied(mut occupied) => (*occupied.get_mut(), false),
         };
 
         self.set_parent_for_instance(instance_index, parent_index);
         self.set_local_transform_impl(entity, template.transform);
+
+        // A new transform is not interpolated from the origin.
+        if inserted {
+            let instance = &mut self.instances[instance_index];
+            instance.previous_world = instance.world;
+        }
     }
 
     fn remove(&mut self, entity: Id) {
@@ -192,6 +218,33 @@ impl TransformStorage {
             .map(|&index| self.instances[index].world)
     }
 
+    /// Returns the world transform saved by the last call to `store_previous`.
+    pub fn previous_world(&self, entity: Accessor) -> Option<Transform> {
+        self.entity_to_instance
+            .get(entity.id() as usize)
+            .map(|&index| self.instances[index].previous_world)
+    }
+
+    /// Returns the world transform blended from the previous one to the current one by `alpha`,
+    /// such as `FrameClock::alpha`.
+    pub fn interpolated_world(&self, entity: Accessor, alpha: f32) -> Option<Transform> {
+        self.entity_to_instance
+            .get(entity.id() as usize)
+            .map(|&index| {
+                let instance = &self.instances[index];
+                instance.previous_world.lerp(&instance.world, alpha)
+            })
+    }
+
+    /// Saves the current world transforms as the previous ones.
+    ///
+    /// The module calls it before each fixed update, so that renderers can blend the transforms
+    /// between the last two fixed updates.
+    pub fn store_previous(&mut self) {
+        for instance in &mut self.instances {
+            instance.previous_world = instance.world;
+        }
+    }
 
     pub fn parent(&self, entity: Accessor) -> Option<Accessor> {
         if let Some(&instance_index) = self.entity_to_instance.get(entity.id() as usize) {
@@ -781,6 +834,71 @@ mod tests {
         assert_eq!(cursor.next(&storage), None);
     }
 
+    #[test]
+    fn test_lerp_shortest_rotation() {
+        let from = Transform {
+            position: Point2::new(0., 0.),
+            rotation: Rad::from(Deg(350.)),
+            scale: Vector2::new(1., 1.),
+        };
+
+        let to = Transform {
+            position: Point2::new(4., 2.),
+            rotation: Rad::from(Deg(30.)),
+            scale: Vector2::new(3., 1.),
+        };
+
+        let expected = Transform {
+            position: Point2::new(2., 1.),
+            rotation: Rad::from(Deg(10.)),
+            scale: Vector2::new(2., 1.),
+        };
+
+        assert_relative_eq!(from.lerp(&to, 0.5), expected, epsilon = 1e-5);
+        assert_ulps_eq!(from.lerp(&to, 0.), from);
+        assert_ulps_eq!(from.lerp(&to, 1.), to);
+    }
+
+    #[test]
+    fn test_interpolated_world() {
+        let mut storage = TransformStorage::new();
+        let mut entities = Entities::new();
+
+        let (entity, accessor) = spawn_entity(&mut entities);
+        let transform = Transform {
+            position: Point2::new(2., 0.),
+            rotation: Rad(0.),
+            scale: Vector2::new(1., 1.),
+        };
+
+        storage.insert(&entities,
+                       entity.id(),
+                       TransformTemplate {
+                           transform: transform,
+                           parent: None,
+                       });
+
+        transform_approx_eq!(storage.previous_world(accessor), Some(transform));
+
+        storage.store_previous();
+        let moved = Transform {
+            position: Point2::new(6., 0.),
+            rotation: Rad(0.),
+            scale: Vector2::new(1., 1.),
+        };
+        storage.set_local(accessor, moved);
+
+        let expected = Transform {
+            position: Point2::new(3., 0.),
+            rotation: Rad(0.),
+            scale: Vector2::new(1., 1.),
+        };
+
+        transform_approx_eq!(storage.previous_world(accessor), Some(transform));
+        transform_approx_eq!(storage.interpolated_world(accessor, 0.25), Some(expected));
+        transform_approx_eq!(storage.interpolated_world(accessor, 1.), Some(moved));
+    }
+
     fn spawn_entity_with_children<'a>(entities: &'a mut Entities,
                                       storage: &mut TransformStorage,
                                       children_count: usize)
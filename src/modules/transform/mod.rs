@@ -6,6 +6,8 @@ use modules::storages::packed::{self, Packed};
 use ecs::entity::{Accessor, EntityRef};
 use ecs::state::CommitArgs;
 use ecs::module::{Module, StorageLock, StorageReadGuard, StorageWriteGuard, Template};
+use ecs::module::ModuleSnapshot;
+use std::any::Any;
 use ecs::Context;
 use ecs::policy::Id;
 use std::ops::Index;
@@ -16,6 +18,7 @@ pub struct StaticTransform(Transform);
 
 impl Template for StaticTransform {}
 
+#[derive(Clone)]
 pub struct StaticTransformStorage {
     transforms: Packed<Transform>,
 }
@@ -103,6 +106,27 @@ impl<Cx: Context> Module<Cx> for TransformModule {
         statics.commit(args);
         dynamics.commit(args);
     }
+
+    fn begin_fixed_update(&mut self, _cx: &mut Cx) {
+        self.dynamics.write().store_previous();
+    }
+
+    fn snapshot(&self) -> Option<ModuleSnapshot> {
+        let statics = self.statics.read().clone();
+        let dynamics = self.dynamics.read().clone();
+
+        Some(Box::new((statics, dynamics)))
+    }
+
+    fn restore(&mut self, snapshot: &ModuleSnapshot) {
+        let snapshot: &Any = &**snapshot;
+
+        if let Some(&(ref statics, ref dynamics)) =
+               snapshot.downcast_ref::<(StaticTransformStorage, TransformStorage)>() {
+            *self.statics.write() = statics.clone();
+            *self.dynamics.write() = dynamics.clone();
+        }
+    }
 }
 
 derive_component!(Transform, TransformTemplate, TransformModule);
@@ -110,3 +134,70 @@ impl_has_component!(Transform, TransformStorage, TransformModule => dynamics);
 
 derive_component!(StaticTransform, StaticTransform, TransformModule);
 impl_has_component!(StaticTransform, StaticTransformStorage, TransformModule => statics);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maths::{Point2, Rad, Vector2};
+    use ecs::entity::EntityRef;
+    use ecs::state::{State, StateBuilder};
+
+    struct TestContext;
+    impl Context for TestContext {}
+
+    fn transform(x: f32) -> Transform {
+        Transform {
+            position: Point2::new(x, 0.),
+            rotation: Rad(0.),
+            scale: Vector2::new(1., 1.),
+        }
+    }
+
+    fn spawn(state: &mut State<TestContext>, x: f32) -> EntityRef {
+        let mut entity_ref = None;
+        state.update().commit(&mut TestContext, |_, commit, _| {
+            let template = TransformTemplate {
+                transform: transform(x),
+                parent: None,
+            };
+            entity_ref = Some(commit.spawn_later().set::<Transform>(template).entity_ref());
+        });
+
+        entity_ref.unwrap()
+    }
+
+    fn move_to(state: &mut State<TestContext>, entity_ref: EntityRef, x: f32) {
+        state.fixed_update(&mut TestContext).commit(&mut TestContext, |state, _, _| {
+            let accessor = state.accessor(entity_ref).unwrap();
+            state.write::<Transform>().set_local(accessor, transform(x));
+        });
+    }
+
+    fn interpolated(state: &State<TestContext>, entity_ref: EntityRef, alpha: f32) -> Transform {
+        let accessor = state.accessor(entity_ref).unwrap();
+        state.read::<Transform>().interpolated_world(accessor, alpha).unwrap()
+    }
+
+    #[test]
+    fn test_fixed_update_interpolation() {
+        let mut builder = StateBuilder::<TestContext>::new();
+        builder.register_component::<Transform>()
+            .register_component::<StaticTransform>()
+            .register_module(TransformModule::new());
+        let mut state = builder.build();
+
+        let entity_ref = spawn(&mut state, 2.);
+        assert_ulps_eq!(interpolated(&state, entity_ref, 0.5), transform(2.));
+
+        move_to(&mut state, entity_ref, 6.);
+        assert_ulps_eq!(interpolated(&state, entity_ref, 0.25), transform(3.));
+
+        // The other updates keep the previous transforms
+        state.update().commit(&mut TestContext, |_, _, _| {});
+        assert_ulps_eq!(interpolated(&state, entity_ref, 0.25), transform(3.));
+
+        move_to(&mut state, entity_ref, 10.);
+        assert_ulps_eq!(interpolated(&state, entity_ref, 0.5), transform(8.));
+        assert_ulps_eq!(interpolated(&state, entity_ref, 1.), transform(10.));
+    }
+}